@@ -1,13 +1,15 @@
 use std::io;
 use std::path::Path;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
-use crossterm::event::{self, Event, KeyEventKind};
+use crossterm::event::{self, Event, KeyEvent, KeyEventKind};
 use ratatui::{backend::CrosstermBackend, Terminal};
 
-use crate::dir::DirectoryState;
+use crate::burst::BurstDetector;
+use crate::dir::{self, DirectoryState};
 use crate::editor::Editor;
-use crate::input::{handle_key_event, InputResult};
+use crate::idle::IdleTimer;
+use crate::input::{handle_key_event, handle_paste, InputResult};
 use crate::ui;
 
 /// The main application struct
@@ -22,8 +24,17 @@ pub struct App {
     pub focus_on_explorer: bool,
     /// Ctrl+w pressed, waiting for second key (w) to toggle focus
     pub pending_ctrl_w: bool,
-    /// Space pressed in normal mode, waiting for 'e' to toggle sidebar / open dir
+    /// Space pressed in normal mode, waiting for 'e' (toggle sidebar / open dir) or 'f'
+    /// (reveal the current file)
     pub pending_space_e: bool,
+    /// Key cheat sheet overlay shown (F1); any key dismisses it
+    pub help_visible: bool,
+    /// Source of the current time (replaceable so time-based behavior can be driven in tests)
+    pub clock: fn() -> Instant,
+    /// Time since the last key, for the cursor-hold hook
+    idle: IdleTimer,
+    /// Spacing of keys, to spot unbracketed pastes (`pasteburst`)
+    burst: BurstDetector,
     /// Whether the application is still running
     running: bool,
 }
@@ -31,43 +42,48 @@ pub struct App {
 impl App {
     /// Create a new application with an empty buffer
     pub fn new() -> Self {
-        Self {
-            editor: Editor::new(),
-            directory_state: None,
-            sidebar_visible: true,
-            focus_on_explorer: false,
-            pending_ctrl_w: false,
-            pending_space_e: false,
-            running: true,
-        }
+        Self::from_parts(Editor::new(), None)
     }
 
     /// Create a new application with a file loaded
-    pub fn with_file(path: &str) -> io::Result<Self> {
-        let editor = Editor::with_file(path)?;
-        Ok(Self {
-            editor,
-            directory_state: None,
-            sidebar_visible: true,
-            focus_on_explorer: false,
-            pending_ctrl_w: false,
-            pending_space_e: false,
-            running: true,
-        })
+    pub fn with_file(path: &str, max_size: Option<u64>) -> io::Result<Self> {
+        let editor = Editor::with_file(path, max_size)?;
+        Ok(Self::from_parts(editor, None))
+    }
+
+    /// Create a new application editing a file that does not exist yet
+    pub fn with_new_file(path: &str) -> Self {
+        Self::from_parts(Editor::with_new_file(path), None)
+    }
+
+    /// Create a new application editing the text on stdin (`vibeVim -`)
+    pub fn with_stdin() -> io::Result<Self> {
+        let editor = Editor::with_reader(io::stdin().lock())?;
+        Ok(Self::from_parts(editor, None))
     }
 
     /// Create a new application with a directory (file explorer sidebar).
     pub fn with_directory(path: &Path) -> io::Result<Self> {
         let directory_state = DirectoryState::new(path)?;
-        Ok(Self {
-            editor: Editor::new(),
-            directory_state: Some(directory_state),
+        Ok(Self::from_parts(Editor::new(), Some(directory_state)))
+    }
+
+    /// Build an application around an editor with default UI state. Focus starts in the
+    /// file explorer when there is one.
+    fn from_parts(editor: Editor, directory_state: Option<DirectoryState>) -> Self {
+        Self {
+            editor,
+            focus_on_explorer: directory_state.is_some(),
+            directory_state,
             sidebar_visible: true,
-            focus_on_explorer: true,
             pending_ctrl_w: false,
             pending_space_e: false,
+            help_visible: false,
+            clock: Instant::now,
+            idle: IdleTimer::new(Instant::now()),
+            burst: BurstDetector::default(),
             running: true,
-        })
+        }
     }
 
     /// Toggle the file explorer sidebar visibility (when directory_state is Some).
@@ -77,6 +93,17 @@ impl App {
         }
     }
 
+    /// Move focus between the editor and the file explorer (Ctrl+w w, :focus)
+    pub fn toggle_explorer_focus(&mut self) {
+        if self.focus_on_explorer {
+            self.focus_on_explorer = false;
+        } else if self.directory_state.is_some() && self.sidebar_visible {
+            self.focus_on_explorer = true;
+        } else {
+            self.editor.set_status("No file explorer open");
+        }
+    }
+
     /// Toggle sidebar visibility if a directory is open; otherwise open current directory in the sidebar.
     pub fn toggle_sidebar_or_open_current_dir(&mut self) {
         if self.directory_state.is_some() {
@@ -110,26 +137,106 @@ impl App {
         }
     }
 
+    /// Open the explorer on the current file's directory with the file selected (`:reveal`).
+    /// `[No Name]` buffers show the working directory.
+    pub fn reveal_current_file(&mut self) {
+        let file = self.editor.current_buffer().file_path.clone();
+        let cwd = match std::env::current_dir() {
+            Ok(cwd) => cwd,
+            Err(e) => {
+                self.editor.set_status(&format!("{}", e));
+                return;
+            }
+        };
+        let path = dir::reveal_dir(file.as_deref(), &cwd);
+        let opened = match self.directory_state.as_mut() {
+            Some(state) => state.file_explorer_mut().set_cwd(&path),
+            None => DirectoryState::new(&path).map(|state| self.directory_state = Some(state)),
+        };
+        if let Err(e) = opened {
+            self.editor.set_status(&format!("{}", e));
+            return;
+        }
+        if let (Some(state), Some(file)) = (self.directory_state.as_mut(), file.as_deref()) {
+            state.select(file);
+        }
+        self.sidebar_visible = true;
+        self.focus_on_explorer = true;
+    }
+
+    /// Open the files marked in the explorer (or the selected one when none are) into
+    /// buffers, keeping the focus in the explorer (`t`)
+    pub fn open_explorer_files(&mut self) {
+        let Some(state) = self.directory_state.as_mut() else {
+            return;
+        };
+        let mut paths = state.take_marked();
+        if paths.is_empty() {
+            paths.push(state.file_explorer().current().path().clone());
+        }
+        self.editor.open_files(&paths);
+    }
+
+    /// Show `dir` in the explorer, if one is open (after `:cd`)
+    pub fn follow_directory(&mut self, dir: &Path) {
+        if let Some(state) = self.directory_state.as_mut() {
+            if let Err(e) = state.file_explorer_mut().set_cwd(dir) {
+                self.editor.set_status(&format!("{}", e));
+            }
+        }
+    }
+
+    /// Fire the cursor-hold hook once `updatetime` ms have passed without a key
+    pub fn check_idle(&mut self) {
+        let timeout = Duration::from_millis(self.editor.options.updatetime as u64);
+        if self.idle.check((self.clock)(), timeout) {
+            self.editor.cursor_hold();
+        }
+    }
+
+    /// Handle a key press, noting its time for the idle and paste-burst detection
+    fn handle_key(&mut self, key: KeyEvent) {
+        let now = (self.clock)();
+        self.idle.input(now);
+        let in_burst = self.burst.key(now);
+        self.editor.key_burst = in_burst && self.editor.options.pasteburst;
+        match handle_key_event(self, key) {
+            InputResult::Continue => {}
+            InputResult::Exit => self.running = false,
+        }
+    }
+
     /// Run the main application loop
     pub fn run(&mut self, terminal: &mut Terminal<CrosstermBackend<io::Stdout>>) -> io::Result<()> {
         while self.running {
-            // Render the UI
+            // Render the UI, from scratch if the screen may be out of sync
+            if self.editor.redraw_pending {
+                self.editor.redraw_pending = false;
+                terminal.clear()?;
+            }
             terminal.draw(|frame| ui::render(frame, self))?;
 
             // Poll for events with a timeout
             if event::poll(Duration::from_millis(100))? {
                 // Handle the event
-                if let Event::Key(key) = event::read()? {
+                match event::read()? {
                     // Handle key press and repeat (not release)
-                    if matches!(key.kind, KeyEventKind::Press | KeyEventKind::Repeat) {
-                        match handle_key_event(self, key) {
-                            InputResult::Continue => {}
-                            InputResult::Exit => self.running = false,
-                        }
+                    Event::Key(key)
+                        if matches!(key.kind, KeyEventKind::Press | KeyEventKind::Repeat) =>
+                    {
+                        self.handle_key(key);
                     }
+                    Event::Paste(text) => {
+                        self.idle.input((self.clock)());
+                        handle_paste(self, &text);
+                    }
+                    _ => {}
                 }
+            } else {
+                self.check_idle();
             }
         }
+        self.editor.remember_position();
 
         Ok(())
     }
@@ -140,3 +247,76 @@ impl Default for App {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crossterm::event::{KeyCode, KeyModifiers};
+    use std::cell::Cell;
+
+    thread_local! {
+        static NOW: Cell<Option<Instant>> = const { Cell::new(None) };
+    }
+
+    /// A clock that stands still until a test moves it with `advance`
+    fn fake_clock() -> Instant {
+        NOW.with(|now| *now.get().get_or_insert_with(Instant::now))
+    }
+
+    fn advance(ms: u64) {
+        let next = fake_clock() + Duration::from_millis(ms);
+        NOW.with(|now| now.set(Some(next)));
+    }
+
+    #[test]
+    fn cursor_hold_fires_after_updatetime_on_the_injected_clock() {
+        let mut app = App::new();
+        app.clock = fake_clock;
+        app.idle = IdleTimer::new(fake_clock());
+        app.editor.options.apply("updatetime=500").unwrap();
+        app.editor.options.apply("cursorhold=charinfo").unwrap();
+        app.editor.current_buffer_mut().insert_str(0, 0, "a");
+
+        advance(499);
+        app.check_idle();
+        assert_eq!(app.editor.status_message, None);
+        advance(1);
+        app.check_idle();
+        assert_eq!(app.editor.status_message.as_deref(), Some("<a> 97, Hex 61, Octal 141"));
+    }
+
+    fn type_text(app: &mut App, text: &str, gap_ms: u64) {
+        for c in text.chars() {
+            advance(gap_ms);
+            app.handle_key(KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE));
+        }
+    }
+
+    #[test]
+    fn fast_keys_skip_abbreviations_and_autopairs_with_pasteburst() {
+        let mut app = App::new();
+        app.clock = fake_clock;
+        app.editor.options.apply("autopairs").unwrap();
+        app.editor.options.apply("pasteburst").unwrap();
+        app.editor.command_buffer = "iabbrev teh the".to_string();
+        app.editor.execute_command();
+        type_text(&mut app, "i", 100);
+        // Pasted: arrives 1 ms apart
+        type_text(&mut app, "teh (", 1);
+        assert_eq!(app.editor.current_buffer().line(0).unwrap().to_string(), "teh (");
+        // Typed: 100 ms apart
+        type_text(&mut app, " teh ", 100);
+        assert_eq!(app.editor.current_buffer().line(0).unwrap().to_string(), "teh ( the ");
+    }
+
+    #[test]
+    fn fast_keys_expand_as_usual_without_pasteburst() {
+        let mut app = App::new();
+        app.clock = fake_clock;
+        app.editor.options.apply("autopairs").unwrap();
+        type_text(&mut app, "i", 100);
+        type_text(&mut app, "(", 1);
+        type_text(&mut app, "(", 1);
+        assert_eq!(app.editor.current_buffer().line(0).unwrap().to_string(), "(())");
+    }
+}
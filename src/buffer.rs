@@ -1,7 +1,11 @@
 use ropey::Rope;
-use std::fs::File;
-use std::io::{BufReader, BufWriter, Error as IoError};
-use std::path::PathBuf;
+use std::fs::{File, Metadata, OpenOptions};
+use std::io::{BufReader, BufWriter, Error as IoError, ErrorKind, Read, Write};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+use crate::filetype;
+use crate::history::{Edit, History};
 
 /// A text buffer backed by a Rope data structure for efficient editing.
 pub struct Buffer {
@@ -11,6 +15,24 @@ pub struct Buffer {
     pub file_path: Option<PathBuf>,
     /// Whether the buffer has been modified since last save
     pub modified: bool,
+    /// The file does not exist on disk yet; it is created on the first write
+    pub is_new: bool,
+    /// The file started with a UTF-8 byte order mark; it is kept out of the text and written
+    /// back on save (`:set nobomb` drops it)
+    pub has_bom: bool,
+    /// The file ended with a line break when read (new buffers: true). Saving keeps the file
+    /// that way: a missing final line break is only added back when this is set
+    pub eol: bool,
+    /// Detected (or `:set filetype=`) filetype, e.g. "rust"
+    pub filetype: Option<String>,
+    /// Positions (line, col) of recent changes, oldest first: vim's change list for g; and g,
+    pub changes: Vec<(usize, usize)>,
+    /// Current entry while walking `changes` (equal to its length when not walking)
+    pub change_idx: usize,
+    /// Incremented on every text change; lets callers cache derived data per revision
+    revision: u64,
+    /// Undo and redo steps
+    history: History,
 }
 
 impl Buffer {
@@ -20,6 +42,14 @@ impl Buffer {
             text: Rope::new(),
             file_path: None,
             modified: false,
+            is_new: false,
+            has_bom: false,
+            eol: true,
+            filetype: None,
+            changes: Vec::new(),
+            change_idx: 0,
+            revision: 0,
+            history: History::default(),
         }
     }
 
@@ -28,27 +58,91 @@ impl Buffer {
         std::fs::canonicalize(path).unwrap_or_else(|_| PathBuf::from(path))
     }
 
-    /// Load a buffer from a file
-    pub fn from_file(path: &str) -> Result<Self, IoError> {
+    /// Load a file. Files larger than `max_size` bytes are refused with an error naming both
+    /// sizes, so an accidentally opened multi-GB log doesn't hang the editor.
+    pub fn from_file(path: &str, max_size: Option<u64>) -> Result<Self, IoError> {
         let file = File::open(path)?;
-        let reader = BufReader::new(file);
-        let text = Rope::from_reader(reader)?;
-        let file_path = Some(Self::normalize_path(path));
+        if let Some(max) = max_size {
+            let size = file.metadata()?.len();
+            if size > max {
+                return Err(IoError::new(
+                    ErrorKind::InvalidData,
+                    format!(
+                        "file is too large ({}, maxfilesize is {})",
+                        format_size(size),
+                        format_size(max)
+                    ),
+                ));
+            }
+        }
+        let mut buffer = Self::from_reader(BufReader::new(file))?;
+        buffer.file_path = Some(Self::normalize_path(path));
+        buffer.detect_filetype();
+        Ok(buffer)
+    }
 
-        Ok(Self {
+    /// Read a buffer without a file name from `reader` (e.g. stdin for `vibeVim -`). A byte
+    /// order mark and a missing final line break are recorded as for files.
+    pub fn from_reader(reader: impl Read) -> Result<Self, IoError> {
+        let mut text = Rope::from_reader(reader)?;
+        let has_bom = text.get_char(0) == Some('\u{feff}');
+        if has_bom {
+            text.remove(0..1);
+        }
+
+        let mut buffer = Self {
             text,
-            file_path,
-            modified: false,
-        })
+            has_bom,
+            ..Self::new()
+        };
+        buffer.eol = buffer.has_final_newline();
+        buffer.detect_filetype();
+        Ok(buffer)
+    }
+
+    /// An empty buffer for a file that does not exist yet (written on the first `:w`)
+    pub fn new_file(path: &str) -> Self {
+        let mut buffer = Self::new();
+        buffer.file_path = Some(PathBuf::from(path));
+        buffer.is_new = true;
+        buffer.detect_filetype();
+        buffer
     }
 
-    /// Save the buffer to its associated file
-    pub fn save(&mut self) -> Result<(), IoError> {
-        if let Some(ref path) = self.file_path {
-            let file = File::create(path)?;
-            let writer = BufWriter::new(file);
-            self.text.write_to(writer)?;
+    /// Detect the filetype from the file path and the first line (shebang)
+    pub fn detect_filetype(&mut self) {
+        let first_line = self.line(0).map(|l| l.to_string());
+        self.filetype = filetype::detect(self.file_path.as_deref(), first_line.as_deref());
+    }
+
+    /// Save the buffer to its associated file. With `atomic`, the text is written to a
+    /// temporary file that then replaces the original (see `write_atomic`). With `backup`,
+    /// an existing file is first copied there; if that fails nothing is written. With
+    /// `fix_eol` (the `fixeol` option) a missing final line break is written even if the file
+    /// was read without one; `eol` only records that once the write has succeeded.
+    pub fn save(
+        &mut self,
+        atomic: bool,
+        backup: Option<&Path>,
+        fix_eol: bool,
+    ) -> Result<(), IoError> {
+        if let Some(path) = self.file_path.clone() {
+            if let Some(backup) = backup.filter(|_| path.exists()) {
+                std::fs::copy(&path, backup).map_err(|e| {
+                    IoError::new(
+                        e.kind(),
+                        format!("Cannot make backup file {}: {}", backup.display(), e),
+                    )
+                })?;
+            }
+            if !atomic || !self.write_atomic(&path, fix_eol)? {
+                let file = File::create(path)?;
+                self.write_contents(BufWriter::new(file), fix_eol)?;
+            }
+            self.eol |= fix_eol;
             self.modified = false;
+            self.is_new = false;
+            self.history.mark_saved();
             Ok(())
         } else {
             Err(IoError::new(
@@ -58,10 +152,188 @@ impl Buffer {
         }
     }
 
+    /// Re-read the file from disk, dropping unsaved changes. With `keep_undo`, replacing the
+    /// text is itself an undo step, so `u` brings the changes back; otherwise the undo history
+    /// starts over.
+    pub fn reload(&mut self, max_size: Option<u64>, keep_undo: bool) -> Result<(), IoError> {
+        let path = self.file_path.clone().ok_or_else(|| {
+            IoError::new(ErrorKind::NotFound, "No file path associated with buffer")
+        })?;
+        let disk = Self::from_file(&path.to_string_lossy(), max_size)?;
+        if keep_undo {
+            self.history.commit();
+            if self.text != disk.text {
+                self.edit(0, self.text.len_chars(), &disk.text.to_string());
+            }
+        } else {
+            self.text = disk.text;
+            self.history = History::default();
+            self.revision += 1;
+        }
+        self.has_bom = disk.has_bom;
+        self.eol = disk.eol;
+        self.is_new = false;
+        self.modified = false;
+        self.history.mark_saved();
+        self.detect_filetype();
+        Ok(())
+    }
+
+    /// Write the file contents as saved (including the BOM, if any) to `writer`. With `eol`
+    /// or `fix_eol`, a missing final line break is written too; the text itself is left alone,
+    /// so saving never adds an undo step.
+    pub fn write_contents(&self, mut writer: impl Write, fix_eol: bool) -> Result<(), IoError> {
+        if self.has_bom {
+            writer.write_all("\u{feff}".as_bytes())?;
+        }
+        self.text.write_to(&mut writer)?;
+        if (self.eol || fix_eol) && !self.has_final_newline() {
+            writer.write_all(b"\n")?;
+        }
+        writer.flush()
+    }
+
+    /// Write the file contents to a temporary file next to `path`, with the original's
+    /// permissions, and rename it over `path`, so a crash mid-write leaves the old file
+    /// intact. A symlink is followed and its target replaced. Returns false, leaving no
+    /// temporary file behind, when this is not possible here and the caller should write in
+    /// place: the file has other hard links (the rename would split them from the new
+    /// contents), the directory is not writable, or the rename fails (e.g. across devices).
+    /// A failed write is an error, since writing in place would fail the same way after
+    /// truncating the file.
+    fn write_atomic(&self, path: &Path, fix_eol: bool) -> Result<bool, IoError> {
+        let (target, original) = match std::fs::canonicalize(path) {
+            Ok(target) => {
+                let meta = std::fs::metadata(&target)?;
+                (target, Some(meta))
+            }
+            Err(e) if e.kind() == ErrorKind::NotFound => (path.to_path_buf(), None),
+            Err(e) => return Err(e),
+        };
+        let Some(name) = target.file_name() else {
+            return Ok(false);
+        };
+        if original.as_ref().is_some_and(has_other_links) {
+            return Ok(false);
+        }
+        let temp = target.with_file_name(format!(
+            ".{}.{}.tmp",
+            name.to_string_lossy(),
+            std::process::id()
+        ));
+        let Ok(file) = OpenOptions::new().write(true).create_new(true).open(&temp) else {
+            return Ok(false);
+        };
+        let written = self
+            .write_contents(BufWriter::new(&file), fix_eol)
+            .and_then(|()| file.sync_all());
+        if let Err(e) = written {
+            let _ = std::fs::remove_file(&temp);
+            return Err(e);
+        }
+        let permissions_kept = original
+            .as_ref()
+            .is_none_or(|meta| std::fs::set_permissions(&temp, meta.permissions()).is_ok());
+        if !permissions_kept || std::fs::rename(&temp, &target).is_err() {
+            let _ = std::fs::remove_file(&temp);
+            return Ok(false);
+        }
+        Ok(true)
+    }
+
     /// Save the buffer to a specific file path
-    pub fn save_as(&mut self, path: &str) -> Result<(), IoError> {
+    pub fn save_as(
+        &mut self,
+        path: &str,
+        atomic: bool,
+        backup: Option<&Path>,
+        fix_eol: bool,
+    ) -> Result<(), IoError> {
         self.file_path = Some(PathBuf::from(path));
-        self.save()
+        if self.filetype.is_none() {
+            self.detect_filetype();
+        }
+        self.save(atomic, backup, fix_eol)
+    }
+
+    /// Whether the text ends with a line break (an empty buffer counts as ending with one)
+    pub fn has_final_newline(&self) -> bool {
+        let len = self.text.len_chars();
+        len == 0 || self.text.char(len - 1) == '\n'
+    }
+
+    /// Replace the `len` chars at char index `at` with `inserted`, recording the change for
+    /// undo. All text changes go through here.
+    fn edit(&mut self, at: usize, len: usize, inserted: &str) {
+        let removed = self.text.slice(at..at + len).to_string();
+        self.text.remove(at..at + len);
+        self.text.insert(at, inserted);
+        self.history.record(Edit {
+            at,
+            removed,
+            inserted: inserted.to_string(),
+        });
+        self.mark_changed();
+    }
+
+    /// Make `edit` without recording it (for undo and redo)
+    fn apply(&mut self, edit: &Edit) {
+        let at = edit.at;
+        self.text.remove(at..at + edit.removed.chars().count());
+        self.text.insert(at, &edit.inserted);
+    }
+
+    /// End the current undo step: the changes since the last call are undone together
+    pub fn commit_undo_step(&mut self) {
+        self.history.commit();
+    }
+
+    pub fn can_undo(&self) -> bool {
+        self.history.can_undo()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        self.history.can_redo()
+    }
+
+    /// Undo the last step. Returns where it changed the text (its first changed position),
+    /// or None if there was nothing to undo.
+    pub fn undo(&mut self) -> Option<(usize, usize)> {
+        let step = self.history.undo()?;
+        for edit in step.iter().rev() {
+            self.apply(&edit.inverse());
+        }
+        Some(self.finish_history_move(&step))
+    }
+
+    /// Redo the last undone step; returns where it changed the text
+    pub fn redo(&mut self) -> Option<(usize, usize)> {
+        let step = self.history.redo()?;
+        for edit in &step {
+            self.apply(edit);
+        }
+        Some(self.finish_history_move(&step))
+    }
+
+    /// Bookkeeping after undo or redo: the text is unmodified again if it is back to the
+    /// saved state. Returns the (line, col) of the first position the step touched.
+    fn finish_history_move(&mut self, step: &[Edit]) -> (usize, usize) {
+        self.revision += 1;
+        self.modified = !self.history.at_saved();
+        let at = step.iter().map(|e| e.at).min().unwrap_or(0).min(self.text.len_chars());
+        let line = self.text.char_to_line(at);
+        (line, at - self.text.line_to_char(line))
+    }
+
+    /// Record a text change: sets `modified` and bumps the revision
+    fn mark_changed(&mut self) {
+        self.modified = true;
+        self.revision += 1;
+    }
+
+    /// Revision counter, changed whenever the text changes
+    pub fn revision(&self) -> u64 {
+        self.revision
     }
 
     /// Get the total number of lines in the buffer
@@ -78,6 +350,17 @@ impl Buffer {
         }
     }
 
+    /// Index of the last line holding text: the empty "line" ropey reports after a final
+    /// line break is not one
+    pub fn last_text_line(&self) -> usize {
+        let last = self.line_count().saturating_sub(1);
+        if last > 0 && self.line_len(last) == 0 {
+            last - 1
+        } else {
+            last
+        }
+    }
+
     /// Get the length of a specific line (excluding newline)
     pub fn line_len(&self, line_idx: usize) -> usize {
         if let Some(line) = self.line(line_idx) {
@@ -93,12 +376,38 @@ impl Buffer {
         }
     }
 
+    /// Char index of (line, col), clamped to the end of the text
+    pub fn char_idx(&self, line: usize, col: usize) -> usize {
+        if line >= self.line_count() {
+            return self.text.len_chars();
+        }
+        (self.text.line_to_char(line) + col).min(self.text.len_chars())
+    }
+
+    /// The text between two char indexes
+    pub fn text_between(&self, range: Range<usize>) -> String {
+        self.text.slice(range).to_string()
+    }
+
     /// Insert a character at the given line and column position
     pub fn insert_char(&mut self, line: usize, col: usize, ch: char) {
         let line_start = self.text.line_to_char(line);
-        let char_idx = line_start + col;
-        self.text.insert_char(char_idx, ch);
-        self.modified = true;
+        self.edit(line_start + col, 0, ch.encode_utf8(&mut [0; 4]));
+    }
+
+    /// Insert a string at the given line and column position
+    pub fn insert_str(&mut self, line: usize, col: usize, text: &str) {
+        let char_idx = self.text.line_to_char(line) + col;
+        self.edit(char_idx, 0, text);
+    }
+
+    /// Delete `count` characters starting at the given line and column position
+    pub fn delete_range(&mut self, line: usize, col: usize, count: usize) {
+        let start = self.text.line_to_char(line) + col;
+        let end = (start + count).min(self.text.len_chars());
+        if start < end {
+            self.edit(start, end - start, "");
+        }
     }
 
     /// Delete a character at the given line and column position
@@ -108,8 +417,7 @@ impl Buffer {
             let line_start = self.text.line_to_char(line);
             let char_idx = line_start + col;
             if char_idx < self.text.len_chars() {
-                self.text.remove(char_idx..char_idx + 1);
-                self.modified = true;
+                self.edit(char_idx, 1, "");
             }
         }
     }
@@ -121,10 +429,17 @@ impl Buffer {
             self.delete_char(line, col - 1);
             Some((line, col - 1))
         } else if line > 0 {
-            // Join with previous line
-            let prev_line_len = self.line_len(line - 1);
-            self.delete_char(line - 1, prev_line_len);
-            Some((line - 1, prev_line_len))
+            // Join with previous line: remove its whole line break (`\n` or `\r\n`) so the
+            // cursor lands exactly where the two lines meet
+            let break_start = self.text.line_to_char(line) - 1;
+            let break_start = if break_start > 0 && self.text.char(break_start - 1) == '\r' {
+                break_start - 1
+            } else {
+                break_start
+            };
+            let join_col = break_start - self.text.line_to_char(line - 1);
+            self.edit(break_start, self.text.line_to_char(line) - break_start, "");
+            Some((line - 1, join_col))
         } else {
             None
         }
@@ -307,6 +622,39 @@ impl Buffer {
         None
     }
 
+    /// Count the matches of `pattern` (overlapping ones included, as n finds them) and find
+    /// the 1-based index of the one starting at (line, col). Counting stops after `limit + 1`
+    /// matches, so a total above `limit` means "more than `limit`" and a match past that
+    /// point has no index.
+    pub fn match_count(
+        &self,
+        pattern: &str,
+        line: usize,
+        col: usize,
+        limit: usize,
+    ) -> (Option<usize>, usize) {
+        let pattern_chars: Vec<char> = pattern.chars().collect();
+        let mut index = None;
+        let mut total = 0;
+        if pattern_chars.is_empty() {
+            return (index, total);
+        }
+        for line_idx in 0..self.line_count() {
+            let mut start = 0;
+            while let Some((_, match_col)) = self.find_in_line(line_idx, start, &pattern_chars) {
+                total += 1;
+                if (line_idx, match_col) == (line, col) {
+                    index = Some(total);
+                }
+                if total > limit {
+                    return (index, total);
+                }
+                start = match_col + 1;
+            }
+        }
+        (index, total)
+    }
+
     /// Get the filename (just the name, not the full path)
     pub fn filename(&self) -> Option<String> {
         self.file_path
@@ -328,3 +676,237 @@ impl Default for Buffer {
         Self::new()
     }
 }
+
+/// Human-readable byte count for messages ("3.2 GB", "512 KB")
+fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KB", "MB", "GB", "TB"];
+    if bytes < 1024 {
+        return format!("{} bytes", bytes);
+    }
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if size.fract() == 0.0 {
+        format!("{} {}", size, UNITS[unit])
+    } else {
+        format!("{:.1} {}", size, UNITS[unit])
+    }
+}
+
+/// Whether the file `meta` describes is also reachable through other hard links
+#[cfg(unix)]
+fn has_other_links(meta: &Metadata) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    meta.nlink() > 1
+}
+
+#[cfg(not(unix))]
+fn has_other_links(_meta: &Metadata) -> bool {
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::{buffer, temp_dir, temp_file};
+
+    fn open(path: &Path) -> Buffer {
+        Buffer::from_file(path.to_str().unwrap(), None).unwrap()
+    }
+
+    #[test]
+    fn bom_is_stripped_on_load_and_written_back() {
+        let path = temp_file("bom", b"\xef\xbb\xbfhello\n");
+        let mut buffer = open(&path);
+        assert!(buffer.has_bom);
+        assert_eq!(buffer.text.to_string(), "hello\n");
+        buffer.save(false, None, false).unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), b"\xef\xbb\xbfhello\n");
+    }
+
+    #[test]
+    fn file_without_bom_round_trips_unchanged() {
+        let path = temp_file("no-bom", b"hello\n");
+        let mut buffer = open(&path);
+        assert!(!buffer.has_bom);
+        buffer.save(true, None, false).unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), b"hello\n");
+    }
+
+    #[test]
+    fn nobomb_drops_the_bom_on_save() {
+        let path = temp_file("nobomb", b"\xef\xbb\xbfhello\n");
+        let mut buffer = open(&path);
+        buffer.has_bom = false;
+        buffer.save(true, None, false).unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), b"hello\n");
+    }
+
+    #[test]
+    fn file_with_a_final_line_break_round_trips() {
+        let path = temp_file("eol", b"a\nb\n");
+        let mut buffer = open(&path);
+        assert!(buffer.eol);
+        buffer.save(false, None, false).unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), b"a\nb\n");
+    }
+
+    #[test]
+    fn file_without_a_final_line_break_is_saved_without_one() {
+        let path = temp_file("noeol", b"a\nb");
+        let mut buffer = open(&path);
+        assert!(!buffer.eol);
+        buffer.save(true, None, false).unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), b"a\nb");
+    }
+
+    #[test]
+    fn fix_eol_adds_the_line_break_only_on_disk() {
+        let path = temp_file("fixeol", b"a\nb");
+        let mut buffer = open(&path);
+        buffer.save(false, None, true).unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), b"a\nb\n");
+        assert_eq!(buffer.text.to_string(), "a\nb");
+        assert!(buffer.eol);
+        assert!(!buffer.can_undo());
+    }
+
+    #[test]
+    fn failed_save_leaves_eol_alone() {
+        let path = temp_file("fixeol-fail", b"a");
+        let mut buffer = open(&path);
+        buffer.file_path = Some(path.join("not-a-dir"));
+        assert!(buffer.save(true, None, true).is_err());
+        assert!(!buffer.eol);
+    }
+
+    #[test]
+    fn files_over_max_size_are_refused() {
+        let path = temp_file("max-size", [b'x'; 2048]);
+        let e = Buffer::from_file(path.to_str().unwrap(), Some(1024)).err().unwrap();
+        assert_eq!(e.kind(), ErrorKind::InvalidData);
+        assert_eq!(e.to_string(), "file is too large (2 KB, maxfilesize is 1 KB)");
+        assert!(Buffer::from_file(path.to_str().unwrap(), Some(2048)).is_ok());
+        assert!(Buffer::from_file(path.to_str().unwrap(), None).is_ok());
+    }
+
+    #[test]
+    fn sizes_are_shown_in_readable_units() {
+        assert_eq!(format_size(512), "512 bytes");
+        assert_eq!(format_size(1536), "1.5 KB");
+        assert_eq!(format_size(3 * 1024 * 1024 * 1024), "3 GB");
+    }
+
+    #[test]
+    fn match_count_counts_overlapping_matches_and_finds_the_index() {
+        let buffer = buffer("aaa\nxaa\n");
+        assert_eq!(buffer.match_count("aa", 0, 1, 999), (Some(2), 3));
+        assert_eq!(buffer.match_count("aa", 1, 1, 999), (Some(3), 3));
+        // Not on a match
+        assert_eq!(buffer.match_count("aa", 1, 0, 999), (None, 3));
+        assert_eq!(buffer.match_count("", 0, 0, 999), (None, 0));
+    }
+
+    #[test]
+    fn match_count_stops_past_the_limit() {
+        let buffer = buffer(&"x ".repeat(10));
+        assert_eq!(buffer.match_count("x", 0, 0, 3), (Some(1), 4));
+        assert_eq!(buffer.match_count("x", 0, 16, 3), (None, 4));
+    }
+
+    /// File names in `dir`, sorted
+    fn names_in(dir: &Path) -> Vec<String> {
+        let mut names: Vec<String> = std::fs::read_dir(dir)
+            .unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn atomic_save_replaces_the_contents_and_leaves_no_temp_file() {
+        let path = temp_file("atomic", b"old\n");
+        let mut buffer = open(&path);
+        buffer.insert_str(0, 0, "new ");
+        buffer.save(true, None, false).unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), b"new old\n");
+        assert_eq!(names_in(path.parent().unwrap()), ["f.txt"]);
+        assert!(!buffer.modified);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn atomic_save_keeps_the_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+        let path = temp_file("atomic-mode", b"#!/bin/sh\n");
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o750)).unwrap();
+        let mut buffer = open(&path);
+        buffer.insert_str(1, 0, "true\n");
+        buffer.save(true, None, false).unwrap();
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o750);
+        assert_eq!(std::fs::read(&path).unwrap(), b"#!/bin/sh\ntrue\n");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn atomic_save_through_a_symlink_replaces_the_target() {
+        let target = temp_file("atomic-link", b"old\n");
+        let link = target.with_file_name("link.txt");
+        std::os::unix::fs::symlink(&target, &link).unwrap();
+        let mut buffer = open(&link);
+        buffer.insert_str(0, 0, "new ");
+        buffer.save(true, None, false).unwrap();
+        assert!(std::fs::symlink_metadata(&link).unwrap().file_type().is_symlink());
+        assert_eq!(std::fs::read(&target).unwrap(), b"new old\n");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn atomic_save_of_a_hard_linked_file_writes_in_place() {
+        let path = temp_file("atomic-hardlink", b"old\n");
+        let other = path.with_file_name("other.txt");
+        std::fs::hard_link(&path, &other).unwrap();
+        let mut buffer = open(&path);
+        buffer.insert_str(0, 0, "new ");
+        buffer.save(true, None, false).unwrap();
+        assert_eq!(std::fs::read(&other).unwrap(), b"new old\n");
+    }
+
+    #[test]
+    fn backup_holds_the_contents_from_before_the_save() {
+        let path = temp_file("backup", b"before\n");
+        let backup = path.with_file_name("f.txt~");
+        let mut buffer = open(&path);
+        buffer.insert_str(0, 0, "after ");
+        buffer.save(true, Some(&backup), false).unwrap();
+        assert_eq!(std::fs::read(&backup).unwrap(), b"before\n");
+        assert_eq!(std::fs::read(&path).unwrap(), b"after before\n");
+    }
+
+    #[test]
+    fn failed_backup_leaves_the_file_alone() {
+        let path = temp_file("backup-fail", b"before\n");
+        let backup = path.with_file_name("missing-dir").join("f.txt~");
+        let mut buffer = open(&path);
+        buffer.insert_str(0, 0, "after ");
+        let e = buffer.save(false, Some(&backup), false).unwrap_err();
+        assert!(e.to_string().starts_with("Cannot make backup file"), "{}", e);
+        assert_eq!(std::fs::read(&path).unwrap(), b"before\n");
+        assert!(buffer.modified);
+    }
+
+    #[test]
+    fn new_file_needs_no_backup() {
+        let path = temp_dir("backup-new").join("new.txt");
+        let backup = path.with_file_name("new.txt~");
+        let mut buffer = Buffer::new_file(path.to_str().unwrap());
+        buffer.insert_str(0, 0, "x");
+        buffer.save(false, Some(&backup), false).unwrap();
+        assert!(!backup.exists());
+    }
+}
@@ -0,0 +1,48 @@
+//! Paste detection for terminals without bracketed paste: keys arriving faster than anyone
+//! types are taken to be pasted. Time is passed in by the caller (see `App::clock`).
+
+use std::time::{Duration, Instant};
+
+/// Keys closer together than this are part of a burst
+const BURST_GAP: Duration = Duration::from_millis(10);
+
+/// Remembers when the last key arrived
+#[derive(Default)]
+pub struct BurstDetector {
+    last_key: Option<Instant>,
+}
+
+impl BurstDetector {
+    /// Record a key; true if it came within `BURST_GAP` of the previous one
+    pub fn key(&mut self, now: Instant) -> bool {
+        let in_burst = self
+            .last_key
+            .is_some_and(|last| now.saturating_duration_since(last) < BURST_GAP);
+        self.last_key = Some(now);
+        in_burst
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keys_within_the_gap_are_a_burst() {
+        let start = Instant::now();
+        let mut detector = BurstDetector::default();
+        assert!(!detector.key(start));
+        assert!(detector.key(start + Duration::from_millis(5)));
+        assert!(detector.key(start + Duration::from_millis(14)));
+        // The gap is measured from the previous key, not the first one
+        assert!(!detector.key(start + Duration::from_millis(40)));
+    }
+
+    #[test]
+    fn a_clock_going_backwards_counts_as_a_burst() {
+        let start = Instant::now() + Duration::from_secs(1);
+        let mut detector = BurstDetector::default();
+        detector.key(start);
+        assert!(detector.key(start - Duration::from_millis(500)));
+    }
+}
@@ -0,0 +1,225 @@
+//! Command-line argument handling (parsed before the TUI starts).
+
+/// What `main` should do for the given arguments
+#[derive(Debug, PartialEq, Eq)]
+pub enum CliAction {
+    /// Print usage and exit
+    Help,
+    /// Print the version and exit
+    Version,
+    /// Start the editor
+    Run(CliArgs),
+    /// Bad arguments: print the message and usage to stderr and exit with an error
+    Error(String),
+}
+
+/// Options for starting the editor
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct CliArgs {
+    /// File or directory to open (may carry a `:line[:col]` suffix)
+    pub path: Option<String>,
+    /// Edit the text read from stdin instead of a file (`-`)
+    pub stdin: bool,
+    /// Line to start on (`+N`; a bare `+` is the last line, kept as `usize::MAX`)
+    pub line: Option<usize>,
+    /// Open with the `readonly` option set, so the file isn't overwritten by accident (`-R`)
+    pub read_only: bool,
+    /// Skip the user config and use built-in defaults (`--clean`, like vim's `-u NONE`)
+    pub no_config: bool,
+    /// Draw in the normal screen instead of the alternate one, so the last screen stays in the
+    /// scrollback after quitting (`--no-altscreen`)
+    pub no_altscreen: bool,
+}
+
+/// Classify the process arguments (without the program name).
+pub fn parse_args(args: &[String]) -> CliAction {
+    let mut cli = CliArgs::default();
+    let mut only_paths = false;
+    for arg in args {
+        // Only the first path (or `-`) is opened; later ones are ignored as before
+        let first_input = cli.path.is_none() && !cli.stdin;
+        if only_paths {
+            if first_input {
+                cli.path = Some(arg.clone());
+            }
+        } else if arg == "-" {
+            cli.stdin |= first_input;
+        } else if let Some(line) = arg.strip_prefix('+') {
+            cli.line = match line {
+                "" => Some(usize::MAX),
+                _ => match line.parse() {
+                    Ok(line) => Some(line),
+                    Err(_) => return CliAction::Error(format!("Invalid line number: {}", arg)),
+                },
+            };
+        } else if arg.starts_with('-') {
+            match arg.as_str() {
+                "-h" | "--help" => return CliAction::Help,
+                "-V" | "--version" => return CliAction::Version,
+                "-R" => cli.read_only = true,
+                "--clean" | "--noconfig" => cli.no_config = true,
+                "--no-altscreen" => cli.no_altscreen = true,
+                "--" => only_paths = true,
+                _ => return CliAction::Error(format!("Unknown option: {}", arg)),
+            }
+        } else if first_input {
+            cli.path = Some(arg.clone());
+        }
+    }
+    CliAction::Run(cli)
+}
+
+/// Version line printed by `--version`
+pub fn version() -> String {
+    format!("vibeVim {}", env!("CARGO_PKG_VERSION"))
+}
+
+/// Usage text printed by `--help`
+pub fn usage() -> String {
+    format!(
+        "{}
+A modal terminal text editor with vim-like keybindings.
+
+Usage: vibeVim [OPTIONS] [+LINE] [PATH | -]
+
+Arguments:
+  [PATH]             File to open (created on first write if it doesn't exist),
+                     or a directory to browse in the file explorer (`.` for the cwd).
+                     A trailing :LINE or :LINE:COL opens the file at that position.
+  -                  Edit the text read from stdin
+  +LINE              Start on line LINE (a bare + starts on the last line)
+
+Options:
+  -h, --help         Print this help and exit
+  -V, --version      Print the version and exit
+  -R                 Read-only: refuse to write files (:set noreadonly allows it)
+  --clean            Ignore the user config and start with built-in defaults
+                     (alias: --noconfig)
+  --no-altscreen     Draw in the main screen; the last screen stays visible after quitting
+  --                 Treat the following argument as a path, even if it starts with -",
+        version()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> CliAction {
+        let args: Vec<String> = args.iter().map(|a| a.to_string()).collect();
+        parse_args(&args)
+    }
+
+    fn run(path: Option<&str>) -> CliAction {
+        CliAction::Run(CliArgs {
+            path: path.map(str::to_string),
+            ..CliArgs::default()
+        })
+    }
+
+    #[test]
+    fn help_and_version() {
+        assert_eq!(parse(&["--help"]), CliAction::Help);
+        assert_eq!(parse(&["-h"]), CliAction::Help);
+        assert_eq!(parse(&["--version"]), CliAction::Version);
+        assert_eq!(parse(&["-V"]), CliAction::Version);
+        // The first of them wins
+        assert_eq!(parse(&["file.txt", "-V", "-h"]), CliAction::Version);
+    }
+
+    #[test]
+    fn paths() {
+        assert_eq!(parse(&[]), run(None));
+        assert_eq!(parse(&["file.txt"]), run(Some("file.txt")));
+        assert_eq!(parse(&["."]), run(Some(".")));
+        assert_eq!(parse(&["a", "b"]), run(Some("a")));
+    }
+
+    #[test]
+    fn lone_dash_reads_stdin() {
+        let stdin = CliAction::Run(CliArgs {
+            stdin: true,
+            ..CliArgs::default()
+        });
+        assert_eq!(parse(&["-"]), stdin);
+        assert_eq!(parse(&["-", "file.txt"]), stdin);
+        assert_eq!(parse(&["file.txt", "-"]), run(Some("file.txt")));
+        // After -- it names a file called "-"
+        assert_eq!(parse(&["--", "-"]), run(Some("-")));
+    }
+
+    #[test]
+    fn plus_sets_the_start_line() {
+        let at_line = |line| {
+            CliAction::Run(CliArgs {
+                path: Some("f".to_string()),
+                line: Some(line),
+                ..CliArgs::default()
+            })
+        };
+        assert_eq!(parse(&["+42", "f"]), at_line(42));
+        assert_eq!(parse(&["f", "+42"]), at_line(42));
+        assert_eq!(parse(&["+", "f"]), at_line(usize::MAX));
+        assert_eq!(
+            parse(&["+abc", "f"]),
+            CliAction::Error("Invalid line number: +abc".to_string())
+        );
+        assert_eq!(parse(&["--", "+42"]), run(Some("+42")));
+    }
+
+    #[test]
+    fn read_only_flag() {
+        let expected = CliAction::Run(CliArgs {
+            path: Some("f".to_string()),
+            read_only: true,
+            ..CliArgs::default()
+        });
+        assert_eq!(parse(&["-R", "f"]), expected);
+        assert_eq!(parse(&["f", "-R"]), expected);
+    }
+
+    #[test]
+    fn usage_lists_the_arguments() {
+        let usage = usage();
+        for arg in ["[PATH]", "  -  ", "+LINE", "-R", "--clean", "--no-altscreen", "--  "] {
+            assert!(usage.contains(arg), "usage is missing {}", arg);
+        }
+    }
+
+    #[test]
+    fn double_dash_ends_options() {
+        assert_eq!(parse(&["--", "--help"]), run(Some("--help")));
+    }
+
+    #[test]
+    fn unknown_option_is_an_error() {
+        assert_eq!(
+            parse(&["--frobnicate"]),
+            CliAction::Error("Unknown option: --frobnicate".to_string())
+        );
+    }
+
+    #[test]
+    fn clean_flags() {
+        let clean = CliAction::Run(CliArgs {
+            path: Some("f".to_string()),
+            no_config: true,
+            ..CliArgs::default()
+        });
+        assert_eq!(parse(&["--clean", "f"]), clean);
+        assert_eq!(parse(&["f", "--noconfig"]), clean);
+    }
+
+    #[test]
+    fn no_altscreen_flag() {
+        let expected = CliAction::Run(CliArgs {
+            path: Some("f".to_string()),
+            no_altscreen: true,
+            ..CliArgs::default()
+        });
+        assert_eq!(parse(&["--no-altscreen", "f"]), expected);
+        assert_eq!(parse(&["f", "--no-altscreen"]), expected);
+        assert_eq!(parse(&["--", "--no-altscreen"]), run(Some("--no-altscreen")));
+        assert!(usage().contains("--no-altscreen"));
+    }
+}
@@ -0,0 +1,150 @@
+//! User config: `$XDG_CONFIG_HOME/vibevim/config.toml` (or `~/.config/vibevim/config.toml`).
+//! Set `$VIBEVIM_CONFIG_DIR` to use another directory (isolated configs for tests/containers).
+//! Only flat `key = value` lines are supported; each one is applied like `:set`:
+//!
+//! ```toml
+//! statusline = " %f%m%=%l:%c "
+//! ```
+
+use std::path::PathBuf;
+
+use crate::options::Options;
+
+/// Config directory for vibevim: $VIBEVIM_CONFIG_DIR if set, else $XDG_CONFIG_HOME/vibevim
+/// or $HOME/.config/vibevim.
+pub fn config_dir() -> Option<PathBuf> {
+    if let Some(dir) = std::env::var_os("VIBEVIM_CONFIG_DIR").filter(|v| !v.is_empty()) {
+        return Some(PathBuf::from(dir));
+    }
+    let base = std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|v| !v.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|h| PathBuf::from(h).join(".config")))?;
+    Some(base.join("vibevim"))
+}
+
+/// Path to config.toml.
+pub fn config_path() -> Option<PathBuf> {
+    config_dir().map(|d| d.join("config.toml"))
+}
+
+/// Turn one `key = value` line into a `:set` argument. Booleans become `key`/`nokey`,
+/// quoted strings are unquoted. Returns None for blank lines, comments and section headers.
+fn line_to_set_arg(line: &str) -> Option<Result<String, String>> {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') || line.starts_with('[') {
+        return None;
+    }
+    let Some((key, value)) = line.split_once('=') else {
+        return Some(Err(format!("expected `key = value`: {}", line)));
+    };
+    let key = key.trim();
+    let value = value.trim();
+    let arg = match value {
+        "true" => key.to_string(),
+        "false" => format!("no{}", key),
+        _ => {
+            let unquoted = value
+                .strip_prefix('"')
+                .and_then(|v| v.strip_suffix('"'))
+                .or_else(|| value.strip_prefix('\'').and_then(|v| v.strip_suffix('\'')))
+                .unwrap_or(value);
+            format!("{}={}", key, unquoted)
+        }
+    };
+    Some(Ok(arg))
+}
+
+/// Load config.toml into `options`. A missing file is not an error; the returned
+/// list holds one message per line that could not be applied.
+pub fn load_config(options: &mut Options) -> Vec<String> {
+    let Some(path) = config_path() else {
+        return Vec::new();
+    };
+    let Ok(contents) = std::fs::read_to_string(&path) else {
+        return Vec::new();
+    };
+    let mut errors = Vec::new();
+    for (idx, line) in contents.lines().enumerate() {
+        let result = match line_to_set_arg(line) {
+            Some(Ok(arg)) => options.apply(&arg).map(|_| ()),
+            Some(Err(e)) => Err(e),
+            None => Ok(()),
+        };
+        if let Err(e) = result {
+            errors.push(format!("config.toml:{}: {}", idx + 1, e));
+        }
+    }
+    errors
+}
+
+/// Options to start with: the defaults with config.toml applied, or just the defaults when
+/// `no_config` (`--clean`). Also returns the config's error messages.
+pub fn startup_options(no_config: bool) -> (Options, Vec<String>) {
+    let mut options = Options::default();
+    let errors = if no_config {
+        Vec::new()
+    } else {
+        load_config(&mut options)
+    };
+    (options, errors)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::temp_dir;
+    use std::sync::Mutex;
+
+    /// Held by tests that set `VIBEVIM_CONFIG_DIR`, since the environment is shared by all
+    /// test threads
+    static ENV_LOCK: Mutex<()> = Mutex::new(());
+
+    /// A fresh config directory holding `config`, set as `VIBEVIM_CONFIG_DIR`
+    fn use_config_dir(name: &str, config: &str) -> PathBuf {
+        let dir = temp_dir(name);
+        std::fs::write(dir.join("config.toml"), config).unwrap();
+        std::env::set_var("VIBEVIM_CONFIG_DIR", &dir);
+        dir
+    }
+
+    #[test]
+    fn clean_start_skips_the_user_config() {
+        let _lock = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        use_config_dir("clean", "tabstop = 3\nnosuch = 1\n");
+
+        let (options, errors) = startup_options(false);
+        assert_eq!(options.tabstop, 3);
+        assert_eq!(errors, vec!["config.toml:2: Unknown option: nosuch".to_string()]);
+
+        let (options, errors) = startup_options(true);
+        assert_eq!(options.tabstop, Options::default().tabstop);
+        assert!(errors.is_empty());
+        std::env::remove_var("VIBEVIM_CONFIG_DIR");
+    }
+
+    #[test]
+    fn config_lines_become_set_arguments() {
+        let arg = |line| line_to_set_arg(line).map(|r| r.unwrap());
+        assert_eq!(arg("autopairs = true").as_deref(), Some("autopairs"));
+        assert_eq!(arg("autopairs = false").as_deref(), Some("noautopairs"));
+        assert_eq!(arg("statusline = \" %f \"").as_deref(), Some("statusline= %f "));
+        assert_eq!(arg("# comment"), None);
+        assert_eq!(arg("[section]"), None);
+        assert!(line_to_set_arg("tabstop").unwrap().is_err());
+    }
+
+    #[test]
+    fn config_dir_env_override() {
+        let _lock = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let dir = use_config_dir("override", "");
+        assert_eq!(config_dir(), Some(dir.clone()));
+        assert_eq!(config_path(), Some(dir.join("config.toml")));
+
+        // An empty value is ignored, like an unset one
+        std::env::set_var("VIBEVIM_CONFIG_DIR", "");
+        assert_ne!(config_dir(), Some(PathBuf::new()));
+        std::env::remove_var("VIBEVIM_CONFIG_DIR");
+        assert_ne!(config_dir(), Some(dir));
+    }
+}
@@ -3,7 +3,7 @@
 //! Hidden files are shown (ratatui-explorer does not filter dotfiles).
 
 use std::io;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 use ratatui::style::{Color, Style};
 use ratatui_explorer::{FileExplorer, Theme};
@@ -13,6 +13,8 @@ use ratatui_explorer::{FileExplorer, Theme};
 pub struct DirectoryState {
     /// File explorer widget state (cwd, file list, selection).
     pub file_explorer: FileExplorer,
+    /// Entries marked with `m` for opening together, in the order they were marked
+    marked: Vec<PathBuf>,
 }
 
 impl DirectoryState {
@@ -26,7 +28,10 @@ impl DirectoryState {
             .with_highlight_dir_style(Style::default().fg(Color::LightBlue).bg(Color::DarkGray));
         let mut file_explorer = FileExplorer::with_theme(theme)?;
         file_explorer.set_cwd(path)?;
-        Ok(Self { file_explorer })
+        Ok(Self {
+            file_explorer,
+            marked: Vec::new(),
+        })
     }
 
     /// Reference to the file explorer for rendering and input.
@@ -39,9 +44,172 @@ impl DirectoryState {
         &mut self.file_explorer
     }
 
+    /// The current directory for the sidebar title, with the home directory shown as `~` and
+    /// cut from the left to fit `width` columns
+    pub fn cwd_title(&self, width: usize) -> String {
+        let cwd = self.file_explorer.cwd();
+        let home = std::env::var_os("HOME").map(PathBuf::from);
+        let path = match home.as_deref().and_then(|home| cwd.strip_prefix(home).ok()) {
+            Some(rest) if rest.as_os_str().is_empty() => "~".to_string(),
+            Some(rest) => format!("~/{}", rest.display()),
+            None => cwd.display().to_string(),
+        };
+        truncate_left(&path, width)
+    }
+
+    /// Show the parent directory with the directory we came from selected. At the
+    /// filesystem root nothing changes.
+    pub fn go_to_parent(&mut self) -> io::Result<()> {
+        let cwd = self.file_explorer.cwd();
+        // A relative cwd like `src` has an empty parent; resolve it first
+        let cwd = cwd.canonicalize().unwrap_or_else(|_| cwd.clone());
+        let Some(parent) = cwd.parent() else {
+            return Ok(());
+        };
+        self.file_explorer.set_cwd(parent)?;
+        self.select(&cwd);
+        Ok(())
+    }
+
+    /// Select the entry for `path` if it is listed; returns whether it was
+    pub fn select(&mut self, path: &Path) -> bool {
+        let idx = self.file_explorer.files().iter().position(|f| f.path() == path);
+        if let Some(idx) = idx {
+            self.file_explorer.set_selected_idx(idx);
+        }
+        idx.is_some()
+    }
+
+    /// Show the home directory
+    pub fn go_home(&mut self) -> io::Result<()> {
+        let home = std::env::var_os("HOME")
+            .map(PathBuf::from)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "HOME is not set"))?;
+        self.file_explorer.set_cwd(home)
+    }
+
+    /// Mark the selected entry, or unmark it if it already is
+    pub fn toggle_mark(&mut self) {
+        let path = self.file_explorer.current().path().clone();
+        match self.marked.iter().position(|p| *p == path) {
+            Some(idx) => {
+                self.marked.remove(idx);
+            }
+            None => self.marked.push(path),
+        }
+    }
+
+    pub fn is_marked(&self, path: &Path) -> bool {
+        self.marked.iter().any(|p| p == path)
+    }
+
+    /// The marked entries in marking order, clearing the marks
+    pub fn take_marked(&mut self) -> Vec<PathBuf> {
+        std::mem::take(&mut self.marked)
+    }
+
     /// Re-read the current directory (e.g. after external file changes).
     pub fn refresh(&mut self) -> io::Result<()> {
         let cwd = self.file_explorer.cwd().clone();
         self.file_explorer.set_cwd(cwd)
     }
 }
+
+/// Directory to show when revealing `file` in the explorer: its parent, or `cwd` for
+/// `[No Name]` buffers and bare relative file names
+pub fn reveal_dir(file: Option<&Path>, cwd: &Path) -> PathBuf {
+    file.and_then(Path::parent)
+        .filter(|parent| !parent.as_os_str().is_empty())
+        .map_or_else(|| cwd.to_path_buf(), Path::to_path_buf)
+}
+
+/// `text` cut to its last `width` chars, with `…` marking the cut
+pub fn truncate_left(text: &str, width: usize) -> String {
+    let len = text.chars().count();
+    if len <= width {
+        return text.to_string();
+    }
+    if width == 0 {
+        return String::new();
+    }
+    let tail: String = text.chars().skip(len - (width - 1)).collect();
+    format!("…{}", tail)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::temp_dir;
+
+    #[test]
+    fn truncate_left_keeps_the_end() {
+        assert_eq!(truncate_left("/a/b", 10), "/a/b");
+        assert_eq!(truncate_left("/a/b", 4), "/a/b");
+        assert_eq!(truncate_left("/home/me/src", 5), "…/src");
+        assert_eq!(truncate_left("/a/b", 1), "…");
+        assert_eq!(truncate_left("/a/b", 0), "");
+    }
+
+    #[test]
+    fn cwd_title_fits_the_width() {
+        let dir = temp_dir("title").join("project");
+        std::fs::create_dir_all(&dir).unwrap();
+        let state = DirectoryState::new(&dir).unwrap();
+        assert!(state.cwd_title(200).ends_with("/project"));
+        assert_eq!(state.cwd_title(8), "…project");
+    }
+
+    #[test]
+    fn cwd_title_shows_home_as_tilde() {
+        let Some(home) = std::env::var_os("HOME").map(PathBuf::from) else {
+            return;
+        };
+        if !home.is_dir() {
+            return;
+        }
+        let state = DirectoryState::new(&home).unwrap();
+        assert_eq!(state.cwd_title(40), "~");
+    }
+
+    #[test]
+    fn parent_selects_the_directory_we_came_from() {
+        let root = temp_dir("parent");
+        for name in ["a", "b", "c"] {
+            std::fs::create_dir(root.join(name)).unwrap();
+        }
+        let mut state = DirectoryState::new(&root.join("b")).unwrap();
+        state.go_to_parent().unwrap();
+        let root = root.canonicalize().unwrap();
+        assert_eq!(state.file_explorer().cwd(), &root);
+        assert_eq!(state.file_explorer().current().path(), &root.join("b"));
+    }
+
+    #[test]
+    fn parent_of_the_root_stays_put() {
+        let mut state = DirectoryState::new(Path::new("/")).unwrap();
+        state.go_to_parent().unwrap();
+        assert_eq!(state.file_explorer().cwd(), Path::new("/"));
+    }
+
+    #[test]
+    fn home_goes_to_home() {
+        let Some(home) = std::env::var_os("HOME").map(PathBuf::from) else {
+            return;
+        };
+        if !home.is_dir() {
+            return;
+        }
+        let mut state = DirectoryState::new(&std::env::temp_dir()).unwrap();
+        state.go_home().unwrap();
+        assert_eq!(state.file_explorer().cwd(), &home);
+    }
+
+    #[test]
+    fn reveal_dir_is_the_parent_or_the_working_directory() {
+        let cwd = Path::new("/work");
+        assert_eq!(reveal_dir(Some(Path::new("/a/b/f.rs")), cwd), Path::new("/a/b"));
+        assert_eq!(reveal_dir(Some(Path::new("src/f.rs")), cwd), Path::new("src"));
+        assert_eq!(reveal_dir(Some(Path::new("f.rs")), cwd), cwd);
+        assert_eq!(reveal_dir(None, cwd), cwd);
+    }
+}
@@ -1,13 +1,66 @@
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
 use crate::buffer::Buffer;
+use crate::filetype;
+use crate::fold::{self, FoldState};
+use crate::git;
+use crate::matchpair;
+use crate::modeline;
 use crate::mode::Mode;
+use crate::options::{self, CursorHold, Options};
+use crate::positions;
+use crate::range::{self, LineRange};
+use crate::sort::{self, SortFlags};
+use crate::spell::{self, Dictionary};
+use crate::tabs;
+
+/// Search matches counted for `searchcount` before giving up with `>999`
+const SEARCH_COUNT_LIMIT: usize = 999;
 
-/// Pending two-key or replace action in normal mode (gg, dd, r)
+/// Pending two-key or replace action in normal mode (gg, ga, g;, gcc, dd, z?, ZZ/ZQ, r, ]w/[w)
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum PendingNormal {
     None,
     SecondG,
     SecondD,
+    SecondZ,
+    SecondUpperZ,
+    CommentLine,
     ReplaceChar,
+    /// After `]` (waiting for `w`, `s` or `}`)
+    SecondRightBracket,
+    /// After `[` (waiting for `w`, `s` or `{`)
+    SecondLeftBracket,
+    /// After `"` in visual mode (waiting for a register name)
+    Register,
+    /// After `"_` or Space in visual mode: the next d or x deletes into the blackhole register
+    Blackhole,
+}
+
+/// Insert-mode Ctrl+v in progress
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LiteralInput {
+    /// Waiting for the key to insert literally
+    Key,
+    /// Reading a character code (`065`, `x41`, `o101`, `u00e9`, `U0001f600`)
+    Code {
+        /// Key that started the code (`u`, `x`, ...), inserted itself if no digits follow
+        prefix: Option<char>,
+        digits: String,
+        radix: u32,
+        max_len: usize,
+    },
+}
+
+/// Chars of one line, cached so repeated motions on a long line don't re-collect it
+struct LineCache {
+    buf: usize,
+    line: usize,
+    revision: u64,
+    chars: Rc<[char]>,
 }
 
 /// Represents the cursor position in the editor
@@ -17,26 +70,68 @@ pub struct Cursor {
     pub col: usize,
 }
 
+/// Where a buffer was left, restored when switching back to it
+#[derive(Debug, Clone, Copy, Default)]
+struct BufferView {
+    cursor: Cursor,
+    viewport_offset: usize,
+}
+
 /// The main editor state
 pub struct Editor {
     /// All open buffers
     pub buffers: Vec<Buffer>,
     /// Index of the current buffer in `buffers`
     pub current_buf: usize,
+    /// Saved cursor and viewport of each buffer (same indices as `buffers`)
+    views: Vec<BufferView>,
     /// Current cursor position
     pub cursor: Cursor,
     /// Current editing mode
     pub mode: Mode,
     /// Viewport offset (first visible line)
     pub viewport_offset: usize,
+    /// The other end of the selection in visual mode (the cursor is one end)
+    pub visual_anchor: Cursor,
+    /// Unnamed register: the text last yanked or deleted in visual mode or with `:y`/`:d`
+    /// (deletes into the blackhole register, `"_d` or `:d _`, leave it alone)
+    pub register: String,
     /// Command line input buffer (for : commands)
     pub command_buffer: String,
     /// Status message to display
     pub status_message: Option<String>,
-    /// Pending two-key or replace action in normal mode (gg, dd, r)
+    /// Pending two-key or replace action in normal mode (gg, ga, g;, gcc, dd, z?, ZZ/ZQ, r, ]w/[w)
     pub pending_normal: PendingNormal,
     /// Last search pattern for n/N repeat
     pub last_search_pattern: Option<String>,
+    /// Closed indentation folds in the current buffer
+    pub folds: FoldState,
+    /// Git branch of the current buffer's directory (cached; refreshed on save and buffer switch)
+    pub git_branch: Option<String>,
+    /// Options set with `:set` or loaded from config.toml
+    pub options: Options,
+    /// Running a single normal-mode command from insert mode (Ctrl+o); insert resumes after it
+    pub insert_normal_pending: bool,
+    /// Insert-mode Ctrl+v waiting for the key or character code to insert
+    pub insert_literal: Option<LiteralInput>,
+    /// The current key came in a burst too fast for typing (with `pasteburst`), so it is
+    /// taken as pasted: abbreviations and autopairs are skipped
+    pub key_burst: bool,
+    /// Clear and repaint the whole terminal before the next frame (Ctrl+l, `:redraw`, and after
+    /// external commands that may have written to it)
+    pub redraw_pending: bool,
+    /// Working directory before the last `:cd`, for `:cd -`
+    previous_dir: Option<PathBuf>,
+    /// Chars of the most recently inspected line (see `current_line_chars`)
+    line_cache: RefCell<Option<LineCache>>,
+    /// Center the cursor line on the next viewport adjustment (see `center_view`)
+    center_pending: bool,
+    /// Pages to scroll on the next viewport adjustment, negative for backward (see `page_down`)
+    page_pending: isize,
+    /// Insert-mode abbreviations (`:iabbrev teh the`), keyed by the typed word
+    pub abbreviations: BTreeMap<String, String>,
+    /// `spellfile` path and its word list as last loaded (None if it could not be read)
+    spell_dict: Option<(String, Option<Rc<Dictionary>>)>,
 }
 
 impl Editor {
@@ -45,24 +140,55 @@ impl Editor {
         Self::from_buffer(Buffer::new())
     }
 
-    /// Create a new editor with a file loaded
-    pub fn with_file(path: &str) -> Result<Self, std::io::Error> {
-        Buffer::from_file(path).map(Self::from_buffer)
+    /// Create a new editor with a file loaded (refused if larger than `max_size` bytes)
+    pub fn with_file(path: &str, max_size: Option<u64>) -> Result<Self, std::io::Error> {
+        Buffer::from_file(path, max_size).map(Self::from_buffer)
+    }
+
+    /// Create a new editor for a file that does not exist yet
+    pub fn with_new_file(path: &str) -> Self {
+        Self::from_buffer(Buffer::new_file(path))
+    }
+
+    /// Create a new editor with text read from `reader` (`vibeVim -` reads stdin). The buffer
+    /// has no file name and starts out modified, so quitting doesn't silently drop the text.
+    pub fn with_reader(reader: impl std::io::Read) -> Result<Self, std::io::Error> {
+        let mut buffer = Buffer::from_reader(reader)?;
+        buffer.modified = true;
+        Ok(Self::from_buffer(buffer))
     }
 
     /// Build an editor from a buffer with default state (cursor, mode, viewport, etc.)
     fn from_buffer(buffer: Buffer) -> Self {
-        Self {
+        let mut editor = Self {
             buffers: vec![buffer],
             current_buf: 0,
+            views: vec![BufferView::default()],
             cursor: Cursor::default(),
             mode: Mode::default(),
             viewport_offset: 0,
+            visual_anchor: Cursor::default(),
+            register: String::new(),
             command_buffer: String::new(),
             status_message: None,
             pending_normal: PendingNormal::None,
             last_search_pattern: None,
-        }
+            folds: FoldState::default(),
+            git_branch: None,
+            options: Options::default(),
+            insert_normal_pending: false,
+            insert_literal: None,
+            key_burst: false,
+            redraw_pending: false,
+            previous_dir: None,
+            line_cache: RefCell::new(None),
+            center_pending: false,
+            page_pending: 0,
+            abbreviations: BTreeMap::new(),
+            spell_dict: None,
+        };
+        editor.refresh_git_branch();
+        editor
     }
 
     /// Reference to the current buffer
@@ -84,29 +210,124 @@ impl Editor {
             .enumerate()
             .find(|(_, b)| b.file_path.as_ref().map(|p| p == &normalized).unwrap_or(false))
         {
-            self.current_buf = idx;
-            self.cursor = Cursor::default();
-            self.viewport_offset = 0;
-            self.clamp_cursor_to_buffer();
+            self.switch_to_buffer(idx);
             return Ok(());
         }
         let path_str = normalized.to_string_lossy().into_owned();
-        let buffer = Buffer::from_file(&path_str)?;
+        let buffer = Buffer::from_file(&path_str, self.options.max_file_size())?;
         self.buffers.push(buffer);
-        self.current_buf = self.buffers.len() - 1;
-        self.cursor = Cursor::default();
-        self.viewport_offset = 0;
+        self.views.push(BufferView::default());
+        self.switch_to_buffer(self.buffers.len() - 1);
+        self.apply_modelines();
+        self.restore_position();
         Ok(())
     }
 
+    /// Open `paths` into buffers in order, skipping directories, and report how many were
+    /// opened (and the last failure, if any). The last one opened becomes current.
+    pub fn open_files(&mut self, paths: &[PathBuf]) {
+        let mut opened = 0;
+        let mut error = None;
+        for path in paths.iter().filter(|p| !p.is_dir()) {
+            let result = match path.to_str() {
+                Some(s) => self.open_file_into_new_buffer(s).map_err(|e| e.to_string()),
+                None => Err("Path is not valid UTF-8".to_string()),
+            };
+            match result {
+                Ok(()) => opened += 1,
+                Err(e) => error = Some(format!("{}: {}", path.display(), e)),
+            }
+        }
+        let plural = if opened == 1 { "" } else { "s" };
+        let report = format!("Opened {} file{}", opened, plural);
+        match error {
+            Some(e) => self.set_status(&format!("{}; {}", report, e)),
+            None => self.set_status(&report),
+        }
+    }
+
+    /// Apply the current buffer's modelines (when `modeline` is on). Options other than
+    /// filetype are global here, so they also affect the other buffers.
+    pub fn apply_modelines(&mut self) {
+        if !self.options.modeline {
+            return;
+        }
+        for arg in modeline::find(self.current_buffer(), self.options.modelines) {
+            if let Some(ft) = arg.strip_prefix("filetype=").or_else(|| arg.strip_prefix("ft=")) {
+                self.current_buffer_mut().filetype = (!ft.is_empty()).then(|| ft.to_string());
+            } else {
+                // A bad value in someone else's file is not worth an error message
+                let _ = self.options.apply(&arg);
+            }
+        }
+    }
+
+    /// Make buffer `idx` current, saving where the current one was left and restoring where
+    /// `idx` was left (clamped, in case its text changed)
+    fn switch_to_buffer(&mut self, idx: usize) {
+        self.remember_position();
+        self.current_buffer_mut().commit_undo_step();
+        self.views[self.current_buf] = BufferView {
+            cursor: self.cursor,
+            viewport_offset: self.viewport_offset,
+        };
+        self.current_buf = idx;
+        let view = self.views[idx];
+        self.cursor = view.cursor;
+        self.folds.clear();
+        self.clamp_cursor_to_buffer();
+        self.viewport_offset = view.viewport_offset.min(self.cursor.line);
+        self.refresh_git_branch();
+    }
+
+    /// Save the cursor position of the current file to the position store (with `restorecursor`).
+    /// Errors are ignored: a missing position is harmless.
+    pub fn remember_position(&self) {
+        if !self.options.restorecursor {
+            return;
+        }
+        let buffer = self.current_buffer();
+        if let (Some(path), Some(store), false) =
+            (&buffer.file_path, positions::store_path(), buffer.is_new)
+        {
+            let path = std::fs::canonicalize(path).unwrap_or_else(|_| path.clone());
+            let _ = positions::save(&store, &path, self.cursor.line, self.cursor.col);
+        }
+    }
+
+    /// Move to the remembered position of the current file (with `restorecursor`), clamped in
+    /// case the file has shrunk since
+    pub fn restore_position(&mut self) {
+        if !self.options.restorecursor {
+            return;
+        }
+        let remembered = match (&self.current_buffer().file_path, positions::store_path()) {
+            (Some(path), Some(store)) => positions::load(&store, path),
+            _ => None,
+        };
+        if let Some((line, col)) = remembered {
+            self.goto_position(line, col);
+        }
+    }
+
+    /// Re-read the git branch for the current buffer's directory (or the cwd for unnamed buffers)
+    pub fn refresh_git_branch(&mut self) {
+        let dir = self
+            .current_buffer()
+            .file_path
+            .as_ref()
+            .and_then(|p| std::fs::canonicalize(p).ok().or_else(|| Some(p.clone())))
+            .and_then(|p| p.parent().map(|d| d.to_path_buf()))
+            .or_else(|| std::env::current_dir().ok());
+        self.git_branch = dir.and_then(|d| git::current_branch(&d));
+    }
+
     /// Switch to next buffer (wrap around)
     pub fn next_buf(&mut self) {
         if self.buffers.len() <= 1 {
             return;
         }
-        self.current_buf = (self.current_buf + 1) % self.buffers.len();
-        self.clamp_cursor_to_buffer();
-        self.viewport_offset = 0;
+        self.switch_to_buffer((self.current_buf + 1) % self.buffers.len());
     }
 
     /// Switch to previous buffer (wrap around)
@@ -114,9 +335,7 @@ impl Editor {
         if self.buffers.len() <= 1 {
             return;
         }
-        self.current_buf = self.current_buf.checked_sub(1).unwrap_or(self.buffers.len() - 1);
-        self.clamp_cursor_to_buffer();
-        self.viewport_offset = 0;
+        self.switch_to_buffer(self.current_buf.checked_sub(1).unwrap_or(self.buffers.len() - 1));
     }
 
     /// Clamp cursor to valid range for current buffer
@@ -152,24 +371,151 @@ impl Editor {
         }
     }
 
-    /// Move cursor up
+    /// Left-motion key `key` (`h`, `<` for <Left>, `[` for <Left> in insert mode): at column 0,
+    /// continue at the end of the previous line when `whichwrap` allows it
+    pub fn move_left_wrapping(&mut self, key: char) {
+        if self.cursor.col > 0 || !self.options.wraps(key) {
+            self.move_left();
+            return;
+        }
+        if let Some(line) = self.prev_visible_line(self.cursor.line) {
+            self.cursor.line = line;
+            self.cursor.col = self.max_col_for_line(line);
+            self.adjust_viewport();
+        }
+    }
+
+    /// Right-motion key `key` (`l`, `>` for <Right>, `]` for <Right> in insert mode): at the last
+    /// column, continue at the start of the next line when `whichwrap` allows it
+    pub fn move_right_wrapping(&mut self, key: char) {
+        let at_end = self.cursor.col >= self.max_col_for_line(self.cursor.line);
+        if !at_end || !self.options.wraps(key) {
+            self.move_right();
+            return;
+        }
+        let next = self.next_visible_line(self.cursor.line);
+        if next < self.current_buffer().line_count() {
+            self.cursor.line = next;
+            self.cursor.col = 0;
+            self.adjust_viewport();
+        }
+    }
+
+    /// Move cursor up (a closed fold counts as one line)
     pub fn move_up(&mut self) {
-        if self.cursor.line > 0 {
-            self.cursor.line -= 1;
+        if let Some(line) = self.prev_visible_line(self.cursor.line) {
+            self.cursor.line = line;
             self.clamp_cursor_col();
             self.adjust_viewport();
         }
     }
 
-    /// Move cursor down
+    /// Move cursor down (a closed fold counts as one line)
     pub fn move_down(&mut self) {
-        if self.cursor.line < self.current_buffer().line_count().saturating_sub(1) {
-            self.cursor.line += 1;
+        let next = self.next_visible_line(self.cursor.line);
+        if next < self.current_buffer().line_count() {
+            self.cursor.line = next;
             self.clamp_cursor_col();
             self.adjust_viewport();
         }
     }
 
+    /// First line shown on screen after `line`, skipping lines hidden in a closed fold
+    fn next_visible_line(&self, line: usize) -> usize {
+        match self.folds.closed_at(line) {
+            Some(f) => f.end + 1,
+            None => line + 1,
+        }
+    }
+
+    /// Line shown on screen before `line` (the header if it is inside a closed fold)
+    fn prev_visible_line(&self, line: usize) -> Option<usize> {
+        let prev = line.checked_sub(1)?;
+        Some(self.folds.closed_at(prev).map(|f| f.start).unwrap_or(prev))
+    }
+
+    /// Number of screen rows used by the lines from `from` up to (not including) `to`
+    pub fn screen_rows_between(&self, from: usize, to: usize) -> usize {
+        if self.folds.is_empty() {
+            return to.saturating_sub(from);
+        }
+        let mut rows = 0;
+        let mut line = from;
+        while line < to {
+            rows += 1;
+            line = self.next_visible_line(line);
+        }
+        rows
+    }
+
+    /// Toggle the indentation fold at the cursor (vim za)
+    pub fn toggle_fold(&mut self) {
+        if !self.folds.open_at(self.cursor.line) {
+            self.close_fold();
+        }
+    }
+
+    /// Open the closed fold at the cursor (vim zo)
+    pub fn open_fold(&mut self) {
+        if !self.folds.open_at(self.cursor.line) {
+            self.set_status("No fold found");
+        }
+    }
+
+    /// Close the indentation fold containing the cursor (vim zc)
+    pub fn close_fold(&mut self) {
+        let buffer = self.current_buffer();
+        let line_count = buffer.line_count();
+        match fold::enclosing_fold_range(buffer, self.cursor.line) {
+            Some(f) => {
+                self.folds.close(f, line_count);
+                self.cursor.line = f.start;
+                self.clamp_cursor_col();
+            }
+            None => self.set_status("No fold found"),
+        }
+    }
+
+    /// Open every fold (vim zR)
+    pub fn open_all_folds(&mut self) {
+        self.folds.clear();
+    }
+
+    /// Close every fold (vim zM)
+    pub fn close_all_folds(&mut self) {
+        self.set_fold_level(|_| 0);
+    }
+
+    /// Open one more level of nested folds (vim zr)
+    pub fn reduce_folding(&mut self) {
+        self.set_fold_level(|level| level + 1);
+    }
+
+    /// Close one more level of nested folds (vim zm)
+    pub fn fold_more(&mut self) {
+        self.set_fold_level(|level| level.saturating_sub(1));
+    }
+
+    /// Close the folds nested at least as deep as the level `change` makes of the current
+    /// one (all folds open counts as one past the deepest), then move the cursor to the
+    /// header of the fold it ends up in
+    fn set_fold_level(&mut self, change: impl Fn(usize) -> usize) {
+        let buffer = self.current_buffer();
+        let line_count = buffer.line_count();
+        let folds = fold::all_folds(buffer);
+        if folds.is_empty() {
+            self.set_status("No fold found");
+            return;
+        }
+        let all_open = folds.iter().map(|(_, depth)| depth + 1).max().unwrap_or(0);
+        let level = change(self.folds.level().unwrap_or(all_open).min(all_open));
+        self.folds.set_level(&folds, level.min(all_open), line_count);
+        if let Some(f) = self.folds.closed_at(self.cursor.line) {
+            self.cursor.line = f.start;
+            self.clamp_cursor_col();
+        }
+    }
+
     /// Move cursor to start of line
     pub fn move_to_line_start(&mut self) {
         self.cursor.col = 0;
@@ -205,6 +551,102 @@ impl Editor {
         }
     }
 
+    /// Move to the start of the next camelCase / snake_case subword (]w), continuing on the
+    /// next line at its end
+    pub fn move_subword_forward(&mut self) {
+        let Some(chars) = self.current_line_chars() else {
+            return;
+        };
+        let next = (self.cursor.col + 1..chars.len()).find(|&i| is_subword_start(&chars, i));
+        if let Some(col) = next {
+            self.cursor.col = col;
+        } else if self.cursor.line + 1 < self.current_buffer().line_count() {
+            self.cursor.line += 1;
+            let chars = self.current_line_chars().unwrap_or_else(|| Rc::from([]));
+            let first = (0..chars.len()).find(|&i| is_subword_start(&chars, i));
+            self.cursor.col = first.unwrap_or(0);
+            self.clamp_cursor_col();
+            self.adjust_viewport();
+        }
+    }
+
+    /// Move to the start of the previous camelCase / snake_case subword ([w), continuing on the
+    /// previous line at its start
+    pub fn move_subword_backward(&mut self) {
+        let Some(chars) = self.current_line_chars() else {
+            return;
+        };
+        let col = self.cursor.col.min(chars.len());
+        if let Some(col) = (0..col).rev().find(|&i| is_subword_start(&chars, i)) {
+            self.cursor.col = col;
+        } else if self.cursor.line > 0 {
+            self.cursor.line -= 1;
+            let chars = self.current_line_chars().unwrap_or_else(|| Rc::from([]));
+            let last = (0..chars.len()).rev().find(|&i| is_subword_start(&chars, i));
+            self.cursor.col = last.unwrap_or(0);
+            self.clamp_cursor_col();
+            self.adjust_viewport();
+        }
+    }
+
+    /// Word list for `spell`, loaded from `spellfile` on first use and whenever the option
+    /// changes. None while spell is off or if the file can't be read (reported once).
+    pub fn spell_dictionary(&mut self) -> Option<Rc<Dictionary>> {
+        if !self.options.spell {
+            return None;
+        }
+        let path = self.options.spellfile.clone();
+        if self.spell_dict.as_ref().is_none_or(|(loaded, _)| *loaded != path) {
+            let dict = match Dictionary::load(Path::new(&path)) {
+                Ok(dict) => Some(Rc::new(dict)),
+                Err(e) => {
+                    self.set_status(&format!("Cannot read spellfile \"{}\": {}", path, e));
+                    None
+                }
+            };
+            self.spell_dict = Some((path, dict));
+        }
+        self.spell_dict.as_ref().and_then(|(_, dict)| dict.clone())
+    }
+
+    /// Move to the next (]s) or previous ([s) misspelled word, searching the whole buffer
+    pub fn move_to_misspelled(&mut self, forward: bool) {
+        let Some(dict) = self.spell_dictionary() else {
+            if !self.options.spell {
+                self.set_status("Spell checking is off (:set spell)");
+            }
+            return;
+        };
+        let buffer = self.current_buffer();
+        let (line, col) = (self.cursor.line, self.cursor.col);
+        let words_on = |l: usize| {
+            let chars: Vec<char> = buffer
+                .line(l)
+                .map(|text| text.chars().take(buffer.line_len(l)).collect())
+                .unwrap_or_default();
+            spell::misspelled(&chars, &dict)
+        };
+        let found = if forward {
+            (line..buffer.line_count()).find_map(|l| {
+                let word = words_on(l).into_iter().find(|w| l > line || w.start > col)?;
+                Some((l, word.start))
+            })
+        } else {
+            (0..=line).rev().find_map(|l| {
+                let word = words_on(l).into_iter().rev().find(|w| l < line || w.start < col)?;
+                Some((l, word.start))
+            })
+        };
+        match found {
+            Some((line, col)) => {
+                self.cursor.line = line;
+                self.cursor.col = col;
+                self.adjust_viewport();
+            }
+            None => self.set_status("No more misspelled words"),
+        }
+    }
+
     /// Move cursor to previous word
     pub fn move_word_backward(&mut self) {
         if self.cursor.col == 0 {
@@ -243,23 +685,174 @@ impl Editor {
         }
     }
 
+    /// Screen column of the cursor within its line, with tabs expanded to `tabstop`. In
+    /// normal mode the cursor sits on the last cell of a tab, like vim.
+    pub fn cursor_screen_col(&self) -> usize {
+        let Some(chars) = self.current_line_chars() else {
+            return self.cursor.col;
+        };
+        let tabstop = self.options.tabstop;
+        let col = self.cursor.col.min(chars.len());
+        let start = tabs::display_width(chars[..col].iter().copied(), tabstop);
+        match chars.get(col) {
+            Some('\t') if matches!(self.mode, Mode::Normal | Mode::Visual) => {
+                tabs::advance(start, '\t', tabstop) - 1
+            }
+            _ => start,
+        }
+    }
+
+    /// The word (letters, digits, `_`) under the cursor, if it is on one
+    pub fn word_under_cursor(&self) -> Option<Vec<char>> {
+        let chars = self.current_line_chars()?;
+        let col = self.cursor.col;
+        if !chars.get(col).is_some_and(|&c| is_keyword_char(c)) {
+            return None;
+        }
+        let start = (0..col).rev().take_while(|&i| is_keyword_char(chars[i])).last().unwrap_or(col);
+        let end = (col..chars.len()).take_while(|&i| is_keyword_char(chars[i])).last()? + 1;
+        Some(chars[start..end].to_vec())
+    }
+
+    /// Smart home: go to column 0 from within the indentation or from the first non-blank,
+    /// otherwise (from column 0 or past the indentation) to the first non-blank
+    pub fn smart_home(&mut self) {
+        let col = self.cursor.col;
+        self.move_to_first_non_blank();
+        if col != 0 && col <= self.cursor.col {
+            self.cursor.col = 0;
+        }
+    }
+
     /// Move cursor to last line of buffer (vim G)
     pub fn move_to_last_line(&mut self) {
         let line_count = self.current_buffer().line_count();
         if line_count > 0 {
             self.cursor.line = line_count.saturating_sub(1);
-            self.clamp_cursor_col();
+            self.place_cursor_after_line_motion();
+            self.adjust_viewport();
+        }
+    }
+
+    /// Move cursor to (line, col), 0-based, clamped to the buffer
+    pub fn goto_position(&mut self, line: usize, col: usize) {
+        self.cursor.line = line;
+        self.cursor.col = col;
+        self.clamp_cursor_to_buffer();
+        self.adjust_viewport();
+    }
+
+    /// Add the cursor position to the change list after an edit. A change on the same line as
+    /// the previous one replaces it, so typing along a line leaves a single entry.
+    pub fn record_change(&mut self) {
+        const MAX_CHANGES: usize = 100;
+        let pos = (self.cursor.line, self.cursor.col);
+        let buffer = self.current_buffer_mut();
+        if buffer.changes.last().is_some_and(|&(line, _)| line == pos.0) {
+            buffer.changes.pop();
+        }
+        buffer.changes.push(pos);
+        if buffer.changes.len() > MAX_CHANGES {
+            buffer.changes.remove(0);
+        }
+        buffer.change_idx = buffer.changes.len();
+    }
+
+    /// Go to an older position in the change list (vim g;)
+    pub fn older_change(&mut self) {
+        let pos = (self.cursor.line, self.cursor.col);
+        let buffer = self.current_buffer();
+        if buffer.changes.is_empty() {
+            self.set_status("Change list is empty");
+            return;
+        }
+        let mut idx = buffer.change_idx.min(buffer.changes.len());
+        // Starting on the newest change itself goes one further back
+        if idx == buffer.changes.len() && buffer.changes[idx - 1] == pos {
+            idx -= 1;
+        }
+        if idx == 0 {
+            self.set_status("At start of change list");
+            return;
+        }
+        self.goto_change(idx - 1);
+    }
+
+    /// Go to a newer position in the change list (vim g,)
+    pub fn newer_change(&mut self) {
+        let buffer = self.current_buffer();
+        if buffer.changes.is_empty() {
+            self.set_status("Change list is empty");
+            return;
+        }
+        if buffer.change_idx + 1 >= buffer.changes.len() {
+            self.set_status("At end of change list");
+            return;
+        }
+        self.goto_change(buffer.change_idx + 1);
+    }
+
+    /// Move to change list entry `idx`, clamped to the buffer
+    fn goto_change(&mut self, idx: usize) {
+        let buffer = self.current_buffer_mut();
+        buffer.change_idx = idx;
+        let (line, col) = buffer.changes[idx];
+        self.goto_position(line, col);
+    }
+
+    /// Jump to the matching bracket or keyword (vim %); stays put if there is none
+    pub fn move_to_match(&mut self) {
+        let buffer = self.current_buffer();
+        let pairs = buffer
+            .filetype
+            .as_deref()
+            .map(filetype::keyword_pairs)
+            .unwrap_or_default();
+        if let Some((line, col)) =
+            matchpair::find_match(buffer, self.cursor.line, self.cursor.col, pairs)
+        {
+            self.cursor.line = line;
+            self.cursor.col = col;
             self.adjust_viewport();
         }
     }
 
+    /// Jump to the `{` (`[{`) or `}` (`]}`) of the block enclosing the cursor
+    pub fn move_to_enclosing_brace(&mut self, forward: bool) {
+        let found = matchpair::find_unmatched(
+            self.current_buffer(),
+            self.cursor.line,
+            self.cursor.col,
+            ('{', '}'),
+            forward,
+        );
+        match found {
+            Some((line, col)) => {
+                self.cursor.line = line;
+                self.cursor.col = col;
+                self.adjust_viewport();
+            }
+            None => self.set_status("No enclosing block"),
+        }
+    }
+
     /// Move cursor to first line of buffer (vim gg)
     pub fn move_to_first_line(&mut self) {
         self.cursor.line = 0;
-        self.clamp_cursor_col();
+        self.place_cursor_after_line_motion();
         self.adjust_viewport();
     }
 
+    /// Column placement after a line motion: first non-blank with `startofline`, else keep the
+    /// column (clamped to the new line)
+    fn place_cursor_after_line_motion(&mut self) {
+        if self.options.startofline {
+            self.move_to_first_non_blank();
+        } else {
+            self.clamp_cursor_col();
+        }
+    }
+
     /// Move cursor to end of current word or next word (vim e)
     pub fn move_to_end_of_word(&mut self) {
         if let Some(chars) = self.current_line_chars() {
@@ -336,21 +929,36 @@ impl Editor {
         self.adjust_viewport();
     }
 
-    /// Maximum valid column for a line in the current mode (Insert: end of line; Normal: last char)
+    /// Maximum valid column for a line in the current mode (Insert: end of line; Normal: last char).
+    /// During Ctrl+o the cursor may also sit past the last char, so `$` returns to insert at the end.
     fn max_col_for_line(&self, line: usize) -> usize {
         let line_len = self.current_buffer().line_len(line);
-        if self.mode == Mode::Insert {
+        if self.mode == Mode::Insert || self.insert_normal_pending {
             line_len
         } else {
-            line_len.saturating_sub(1).max(0)
+            line_len.saturating_sub(1)
         }
     }
 
-    /// Current line as Vec<char> for motion logic (may include newline)
-    fn current_line_chars(&self) -> Option<Vec<char>> {
-        self.current_buffer()
-            .line(self.cursor.line)
-            .map(|line| line.chars().collect())
+    /// Current line as chars for motion logic (may include newline). Cached per buffer, line
+    /// and revision, so motions repeated on a very long line don't collect it each time.
+    fn current_line_chars(&self) -> Option<Rc<[char]>> {
+        let buffer = self.current_buffer();
+        let (buf, line, revision) = (self.current_buf, self.cursor.line, buffer.revision());
+        let mut cache = self.line_cache.borrow_mut();
+        if let Some(c) = cache.as_ref() {
+            if c.buf == buf && c.line == line && c.revision == revision {
+                return Some(Rc::clone(&c.chars));
+            }
+        }
+        let chars: Rc<[char]> = buffer.line(line)?.chars().collect();
+        *cache = Some(LineCache {
+            buf,
+            line,
+            revision,
+            chars: Rc::clone(&chars),
+        });
+        Some(chars)
     }
 
     /// Clamp cursor column to valid range for current line
@@ -365,13 +973,128 @@ impl Editor {
         // For now, we'll handle basic scrolling
     }
 
-    /// Adjust viewport with a specific height
+    /// Adjust viewport with a specific height (in screen rows; a closed fold is one row)
     pub fn adjust_viewport_with_height(&mut self, height: usize) {
-        if self.cursor.line < self.viewport_offset {
-            self.viewport_offset = self.cursor.line;
-        } else if self.cursor.line >= self.viewport_offset + height {
-            self.viewport_offset = self.cursor.line - height + 1;
+        let line_count = self.current_buffer().line_count();
+        self.folds.sync(line_count);
+        // The cursor and the top line always sit on a visible line (a fold header)
+        if let Some(f) = self.folds.closed_at(self.cursor.line) {
+            self.cursor.line = f.start;
+            self.clamp_cursor_col();
+        }
+        if let Some(f) = self.folds.closed_at(self.viewport_offset) {
+            self.viewport_offset = f.start;
+        }
+        if self.page_pending != 0 {
+            self.scroll_pages(height);
+        }
+        if self.center_pending {
+            self.center_pending = false;
+            let half = height.saturating_sub(1) / 2;
+            self.viewport_offset = self.line_rows_above(self.cursor.line, half);
+        }
+
+        // Keep `scrolloff` rows of context around the cursor; a large value (e.g. 999) keeps it
+        // centered. Near the start/end of the buffer there is less context to show.
+        let scrolloff = self.options.scrolloff.min(height.saturating_sub(1) / 2);
+        if self.cursor.line < self.viewport_offset
+            || self.screen_rows_between(self.viewport_offset, self.cursor.line) < scrolloff
+        {
+            self.viewport_offset = self.line_rows_above(self.cursor.line, scrolloff);
+        }
+        let rows_below = self.visible_rows_after(self.cursor.line, scrolloff);
+        let max_rows_above = height.saturating_sub(1 + rows_below);
+        if self.screen_rows_between(self.viewport_offset, self.cursor.line) > max_rows_above {
+            self.viewport_offset = self.line_rows_above(self.cursor.line, max_rows_above);
+        }
+    }
+
+    /// Scroll so the cursor line is in the middle of the window (vim zz). Applied on the next
+    /// viewport adjustment, since only the UI knows the window height.
+    pub fn center_view(&mut self) {
+        self.center_pending = true;
+    }
+
+    /// Scroll a page forward (vim Ctrl+f, PageDown). Applied on the next viewport adjustment,
+    /// like `center_view`.
+    pub fn page_down(&mut self) {
+        self.page_pending += 1;
+    }
+
+    /// Scroll a page backward (vim Ctrl+b, PageUp)
+    pub fn page_up(&mut self) {
+        self.page_pending -= 1;
+    }
+
+    /// Apply pending page scrolls: each moves the top line by the window height less two rows
+    /// (kept for context), then the cursor goes to the first line (forward) or last line
+    /// (backward) of the new window that `scrolloff` allows, if it went off screen
+    fn scroll_pages(&mut self, height: usize) {
+        let pages = std::mem::take(&mut self.page_pending);
+        let rows = height.saturating_sub(2).max(1);
+        for _ in 0..pages.unsigned_abs() {
+            self.viewport_offset = if pages > 0 {
+                self.line_rows_below(self.viewport_offset, rows)
+            } else {
+                self.line_rows_above(self.viewport_offset, rows)
+            };
+        }
+        let scrolloff = self.options.scrolloff.min(height.saturating_sub(1) / 2);
+        let line = if pages > 0 {
+            self.cursor
+                .line
+                .max(self.line_rows_below(self.viewport_offset, scrolloff))
+        } else {
+            let bottom = height.saturating_sub(1 + scrolloff);
+            self.cursor
+                .line
+                .min(self.line_rows_below(self.viewport_offset, bottom))
+        };
+        if line != self.cursor.line {
+            self.cursor.line = line;
+            self.place_cursor_after_line_motion();
+        }
+    }
+
+    /// The line shown `rows` screen rows below `line` (stops at the last line)
+    fn line_rows_below(&self, line: usize, rows: usize) -> usize {
+        let line_count = self.current_buffer().line_count();
+        let mut bottom = line;
+        for _ in 0..rows {
+            let next = self.next_visible_line(bottom);
+            if next >= line_count {
+                break;
+            }
+            bottom = next;
+        }
+        bottom
+    }
+
+    /// The line shown `rows` screen rows above `line` (stops at the first line)
+    fn line_rows_above(&self, line: usize, rows: usize) -> usize {
+        let mut top = line;
+        for _ in 0..rows {
+            match self.prev_visible_line(top) {
+                Some(prev) => top = prev,
+                None => break,
+            }
+        }
+        top
+    }
+
+    /// Number of screen rows below `line` until the end of the buffer, counting at most `limit`
+    fn visible_rows_after(&self, line: usize, limit: usize) -> usize {
+        let line_count = self.current_buffer().line_count();
+        let mut rows = 0;
+        let mut current = line;
+        while rows < limit {
+            current = self.next_visible_line(current);
+            if current >= line_count {
+                break;
+            }
+            rows += 1;
         }
+        rows
     }
 
     /// Enter insert mode
@@ -417,33 +1140,186 @@ impl Editor {
         self.mode = Mode::Insert;
     }
 
-    /// Enter normal mode
-    pub fn enter_normal_mode(&mut self) {
-        self.clear_pending_normal();
+    /// Run one normal-mode command from insert mode (vim i_CTRL-O); the cursor is not moved back
+    pub fn begin_insert_normal_command(&mut self) {
         self.mode = Mode::Normal;
-        // Move cursor back one if we're past the end
-        self.clamp_cursor_col();
+        self.insert_normal_pending = true;
     }
 
-    /// Enter command mode
-    pub fn enter_command_mode(&mut self) {
-        self.mode = Mode::Command;
-        self.command_buffer.clear();
+    /// After a key was handled outside insert mode: if a Ctrl+o command has completed, resume
+    /// insert mode at the resulting position. Waits while a chord or `:`/`/` line is in progress.
+    pub fn finish_insert_normal_command(&mut self) {
+        if !self.insert_normal_pending || self.pending_normal != PendingNormal::None {
+            return;
+        }
+        match self.mode {
+            Mode::Normal => {
+                self.insert_normal_pending = false;
+                self.mode = Mode::Insert;
+            }
+            Mode::Insert => self.insert_normal_pending = false,
+            Mode::Command | Mode::Search | Mode::Visual => {}
+        }
     }
 
-    /// Enter search mode (vim /)
-    pub fn enter_search_mode(&mut self) {
-        self.mode = Mode::Search;
-        self.command_buffer.clear();
+    /// After a key was handled: outside insert mode, the changes it made form one undo step.
+    /// In insert mode (or during a Ctrl+o command) they join the step of the whole session.
+    pub fn finish_undo_step(&mut self) {
+        if self.mode != Mode::Insert && !self.insert_normal_pending {
+            self.current_buffer_mut().commit_undo_step();
+        }
     }
 
-    /// Run forward search from current cursor; move to match and save pattern. Returns true if found.
-    pub fn search_forward(&mut self) -> bool {
-        if self.command_buffer.is_empty() {
-            self.set_status("No pattern");
-            return false;
+    /// Undo the last change (vim u), putting the cursor where it was made
+    pub fn undo(&mut self) {
+        if !self.current_buffer().can_undo() {
+            self.set_status("Already at oldest change");
+            return;
         }
-        if let Some((line, col)) = self.current_buffer().find_forward(
+        if let Some((line, col)) = self.current_buffer_mut().undo() {
+            self.move_to_history_change(line, col);
+        }
+    }
+
+    /// Redo the last undone change (vim Ctrl+r)
+    pub fn redo(&mut self) {
+        if !self.current_buffer().can_redo() {
+            self.set_status("Already at newest change");
+            return;
+        }
+        if let Some((line, col)) = self.current_buffer_mut().redo() {
+            self.move_to_history_change(line, col);
+        }
+    }
+
+    fn move_to_history_change(&mut self, line: usize, col: usize) {
+        self.cursor.line = line;
+        self.cursor.col = col;
+        self.clamp_cursor_to_buffer();
+        self.adjust_viewport();
+    }
+
+    /// Enter normal mode
+    pub fn enter_normal_mode(&mut self) {
+        self.clear_pending_normal();
+        self.mode = Mode::Normal;
+        // Move cursor back one if we're past the end
+        self.clamp_cursor_col();
+    }
+
+    /// Enter visual mode (vim v), selecting from the cursor
+    pub fn enter_visual_mode(&mut self) {
+        self.visual_anchor = self.cursor;
+        self.mode = Mode::Visual;
+    }
+
+    /// Swap the cursor to the other end of the selection (vim v_o)
+    pub fn swap_visual_ends(&mut self) {
+        std::mem::swap(&mut self.visual_anchor, &mut self.cursor);
+        self.clamp_cursor_to_buffer();
+        self.adjust_viewport();
+    }
+
+    /// First and last (inclusive) positions of the visual selection, in buffer order and
+    /// clamped to the text, or None outside visual mode
+    fn visual_selection(&self) -> Option<(Cursor, Cursor)> {
+        if self.mode != Mode::Visual {
+            return None;
+        }
+        let buffer = self.current_buffer();
+        let last_line = buffer.line_count().saturating_sub(1);
+        let anchor_line = self.visual_anchor.line.min(last_line);
+        let anchor = Cursor {
+            line: anchor_line,
+            col: self.visual_anchor.col.min(buffer.line_len(anchor_line)),
+        };
+        if (anchor.line, anchor.col) <= (self.cursor.line, self.cursor.col) {
+            Some((anchor, self.cursor))
+        } else {
+            Some((self.cursor, anchor))
+        }
+    }
+
+    /// Columns of `line` inside the visual selection, and whether its line break is selected
+    /// too (it is when the selection goes on to the next line or ends on an empty line)
+    pub fn visual_cols(&self, line: usize) -> Option<(std::ops::Range<usize>, bool)> {
+        let (start, end) = self.visual_selection()?;
+        if line < start.line || line > end.line {
+            return None;
+        }
+        let len = self.current_buffer().line_len(line);
+        let from = if line == start.line { start.col } else { 0 };
+        let to = if line == end.line { end.col + 1 } else { len };
+        let line_break = line < end.line || end.col >= len;
+        Some((from.min(len)..to.min(len), line_break))
+    }
+
+    /// Char range of the visual selection (the last position included)
+    fn visual_char_range(&self) -> Option<std::ops::Range<usize>> {
+        let (start, end) = self.visual_selection()?;
+        let buffer = self.current_buffer();
+        let from = buffer.char_idx(start.line, start.col);
+        let to = buffer.char_idx(end.line, end.col + 1);
+        Some(from..to.max(from))
+    }
+
+    /// Copy the selection into the register and leave visual mode (vim v_y); the cursor goes
+    /// to the start of the selection
+    pub fn yank_selection(&mut self) {
+        let Some((start, end)) = self.visual_selection() else {
+            return;
+        };
+        let range = self.visual_char_range().unwrap_or_default();
+        self.register = self.current_buffer().text_between(range);
+        let lines = end.line - start.line + 1;
+        if lines > 2 {
+            self.set_status(&format!("{} lines yanked", lines));
+        }
+        self.cursor = start;
+        self.enter_normal_mode();
+    }
+
+    /// Delete the selection into the register (or, with `blackhole`, without touching it) and
+    /// leave visual mode (vim v_d, v_x)
+    pub fn delete_selection(&mut self, blackhole: bool) {
+        let (Some((start, _)), Some(range)) = (self.visual_selection(), self.visual_char_range())
+        else {
+            return;
+        };
+        let line_count = self.current_buffer().line_count();
+        if !blackhole {
+            self.register = self.current_buffer().text_between(range.clone());
+        }
+        self.current_buffer_mut().delete_range(start.line, start.col, range.len());
+        let removed = line_count - self.current_buffer().line_count();
+        if removed > 2 {
+            self.set_status(&format!("{} fewer lines", removed));
+        }
+        self.cursor = start;
+        self.enter_normal_mode();
+        self.clamp_cursor_to_buffer();
+        self.adjust_viewport();
+    }
+
+    /// Enter command mode
+    pub fn enter_command_mode(&mut self) {
+        self.mode = Mode::Command;
+        self.command_buffer.clear();
+    }
+
+    /// Enter search mode (vim /)
+    pub fn enter_search_mode(&mut self) {
+        self.mode = Mode::Search;
+        self.command_buffer.clear();
+    }
+
+    /// Run forward search from current cursor; move to match and save pattern. Returns true if found.
+    pub fn search_forward(&mut self) -> bool {
+        if self.command_buffer.is_empty() {
+            self.set_status("No pattern");
+            return false;
+        }
+        if let Some((line, col)) = self.current_buffer().find_forward(
             self.cursor.line,
             self.cursor.col,
             self.command_buffer.as_str(),
@@ -454,6 +1330,7 @@ impl Editor {
             self.clamp_cursor_col();
             self.adjust_viewport();
             self.last_search_pattern = Some(self.command_buffer.clone());
+            self.show_search_count('/');
             true
         } else {
             self.set_status("Pattern not found");
@@ -479,6 +1356,7 @@ impl Editor {
             self.clamp_cursor_col();
             self.adjust_viewport();
             self.last_search_pattern = Some(self.command_buffer.clone());
+            self.show_search_count('?');
             true
         } else {
             self.set_status("Pattern not found");
@@ -501,7 +1379,11 @@ impl Editor {
             self.cursor.line = line;
             self.cursor.col = col;
             self.clamp_cursor_col();
+            if self.options.searchcenter {
+                self.center_view();
+            }
             self.adjust_viewport();
+            self.show_search_count('/');
             true
         } else {
             self.set_status("Pattern not found");
@@ -524,7 +1406,11 @@ impl Editor {
             self.cursor.line = line;
             self.cursor.col = col;
             self.clamp_cursor_col();
+            if self.options.searchcenter {
+                self.center_view();
+            }
             self.adjust_viewport();
+            self.show_search_count('?');
             true
         } else {
             self.set_status("Pattern not found");
@@ -532,6 +1418,23 @@ impl Editor {
         }
     }
 
+    /// With `searchcount`, show the last search pattern with the index of the match under
+    /// the cursor and the number of matches, like `/foo [3/12]`. Counting stops past
+    /// `SEARCH_COUNT_LIMIT` matches (`[3/>999]`).
+    fn show_search_count(&mut self, prefix: char) {
+        let Some(pattern) = self.last_search_pattern.clone() else {
+            return;
+        };
+        if !self.options.searchcount {
+            return;
+        }
+        let (line, col) = (self.cursor.line, self.cursor.col);
+        let (index, total) =
+            self.current_buffer().match_count(&pattern, line, col, SEARCH_COUNT_LIMIT);
+        let count = format_search_count(index, total, SEARCH_COUNT_LIMIT);
+        self.set_status(&format!("{}{} {}", prefix, pattern, count));
+    }
+
     /// Insert a character at the cursor position
     pub fn insert_char(&mut self, ch: char) {
         let (line, col) = (self.cursor.line, self.cursor.col);
@@ -539,6 +1442,160 @@ impl Editor {
         self.cursor.col += 1;
     }
 
+    /// Insert-mode Tab: a tab character, or with `expandtab` the spaces reaching the next
+    /// `tabstop` column
+    pub fn insert_tab(&mut self) {
+        if !self.options.expandtab {
+            self.insert_char('\t');
+            return;
+        }
+        let tabstop = self.options.tabstop;
+        let vcol = self.cursor_screen_col();
+        let spaces = tabs::fill_whitespace(vcol, tabs::advance(vcol, '\t', tabstop), tabstop, true);
+        let (line, col) = (self.cursor.line, self.cursor.col);
+        self.current_buffer_mut().insert_str(line, col, &spaces);
+        self.cursor.col += spaces.len();
+    }
+
+    /// Insert a character from Ctrl+v as-is (no autopairs or abbreviations). Line break
+    /// characters other than a plain newline can't be kept in the buffer as text, so they are
+    /// refused.
+    pub fn insert_literal_char(&mut self, ch: char) {
+        if matches!(ch, '\n' | '\r' | '\u{0b}' | '\u{0c}' | '\u{85}' | '\u{2028}' | '\u{2029}') {
+            self.set_status("Cannot insert a line break character literally");
+            return;
+        }
+        self.insert_char(ch);
+    }
+
+    /// Insert the character whose code is `digits` in `radix` (Ctrl+v u00e9 and friends)
+    pub fn insert_char_code(&mut self, digits: &str, radix: u32) {
+        match u32::from_str_radix(digits, radix).ok().and_then(char::from_u32) {
+            Some(ch) => self.insert_literal_char(ch),
+            None => self.set_status(&format!("Invalid character code: {}", digits)),
+        }
+    }
+
+    /// Insert pasted text at the cursor in one buffer operation, however long it is (no
+    /// autopairs, abbreviations or indentation), leaving the cursor after it (on its last
+    /// character in normal mode). Line breaks are normalized to `\n`, since terminals often
+    /// send `\r` for them.
+    pub fn paste_text(&mut self, text: &str) {
+        let text = text.replace("\r\n", "\n").replace('\r', "\n");
+        if text.is_empty() {
+            return;
+        }
+        let (line, col) = (self.cursor.line, self.cursor.col);
+        self.current_buffer_mut().insert_str(line, col, &text);
+        match text.rsplit_once('\n') {
+            Some((before, last)) => {
+                self.cursor.line += before.matches('\n').count() + 1;
+                self.cursor.col = last.chars().count();
+            }
+            None => self.cursor.col += text.chars().count(),
+        }
+        if self.mode == Mode::Normal {
+            self.cursor.col = self.cursor.col.saturating_sub(1);
+            self.clamp_cursor_col();
+        }
+    }
+
+    /// Insert a typed character, applying autopairs when enabled: an opening bracket or quote
+    /// also inserts its closer, and typing a closer that is already under the cursor skips it.
+    pub fn insert_typed_char(&mut self, ch: char) {
+        if !is_keyword_char(ch) {
+            self.expand_abbreviation();
+        }
+        if !self.options.autopairs || self.key_burst {
+            self.insert_char(ch);
+            return;
+        }
+        let (line, col) = (self.cursor.line, self.cursor.col);
+        let next = self.char_at(line, col);
+        let prev = col.checked_sub(1).and_then(|c| self.char_at(line, c));
+        let is_quote = matches!(ch, '"' | '\'' | '`');
+        if (matches!(ch, ')' | ']' | '}') || is_quote) && next == Some(ch) {
+            self.cursor.col += 1;
+            return;
+        }
+        let closer = match ch {
+            '(' => Some(')'),
+            '[' => Some(']'),
+            '{' => Some('}'),
+            // Don't pair a quote typed right after a word character (e.g. the ' in don't)
+            _ if is_quote && !prev.is_some_and(|p| p.is_alphanumeric() || p == '_') => Some(ch),
+            _ => None,
+        };
+        self.insert_char(ch);
+        if let Some(closer) = closer {
+            self.current_buffer_mut().insert_char(line, col + 1, closer);
+        }
+    }
+
+    /// Replace the word just before the cursor with its abbreviation, if it is one. Only whole
+    /// words match: the word must start the line or follow a non-keyword character.
+    pub fn expand_abbreviation(&mut self) {
+        if self.abbreviations.is_empty() || self.key_burst {
+            return;
+        }
+        let Some(chars) = self.current_line_chars() else {
+            return;
+        };
+        let end = self.cursor.col.min(chars.len());
+        let mut start = end;
+        while start > 0 && is_keyword_char(chars[start - 1]) {
+            start -= 1;
+        }
+        let word: String = chars[start..end].iter().collect();
+        let Some(expansion) = self.abbreviations.get(&word).cloned() else {
+            return;
+        };
+        let line = self.cursor.line;
+        let buffer = self.current_buffer_mut();
+        buffer.delete_range(line, start, end - start);
+        buffer.insert_str(line, start, &expansion);
+        self.cursor.col = start + expansion.chars().count();
+    }
+
+    /// `:iabbrev [lhs [rhs]]`: define an abbreviation, or show one (or all) of them
+    fn abbreviate(&mut self, args: &str) {
+        let args = args.trim();
+        let (lhs, rhs) = match args.split_once(char::is_whitespace) {
+            Some((lhs, rhs)) => (lhs, rhs.trim()),
+            None => (args, ""),
+        };
+        if lhs.is_empty() {
+            let list: Vec<String> = self
+                .abbreviations
+                .iter()
+                .map(|(lhs, rhs)| format!("{} {}", lhs, rhs))
+                .collect();
+            if list.is_empty() {
+                self.set_status("No abbreviation found");
+            } else {
+                self.set_status(&list.join(", "));
+            }
+        } else if !lhs.chars().all(is_keyword_char) {
+            self.set_status(&format!("Invalid abbreviation: {}", lhs));
+        } else if rhs.is_empty() {
+            match self.abbreviations.get(lhs) {
+                Some(rhs) => self.set_status(&format!("{} {}", lhs, rhs)),
+                None => self.set_status("No abbreviation found"),
+            }
+        } else {
+            self.abbreviations.insert(lhs.to_string(), rhs.to_string());
+        }
+    }
+
+    /// Character at (line, col), if within the line's text (excluding the newline)
+    fn char_at(&self, line: usize, col: usize) -> Option<char> {
+        if col < self.current_buffer().line_len(line) {
+            self.current_buffer().line(line).and_then(|l| l.get_char(col))
+        } else {
+            None
+        }
+    }
+
     /// Insert a newline at cursor position
     pub fn insert_newline(&mut self) {
         let (line, col) = (self.cursor.line, self.cursor.col);
@@ -548,10 +1605,29 @@ impl Editor {
         self.adjust_viewport();
     }
 
+    /// Break the line at the cursor without entering insert mode (the inverse of J): the text
+    /// from the cursor on moves to a new line below, and the cursor moves with it
+    pub fn break_line_at_cursor(&mut self) {
+        self.insert_newline();
+        self.clamp_cursor_col();
+    }
+
+    /// Replace the character at the cursor with a line break (vim r<Enter>)
+    pub fn replace_char_with_line_break(&mut self) {
+        let (line, col) = (self.cursor.line, self.cursor.col);
+        if col < self.current_buffer().line_len(line) {
+            self.current_buffer_mut().delete_char(line, col);
+            self.break_line_at_cursor();
+        }
+    }
+
     /// Delete character at cursor (like 'x' in vim)
     pub fn delete_char_at_cursor(&mut self) {
         let (line, col) = (self.cursor.line, self.cursor.col);
-        self.current_buffer_mut().delete_char(line, col);
+        // Never delete the line break: x on an empty line (or past the end) does nothing
+        if col < self.current_buffer().line_len(line) {
+            self.current_buffer_mut().delete_char(line, col);
+        }
         self.clamp_cursor_col();
     }
 
@@ -588,6 +1664,68 @@ impl Editor {
         self.clamp_cursor_col();
     }
 
+    /// Toggle line comments on lines `start..=end` using the filetype's comment string
+    /// (vim-commentary): if any non-blank line is uncommented, comment them all at their
+    /// common indent; otherwise uncomment them all. Blank lines are left alone.
+    pub fn toggle_comment_lines(&mut self, start: usize, end: usize) {
+        let Some((prefix, suffix)) = self
+            .current_buffer()
+            .filetype
+            .as_deref()
+            .and_then(filetype::comment_string)
+        else {
+            self.set_status("No comment string for this filetype");
+            return;
+        };
+        let end = end.min(self.current_buffer().line_count().saturating_sub(1));
+
+        // (line index, indent width, text after indent) for non-blank lines
+        let mut lines = Vec::new();
+        for line_idx in start..=end {
+            let len = self.current_buffer().line_len(line_idx);
+            let text: String = match self.current_buffer().line(line_idx) {
+                Some(l) => l.chars().take(len).collect(),
+                None => continue,
+            };
+            let body = text.trim_start();
+            if !body.is_empty() {
+                let indent = text.chars().count() - body.chars().count();
+                lines.push((line_idx, indent, body.trim_end().to_string()));
+            }
+        }
+        let is_commented = |body: &str| {
+            body.len() >= prefix.len() + suffix.len()
+                && body.starts_with(prefix)
+                && body.ends_with(suffix)
+        };
+        let comment = lines.iter().any(|(_, _, body)| !is_commented(body));
+        let common_indent = lines.iter().map(|(_, indent, _)| *indent).min().unwrap_or(0);
+
+        for (line_idx, indent, body) in lines {
+            let buffer = self.current_buffer_mut();
+            if comment {
+                if !suffix.is_empty() {
+                    let len = buffer.line_len(line_idx);
+                    buffer.insert_str(line_idx, len, &format!(" {}", suffix));
+                }
+                buffer.insert_str(line_idx, common_indent, &format!("{} ", prefix));
+            } else {
+                let inner = &body[prefix.len()..body.len() - suffix.len()];
+                let inner = inner.strip_prefix(' ').unwrap_or(inner);
+                let inner = if suffix.is_empty() {
+                    inner
+                } else {
+                    inner.strip_suffix(' ').unwrap_or(inner)
+                };
+                let body_len = body.chars().count();
+                let trailing = buffer.line_len(line_idx) - indent - body_len;
+                buffer.delete_range(line_idx, indent, body_len + trailing);
+                buffer.insert_str(line_idx, indent, inner);
+            }
+        }
+        self.clamp_cursor_col();
+    }
+
     /// Delete current line (vim dd); cursor to start of next line or previous if last
     pub fn delete_current_line(&mut self) {
         let line_count = self.current_buffer().line_count();
@@ -605,14 +1743,27 @@ impl Editor {
         if was_last_line && line > 0 {
             self.cursor.line = line - 1;
         }
-        self.cursor.col = 0;
-        self.clamp_cursor_col();
+        self.place_cursor_after_line_motion();
         self.adjust_viewport();
     }
 
-    /// Delete character before cursor (backspace)
+    /// Delete character before cursor (backspace); with autopairs, deletes an empty pair at once
     pub fn backspace(&mut self) {
         let (line, col) = (self.cursor.line, self.cursor.col);
+        if self.options.autopairs && col > 0 {
+            let pair = (self.char_at(line, col - 1), self.char_at(line, col));
+            if matches!(
+                pair,
+                (Some('('), Some(')'))
+                    | (Some('['), Some(']'))
+                    | (Some('{'), Some('}'))
+                    | (Some('"'), Some('"'))
+                    | (Some('\''), Some('\''))
+                    | (Some('`'), Some('`'))
+            ) {
+                self.current_buffer_mut().delete_char(line, col);
+            }
+        }
         if let Some((new_line, new_col)) =
             self.current_buffer_mut().delete_char_before(line, col)
         {
@@ -621,6 +1772,28 @@ impl Editor {
         }
     }
 
+    /// Run the `cursorhold` action (called by App after `updatetime` ms without input)
+    pub fn cursor_hold(&mut self) {
+        match self.options.cursorhold {
+            CursorHold::None => {}
+            CursorHold::CharInfo => {
+                if self.mode == Mode::Normal {
+                    let info = self.char_info_at_cursor();
+                    self.set_status(&info);
+                }
+            }
+        }
+    }
+
+    /// Description of the character under the cursor, like vim's `ga`:
+    /// `<a> 97, Hex 61, Octal 141` (`NUL` on an empty line)
+    pub fn char_info_at_cursor(&self) -> String {
+        match self.char_at(self.cursor.line, self.cursor.col) {
+            Some(ch) => char_info(ch),
+            None => "NUL".to_string(),
+        }
+    }
+
     /// Set a status message
     pub fn set_status(&mut self, msg: &str) {
         self.status_message = Some(msg.to_string());
@@ -633,7 +1806,15 @@ impl Editor {
 
     /// Save the current buffer
     pub fn save(&mut self) -> Result<(), std::io::Error> {
-        self.current_buffer_mut().save()?;
+        self.check_writable()?;
+        let (atomic, fix_eol) = (self.options.atomicsave, self.options.fixeol);
+        let backup = self
+            .current_buffer()
+            .file_path
+            .as_deref()
+            .and_then(|path| self.options.backup_path(path));
+        self.current_buffer_mut().save(atomic, backup.as_deref(), fix_eol)?;
+        self.refresh_git_branch();
         if let Some(name) = self.current_buffer().filename() {
             self.set_status(&format!("\"{}\" written", name));
         } else {
@@ -642,67 +1823,1783 @@ impl Editor {
         Ok(())
     }
 
-    /// Execute a command from the command buffer
-    pub fn execute_command(&mut self) -> Option<EditorCommand> {
-        let cmd = self.command_buffer.trim().to_string();
-        let result = match cmd.as_str() {
-            "q" | "quit" => Some(EditorCommand::Quit),
-            "q!" | "quit!" => Some(EditorCommand::ForceQuit),
-            "bn" | "bnext" => {
-                self.next_buf();
-                None
-            }
-            "bp" | "bprev" | "bprevious" => {
-                self.prev_buf();
+    /// Fails while the `readonly` option is set
+    fn check_writable(&self) -> Result<(), std::io::Error> {
+        if self.options.readonly {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::PermissionDenied,
+                "'readonly' option is set (:set noreadonly to write)",
+            ));
+        }
+        Ok(())
+    }
+
+    /// Re-read the current file from disk (vim :e); unsaved changes are only dropped with
+    /// `force` (:e!), and with `undoreload` they can be brought back with u
+    fn reload(&mut self, force: bool) {
+        if self.current_buffer().file_path.is_none() {
+            self.set_status("No file name");
+            return;
+        }
+        if !force && self.current_buffer().modified {
+            self.set_status("No write since last change (add ! to override)");
+            return;
+        }
+        let (max_size, keep_undo) = (self.options.max_file_size(), self.options.undoreload);
+        if let Err(e) = self.current_buffer_mut().reload(max_size, keep_undo) {
+            self.set_status(&format!("Cannot reload: {}", e));
+            return;
+        }
+        self.folds.clear();
+        self.clamp_cursor_to_buffer();
+        self.apply_modelines();
+        if let Some(name) = self.current_buffer().filename() {
+            self.set_status(&format!("\"{}\" reloaded", name));
+        }
+    }
+
+    /// Save only if the buffer has unsaved changes, then quit (vim :x / ZZ)
+    pub fn write_if_modified_and_quit(&mut self) -> Option<EditorCommand> {
+        if !self.current_buffer().modified {
+            return Some(EditorCommand::Quit);
+        }
+        match self.save() {
+            Ok(_) => Some(EditorCommand::Quit),
+            Err(e) => {
+                self.set_status(&format!("Error saving: {}", e));
                 None
             }
-            "w" | "write" => {
-                match self.save() {
-                    Ok(_) => {}
-                    Err(e) => self.set_status(&format!("Error saving: {}", e)),
-                }
-                None
+        }
+    }
+
+    /// Write the current buffer to `path` (`:w <filename>`)
+    fn write_as(&mut self, path: &str) {
+        if let Err(e) = self.check_writable() {
+            self.set_status(&format!("Error saving: {}", e));
+            return;
+        }
+        let (atomic, fix_eol) = (self.options.atomicsave, self.options.fixeol);
+        let backup = self.options.backup_path(Path::new(path));
+        match self.current_buffer_mut().save_as(path, atomic, backup.as_deref(), fix_eol) {
+            Ok(_) => {
+                self.refresh_git_branch();
+                self.set_status(&format!("\"{}\" written", path));
             }
-            "wq" => {
-                match self.save() {
-                    Ok(_) => Some(EditorCommand::Quit),
-                    Err(e) => {
-                        self.set_status(&format!("Error saving: {}", e));
-                        None
+            Err(e) => self.set_status(&format!("Error saving: {}", e)),
+        }
+    }
+
+    /// `:w !cmd`: run `cmd` through the shell with the buffer on its stdin and show its output
+    /// and exit status. `%` in `cmd` is replaced by the file name (`\%` for a literal `%`).
+    /// This is not a save, so the modified flag is left alone.
+    fn write_to_command(&mut self, command: &str) {
+        let path = self.current_buffer().file_path.as_ref().map(|p| p.display().to_string());
+        let mut expanded = String::new();
+        let mut chars = command.trim().chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' if chars.peek() == Some(&'%') => expanded.push(chars.next().unwrap_or('%')),
+                '%' => match &path {
+                    Some(path) => expanded.push_str(path),
+                    None => {
+                        self.set_status("No file name to substitute for \"%\"");
+                        return;
                     }
-                }
+                },
+                _ => expanded.push(c),
             }
-            _ => {
-                // Check for :w <filename>
-                if let Some(filename) = cmd.strip_prefix("w ").or_else(|| cmd.strip_prefix("write "))
-                {
-                    let path = filename.trim().to_string();
-                    match self.current_buffer_mut().save_as(&path) {
-                        Ok(_) => self.set_status(&format!("\"{}\" written", path)),
-                        Err(e) => self.set_status(&format!("Error saving: {}", e)),
-                    }
-                    None
-                } else {
-                    self.set_status(&format!("Unknown command: {}", cmd));
-                    None
-                }
+        }
+
+        let mut contents = Vec::new();
+        let fix_eol = self.options.fixeol;
+        if let Err(e) = self.current_buffer().write_contents(&mut contents, fix_eol) {
+            self.set_status(&format!("Error writing to command: {}", e));
+            return;
+        }
+        let child = std::process::Command::new("sh")
+            .arg("-c")
+            .arg(&expanded)
+            .stdin(std::process::Stdio::piped())
+            .stdout(std::process::Stdio::piped())
+            .stderr(std::process::Stdio::piped())
+            .spawn();
+        let mut child = match child {
+            Ok(child) => child,
+            Err(e) => {
+                self.set_status(&format!("Cannot run {}: {}", expanded, e));
+                return;
             }
         };
-        self.command_buffer.clear();
-        self.mode = Mode::Normal;
-        result
+        // Feed stdin from another thread so a command that writes a lot of output before
+        // reading all of its input can't deadlock against us
+        let writer = child.stdin.take().map(|mut stdin| {
+            std::thread::spawn(move || {
+                use std::io::Write;
+                let _ = stdin.write_all(&contents);
+            })
+        });
+        let output = child.wait_with_output();
+        self.redraw_pending = true;
+        if let Some(writer) = writer {
+            let _ = writer.join();
+        }
+        match output {
+            Ok(output) => {
+                let mut text = String::from_utf8_lossy(&output.stdout).into_owned();
+                text.push_str(&String::from_utf8_lossy(&output.stderr));
+                let mut msg = text.split_whitespace().collect::<Vec<_>>().join(" ");
+                if !output.status.success() {
+                    let code = output.status.code().map(|c| c.to_string());
+                    let code = code.unwrap_or_else(|| "signal".to_string());
+                    msg = format!("{} [exit {}]", msg, code).trim_start().to_string();
+                }
+                self.set_status(&msg);
+            }
+            Err(e) => self.set_status(&format!("Error running {}: {}", expanded, e)),
+        }
     }
-}
 
-impl Default for Editor {
-    fn default() -> Self {
-        Self::new()
+    /// Apply `:set` arguments; shows queried values or the first error in the status line
+    fn set_options(&mut self, args: &str) {
+        let mut shown = Vec::new();
+        for arg in options::split_set_args(args) {
+            // filetype is buffer-local, so it lives on the buffer rather than in Options
+            if let Some(ft) = arg.strip_prefix("filetype=").or_else(|| arg.strip_prefix("ft=")) {
+                self.current_buffer_mut().filetype = (!ft.is_empty()).then(|| ft.to_string());
+                continue;
+            }
+            if matches!(arg.as_str(), "filetype" | "ft" | "filetype?" | "ft?") {
+                let ft = self.current_buffer().filetype.clone().unwrap_or_default();
+                shown.push(format!("filetype={}", ft));
+                continue;
+            }
+            // bomb is buffer-local too; toggling it changes the file, so the buffer becomes modified
+            if let Some(bomb) = match arg.as_str() {
+                "bomb" => Some(true),
+                "nobomb" => Some(false),
+                "invbomb" | "bomb!" => Some(!self.current_buffer().has_bom),
+                _ => None,
+            } {
+                let buffer = self.current_buffer_mut();
+                if buffer.has_bom != bomb {
+                    buffer.has_bom = bomb;
+                    buffer.modified = true;
+                }
+                continue;
+            }
+            if arg == "bomb?" {
+                let bomb = if self.current_buffer().has_bom { "bomb" } else { "nobomb" };
+                shown.push(bomb.to_string());
+                continue;
+            }
+            match self.options.apply(&arg) {
+                Ok(Some(value)) => shown.push(value),
+                Ok(None) => {}
+                Err(e) => {
+                    self.set_status(&e);
+                    return;
+                }
+            }
+        }
+        if !shown.is_empty() {
+            self.set_status(&shown.join("  "));
+        }
     }
-}
 
-/// Commands that affect the application state
+    /// Convert whitespace runs containing tabs on lines in `range` (vim :retab). Positions are
+    /// computed with the current tabstop and regenerated with `new_tabstop` (which then becomes
+    /// the tabstop), as spaces with expandtab or tabs plus spaces without. With `bang`, runs of
+    /// spaces only are converted too. The cursor keeps its screen column.
+    pub fn retab(&mut self, range: LineRange, bang: bool, new_tabstop: Option<usize>) {
+        let old_ts = self.options.tabstop;
+        let new_ts = new_tabstop.unwrap_or(old_ts);
+        let expandtab = self.options.expandtab;
+        let cursor_vcol = self.current_line_chars().map(|chars| {
+            tabs::display_width(chars[..self.cursor.col.min(chars.len())].iter().copied(), old_ts)
+        });
+
+        let end = range.end.min(self.current_buffer().line_count().saturating_sub(1));
+        for line_idx in range.start..=end {
+            let len = self.current_buffer().line_len(line_idx);
+            let chars: Vec<char> = match self.current_buffer().line(line_idx) {
+                Some(l) => l.chars().take(len).collect(),
+                None => continue,
+            };
+            let mut out = String::new();
+            let mut vcol = 0;
+            let mut i = 0;
+            while i < chars.len() {
+                if chars[i] != ' ' && chars[i] != '\t' {
+                    out.push(chars[i]);
+                    vcol += 1;
+                    i += 1;
+                    continue;
+                }
+                let run_start = i;
+                let start_vcol = vcol;
+                while i < chars.len() && (chars[i] == ' ' || chars[i] == '\t') {
+                    vcol = tabs::advance(vcol, chars[i], old_ts);
+                    i += 1;
+                }
+                let run = &chars[run_start..i];
+                if run.contains(&'\t') || (bang && run.len() > 1) {
+                    out.push_str(&tabs::fill_whitespace(start_vcol, vcol, new_ts, expandtab));
+                } else {
+                    out.extend(run);
+                }
+            }
+            if out.chars().ne(chars.iter().copied()) {
+                let buffer = self.current_buffer_mut();
+                buffer.delete_range(line_idx, 0, len);
+                buffer.insert_str(line_idx, 0, &out);
+            }
+        }
+        self.options.tabstop = new_ts;
+
+        // Put the cursor back on the same screen column
+        if let (Some(target), Some(chars)) = (cursor_vcol, self.current_line_chars()) {
+            let mut vcol = 0;
+            let mut col = 0;
+            while col < chars.len() && chars[col] != '\n' && vcol < target {
+                vcol = tabs::advance(vcol, chars[col], new_ts);
+                col += 1;
+            }
+            self.cursor.col = col;
+        }
+        self.clamp_cursor_col();
+    }
+
+    /// Execute a command that was given a line range, or that defaults to one (`:retab`)
+    fn execute_ranged_command(&mut self, range: Option<LineRange>, cmd: &str) {
+        let line_count = self.current_buffer().line_count();
+        let (name, args) = cmd.split_once(' ').unwrap_or((cmd, ""));
+        let (name, bang) = match name.strip_suffix('!') {
+            Some(n) => (n, true),
+            None => (name, false),
+        };
+        match name {
+            // `:N` jumps to line N
+            "" => {
+                if let Some(range) = range {
+                    self.cursor.line = range.end;
+                    self.place_cursor_after_line_motion();
+                    self.adjust_viewport();
+                }
+            }
+            "ret" | "retab" => {
+                let args = args.trim();
+                let new_tabstop = if args.is_empty() {
+                    None
+                } else {
+                    match options::parse_positive("tabstop", args) {
+                        Ok(ts) => Some(ts),
+                        Err(e) => {
+                            self.set_status(&e);
+                            return;
+                        }
+                    }
+                };
+                self.retab(range.unwrap_or(LineRange::all(line_count)), bang, new_tabstop);
+            }
+            "d" | "de" | "del" | "delete" => {
+                let current = LineRange {
+                    start: self.cursor.line,
+                    end: self.cursor.line,
+                };
+                self.delete_lines(range.unwrap_or(current), args.trim() == "_");
+            }
+            "y" | "ya" | "yank" => {
+                let current = LineRange {
+                    start: self.cursor.line,
+                    end: self.cursor.line,
+                };
+                self.yank_lines(range.unwrap_or(current));
+            }
+            "pu" | "put" => {
+                let line = range.map_or(self.cursor.line, |range| range.end);
+                self.put_lines(Some(line), args.trim());
+            }
+            "sor" | "sort" => match SortFlags::parse(bang, args) {
+                Ok(flags) => self.sort_lines(range.unwrap_or(LineRange::all(line_count)), flags),
+                Err(e) => self.set_status(&e),
+            },
+            _ => self.set_status(&format!("Unknown command: {}", cmd)),
+        }
+    }
+
+    /// Text of whole lines `start..=end`, each with a line break (the register contents of a
+    /// line-wise yank or delete)
+    fn lines_text(&self, start: usize, end: usize) -> String {
+        let buffer = self.current_buffer();
+        let from = buffer.char_idx(start, 0);
+        let to = buffer.char_idx(end, buffer.line_len(end));
+        let mut text = buffer.text_between(from..to);
+        text.push('\n');
+        text
+    }
+
+    /// `:[range]y[ank]`: copy whole lines into the register. The cursor stays put.
+    pub fn yank_lines(&mut self, range: LineRange) {
+        let end = range.end.min(self.current_buffer().last_text_line());
+        let start = range.start.min(end);
+        self.register = self.lines_text(start, end);
+        let yanked = end + 1 - start;
+        if yanked > 2 {
+            self.set_status(&format!("{} lines yanked", yanked));
+        }
+    }
+
+    /// `:reg[isters]` shows the unnamed register (line breaks as `^J`); `:reg clear` empties it
+    fn registers_command(&mut self, args: &str) {
+        match args {
+            "" => {
+                let shown = format!("\"\" {}", self.register.replace('\n', "^J"));
+                self.set_status(&shown);
+            }
+            "clear" => {
+                self.register.clear();
+                self.set_status("Register cleared");
+            }
+            _ => self.set_status(&format!("Invalid argument: {}", args)),
+        }
+    }
+
+    /// `:[line]pu[t]`: put the register as whole lines below `line`, or above the first line
+    /// for None (`:0put`). Text yanked within a line goes on a line of its own too. Only the
+    /// unnamed register (`"`, the default) exists. The cursor goes to the last line put.
+    fn put_lines(&mut self, below: Option<usize>, register: &str) {
+        if !matches!(register, "" | "\"") {
+            self.set_status(&format!("Invalid register name: {}", register));
+            return;
+        }
+        if self.register.is_empty() {
+            self.set_status("Nothing in register \"");
+            return;
+        }
+        let body = self.register.strip_suffix('\n').unwrap_or(&self.register).to_string();
+        let count = body.split('\n').count();
+        let last = match below {
+            Some(line) => {
+                let line = line.min(self.current_buffer().last_text_line());
+                let len = self.current_buffer().line_len(line);
+                self.current_buffer_mut().insert_str(line, len, &format!("\n{}", body));
+                line + count
+            }
+            None => {
+                self.current_buffer_mut().insert_str(0, 0, &format!("{}\n", body));
+                count - 1
+            }
+        };
+        if count > 2 {
+            self.set_status(&format!("{} more lines", count));
+        }
+        self.cursor.line = last;
+        self.move_to_first_non_blank();
+        self.adjust_viewport();
+    }
+
+    /// `:[range]d[elete]`: delete whole lines into the register, or with `blackhole` (`:d _`)
+    /// without touching it (`:%d` empties the buffer, leaving one empty line). The cursor goes
+    /// to the line that followed them.
+    pub fn delete_lines(&mut self, range: LineRange, blackhole: bool) {
+        let end = range.end.min(self.current_buffer().last_text_line());
+        let range = LineRange {
+            start: range.start.min(end),
+            end,
+        };
+        if !blackhole {
+            self.register = self.lines_text(range.start, end);
+        }
+        let buffer = self.current_buffer();
+        let line_count = buffer.line_count();
+        let chars_in = |lines: std::ops::RangeInclusive<usize>| -> usize {
+            lines.map(|l| buffer.line(l).map_or(0, |s| s.len_chars())).sum()
+        };
+        // Take each line with its line break; the buffer's last line has none, so take the
+        // break before the range instead
+        let (line, col, count) = if end + 1 < line_count || range.start == 0 {
+            (range.start, 0, chars_in(range.start..=end))
+        } else {
+            let prev = range.start - 1;
+            let col = buffer.line_len(prev);
+            (prev, col, chars_in(prev..=end) - col)
+        };
+        self.current_buffer_mut().delete_range(line, col, count);
+
+        let deleted = end + 1 - range.start;
+        if deleted > 2 {
+            self.set_status(&format!("{} fewer lines", deleted));
+        }
+        self.cursor.line = range.start.min(self.current_buffer().last_text_line());
+        self.place_cursor_after_line_motion();
+        self.adjust_viewport();
+    }
+
+    /// `:sort`: reorder the lines in `range` (see `sort::sort_lines`). Lines dropped by the
+    /// `u` flag are deleted; the cursor goes to the first line of the range.
+    pub fn sort_lines(&mut self, range: LineRange, flags: SortFlags) {
+        let buffer = self.current_buffer();
+        let end = range.end.min(buffer.last_text_line()).max(range.start);
+        let old: Vec<String> = (range.start..=end)
+            .map(|l| {
+                let len = buffer.line_len(l);
+                buffer.line(l).map(|s| s.chars().take(len).collect()).unwrap_or_default()
+            })
+            .collect();
+        let mut lines = old.clone();
+        sort::sort_lines(&mut lines, flags);
+
+        let buffer = self.current_buffer_mut();
+        for (offset, (new, old)) in lines.iter().zip(&old).enumerate() {
+            if new != old {
+                let line = range.start + offset;
+                buffer.delete_range(line, 0, buffer.line_len(line));
+                buffer.insert_str(line, 0, new);
+            }
+        }
+        // Remove the lines left over after dropping duplicates, with the line breaks before them
+        let kept_end = range.start + lines.len() - 1;
+        if kept_end < end {
+            let from_col = buffer.line_len(kept_end);
+            let through_end: usize =
+                (kept_end..end).map(|l| buffer.line(l).map_or(0, |s| s.len_chars())).sum();
+            buffer.delete_range(kept_end, from_col, through_end - from_col + buffer.line_len(end));
+        }
+
+        self.cursor.line = range.start;
+        self.place_cursor_after_line_motion();
+        self.adjust_viewport();
+    }
+
+    /// `:cd [dir]`: change the working directory. See `cd_target` for the accepted forms.
+    fn change_directory(&mut self, arg: &str) -> Option<EditorCommand> {
+        let home = std::env::var_os("HOME").map(PathBuf::from);
+        let file = self.current_buffer().file_path.clone();
+        let previous = self.previous_dir.as_deref();
+        let target = match cd_target(arg, file.as_deref(), home.as_deref(), previous) {
+            Ok(target) => target,
+            Err(e) => {
+                self.set_status(&e);
+                return None;
+            }
+        };
+        let old = std::env::current_dir().ok();
+        if let Err(e) = std::env::set_current_dir(&target) {
+            self.set_status(&format!("Cannot change directory to {}: {}", target.display(), e));
+            return None;
+        }
+        self.previous_dir = old;
+        let dir = std::env::current_dir().unwrap_or(target);
+        self.set_status(&dir.display().to_string());
+        Some(EditorCommand::ChangedDirectory(dir))
+    }
+
+    /// Execute a command from the command buffer
+    pub fn execute_command(&mut self) -> Option<EditorCommand> {
+        let full_cmd = self.command_buffer.trim().to_string();
+        let (range, cmd) = range::parse_range(
+            &full_cmd,
+            self.cursor.line,
+            self.current_buffer().line_count(),
+        );
+        let cmd = cmd.to_string();
+        let name = cmd.split(' ').next().unwrap_or("").trim_end_matches('!');
+        let ranged = [
+            "ret", "retab", "sor", "sort", "d", "de", "del", "delete", "y", "ya", "yank", "pu",
+            "put",
+        ];
+        // `:0put` puts above the first line, which the range parser would clamp to line 1
+        if let Some(rest) = full_cmd.strip_prefix('0').filter(|rest| {
+            matches!(rest.split(' ').next(), Some("pu" | "put"))
+        }) {
+            let register = rest.split_once(' ').map_or("", |(_, reg)| reg.trim());
+            self.put_lines(None, register);
+            self.command_buffer.clear();
+            self.mode = Mode::Normal;
+            return None;
+        }
+        if range.is_some() || ranged.contains(&name) {
+            self.execute_ranged_command(range, &cmd);
+            self.command_buffer.clear();
+            self.mode = Mode::Normal;
+            return None;
+        }
+        let result = match cmd.as_str() {
+            "q" | "quit" => Some(EditorCommand::Quit),
+            "q!" | "quit!" => Some(EditorCommand::ForceQuit),
+            "bn" | "bnext" => {
+                self.next_buf();
+                None
+            }
+            "bp" | "bprev" | "bprevious" => {
+                self.prev_buf();
+                None
+            }
+            "w" | "write" => {
+                match self.save() {
+                    Ok(_) => {}
+                    Err(e) => self.set_status(&format!("Error saving: {}", e)),
+                }
+                None
+            }
+            "wq" => {
+                match self.save() {
+                    Ok(_) => Some(EditorCommand::Quit),
+                    Err(e) => {
+                        self.set_status(&format!("Error saving: {}", e));
+                        None
+                    }
+                }
+            }
+            "x" | "xit" | "exit" => self.write_if_modified_and_quit(),
+            "redr" | "redraw" | "redr!" | "redraw!" => {
+                self.redraw_pending = true;
+                None
+            }
+            "e" | "edit" => {
+                self.reload(false);
+                None
+            }
+            "e!" | "edit!" => {
+                self.reload(true);
+                None
+            }
+            "pw" | "pwd" => {
+                match std::env::current_dir() {
+                    Ok(dir) => self.set_status(&dir.display().to_string()),
+                    Err(e) => self.set_status(&format!("{}", e)),
+                }
+                None
+            }
+            _ => {
+                let (name, args) = cmd.split_once(' ').unwrap_or((cmd.as_str(), ""));
+                // Check for :w <filename>
+                if let Some(filename) = cmd.strip_prefix("w ").or_else(|| cmd.strip_prefix("write "))
+                {
+                    // :w !cmd pipes the buffer into a shell command instead
+                    match filename.strip_prefix('!') {
+                        Some(command) => self.write_to_command(command),
+                        None => self.write_as(filename.trim()),
+                    }
+                    None
+                } else if let Some(args) = cmd.strip_prefix("set ").or_else(|| cmd.strip_prefix("se ")) {
+                    self.set_options(args);
+                    None
+                } else if matches!(name, "ab" | "abbrev" | "abbreviate" | "iab" | "iabbrev") {
+                    self.abbreviate(args);
+                    None
+                } else if matches!(name, "reg" | "registers") {
+                    self.registers_command(args.trim());
+                    None
+                } else if matches!(name, "cd" | "chd" | "chdir") {
+                    self.change_directory(args.trim())
+                } else if matches!(name, "una" | "unabbreviate" | "iuna" | "iunabbrev") {
+                    if self.abbreviations.remove(args.trim()).is_none() {
+                        self.set_status("No such abbreviation");
+                    }
+                    None
+                } else {
+                    self.set_status(&format!("Unknown command: {}", cmd));
+                    None
+                }
+            }
+        };
+        self.command_buffer.clear();
+        self.mode = Mode::Normal;
+        result
+    }
+}
+
+/// Format `ch` as `<c> dec, Hex hex, Octal oct`. Control characters are shown as `^X`;
+/// non-ASCII code points get 4 (or 8) hex digits, like vim.
+fn char_info(ch: char) -> String {
+    let code = ch as u32;
+    let shown = match code {
+        0..=0x1f => format!("^{}", char::from_u32(code + 0x40).unwrap_or('?')),
+        0x7f => "^?".to_string(),
+        _ => ch.to_string(),
+    };
+    let hex = match code {
+        0..=0x7f => format!("{:02x}", code),
+        0x80..=0xffff => format!("{:04x}", code),
+        _ => format!("{:08x}", code),
+    };
+    format!("<{}> {}, Hex {}, Octal {:o}", shown, code, hex, code)
+}
+
+/// Character classes for subword motions
+#[derive(PartialEq, Eq)]
+enum SubwordClass {
+    Space,
+    Underscore,
+    Lower,
+    Upper,
+    Digit,
+    Punct,
+}
+
+fn subword_class(ch: char) -> SubwordClass {
+    if ch.is_whitespace() {
+        SubwordClass::Space
+    } else if ch == '_' {
+        SubwordClass::Underscore
+    } else if ch.is_uppercase() {
+        SubwordClass::Upper
+    } else if ch.is_alphabetic() {
+        SubwordClass::Lower
+    } else if ch.is_numeric() {
+        SubwordClass::Digit
+    } else {
+        SubwordClass::Punct
+    }
+}
+
+/// Whether a subword starts at `chars[i]`: after whitespace/underscores, at a lower→upper hump
+/// (fooBar), at the last capital of an acronym (HTTPServer), and where digits or punctuation begin
+fn is_subword_start(chars: &[char], i: usize) -> bool {
+    use SubwordClass::*;
+    let cur = subword_class(chars[i]);
+    if matches!(cur, Space | Underscore) {
+        return false;
+    }
+    let Some(&prev) = i.checked_sub(1).and_then(|p| chars.get(p)) else {
+        return true;
+    };
+    match (subword_class(prev), cur) {
+        (Upper, Lower) => false,
+        (Upper, Upper) => chars.get(i + 1).is_some_and(|&next| subword_class(next) == Lower),
+        (prev, cur) => prev != cur,
+    }
+}
+
+/// Directory `:cd` goes to: no argument or `~` means home, `~/dir` is under home, `-` is
+/// the previous directory and `%` the current file's directory. Anything else is used as
+/// given (relative to the working directory).
+fn cd_target(
+    arg: &str,
+    file: Option<&Path>,
+    home: Option<&Path>,
+    previous: Option<&Path>,
+) -> Result<PathBuf, String> {
+    let home_dir = || home.map(Path::to_path_buf).ok_or_else(|| "HOME is not set".to_string());
+    match arg {
+        "" | "~" => home_dir(),
+        "-" => previous
+            .map(Path::to_path_buf)
+            .ok_or_else(|| "No previous directory".to_string()),
+        "%" => {
+            let file = file.ok_or_else(|| "No file name to substitute for \"%\"".to_string())?;
+            Ok(match file.parent() {
+                Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+                _ => PathBuf::from("."),
+            })
+        }
+        _ => match arg.strip_prefix("~/") {
+            Some(rest) => Ok(home_dir()?.join(rest)),
+            None => Ok(PathBuf::from(arg)),
+        },
+    }
+}
+
+/// `[index/total]` for `searchcount`; counts above `limit` show as `>limit`
+fn format_search_count(index: Option<usize>, total: usize, limit: usize) -> String {
+    let show = |n: Option<usize>| match n {
+        Some(n) if n <= limit => n.to_string(),
+        _ => format!(">{}", limit),
+    };
+    format!("[{}/{}]", show(index), show(Some(total)))
+}
+
+/// Characters that make up words for abbreviations and `cursorword`
+fn is_keyword_char(ch: char) -> bool {
+    ch.is_alphanumeric() || ch == '_'
+}
+
+/// Column ranges where `word` occurs in `chars` as a whole word (not inside a longer one)
+pub fn whole_word_matches(chars: &[char], word: &[char]) -> Vec<std::ops::Range<usize>> {
+    if word.is_empty() || word.len() > chars.len() {
+        return Vec::new();
+    }
+    (0..=chars.len() - word.len())
+        .filter(|&start| {
+            let end = start + word.len();
+            chars[start..end] == *word
+                && (start == 0 || !is_keyword_char(chars[start - 1]))
+                && chars.get(end).is_none_or(|&c| !is_keyword_char(c))
+        })
+        .map(|start| start..start + word.len())
+        .collect()
+}
+
+impl Default for Editor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Commands that affect the application state
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum EditorCommand {
     Quit,
     ForceQuit,
+    /// The working directory changed (`:cd`); an open explorer follows it
+    ChangedDirectory(PathBuf),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::temp_dir;
+
+    /// An editor whose only buffer holds `text`, unmodified
+    fn editor(text: &str) -> Editor {
+        let mut editor = Editor::new();
+        let buffer = editor.current_buffer_mut();
+        buffer.insert_str(0, 0, text);
+        buffer.commit_undo_step();
+        buffer.modified = false;
+        editor
+    }
+
+    /// Run an ex command as if typed after `:`
+    fn run(editor: &mut Editor, command: &str) -> Option<EditorCommand> {
+        editor.command_buffer = command.to_string();
+        editor.execute_command()
+    }
+
+    /// Whole text of the current buffer
+    fn text(editor: &Editor) -> String {
+        let buffer = editor.current_buffer();
+        (0..buffer.line_count())
+            .filter_map(|l| buffer.line(l))
+            .map(|l| l.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn comments_rust_lines_at_common_indent() {
+        let mut editor = editor("fn f() {\n    a();\n\n        b();\n}\n");
+        editor.current_buffer_mut().filetype = Some("rust".to_string());
+        editor.toggle_comment_lines(1, 3);
+        assert_eq!(text(&editor), "fn f() {\n    // a();\n\n    //     b();\n}\n");
+        editor.toggle_comment_lines(1, 3);
+        assert_eq!(text(&editor), "fn f() {\n    a();\n\n        b();\n}\n");
+    }
+
+    #[test]
+    fn comments_all_python_lines_when_any_is_uncommented() {
+        let mut editor = editor("# a\nb\n# c\n");
+        editor.current_buffer_mut().filetype = Some("python".to_string());
+        editor.toggle_comment_lines(0, 2);
+        assert_eq!(text(&editor), "# # a\n# b\n# # c\n");
+        editor.toggle_comment_lines(0, 2);
+        assert_eq!(text(&editor), "# a\nb\n# c\n");
+    }
+
+    #[test]
+    fn no_comment_string_leaves_text_alone() {
+        let mut editor = editor("a\n");
+        editor.toggle_comment_lines(0, 0);
+        assert_eq!(text(&editor), "a\n");
+        assert_eq!(
+            editor.status_message.as_deref(),
+            Some("No comment string for this filetype")
+        );
+    }
+
+    /// Type `keys` in insert mode, one character at a time
+    fn type_keys(editor: &mut Editor, keys: &str) {
+        editor.mode = Mode::Insert;
+        for ch in keys.chars() {
+            editor.insert_typed_char(ch);
+        }
+    }
+
+    #[test]
+    fn autopairs_insert_the_closer() {
+        let mut editor = editor("\n");
+        editor.options.autopairs = true;
+        type_keys(&mut editor, "f(");
+        assert_eq!(text(&editor), "f()\n");
+        assert_eq!(editor.cursor.col, 2);
+        type_keys(&mut editor, "\"");
+        assert_eq!(text(&editor), "f(\"\")\n");
+    }
+
+    #[test]
+    fn autopairs_skip_over_a_typed_closer() {
+        let mut editor = editor("\n");
+        editor.options.autopairs = true;
+        type_keys(&mut editor, "[x]");
+        assert_eq!(text(&editor), "[x]\n");
+        assert_eq!(editor.cursor.col, 3);
+    }
+
+    #[test]
+    fn autopairs_leave_apostrophes_in_words_alone() {
+        let mut editor = editor("\n");
+        editor.options.autopairs = true;
+        type_keys(&mut editor, "don't");
+        assert_eq!(text(&editor), "don't\n");
+    }
+
+    #[test]
+    fn backspace_deletes_an_empty_pair() {
+        let mut editor = editor("\n");
+        editor.options.autopairs = true;
+        type_keys(&mut editor, "{");
+        editor.backspace();
+        assert_eq!(text(&editor), "\n");
+        assert_eq!(editor.cursor.col, 0);
+    }
+
+    #[test]
+    fn no_pairs_when_autopairs_is_off() {
+        let mut editor = editor("\n");
+        type_keys(&mut editor, "(");
+        assert_eq!(text(&editor), "(\n");
+    }
+
+    #[test]
+    fn typed_pair_is_one_undo_step() {
+        let mut editor = editor("\n");
+        editor.options.autopairs = true;
+        type_keys(&mut editor, "(a");
+        editor.enter_normal_mode();
+        editor.finish_undo_step();
+        editor.undo();
+        assert_eq!(text(&editor), "\n");
+    }
+
+    #[test]
+    fn exit_writes_a_modified_buffer() {
+        let path = temp_dir("exit-modified").join("f.txt");
+        std::fs::write(&path, "old\n").unwrap();
+        let mut editor = Editor::with_file(path.to_str().unwrap(), None).unwrap();
+        editor.insert_char('n');
+        assert_eq!(run(&mut editor, "x"), Some(EditorCommand::Quit));
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "nold\n");
+    }
+
+    #[test]
+    fn readonly_refuses_to_write_the_file() {
+        let path = temp_dir("readonly").join("f.txt");
+        std::fs::write(&path, "old\n").unwrap();
+        let mut editor = Editor::with_file(path.to_str().unwrap(), None).unwrap();
+        editor.options.readonly = true;
+        editor.insert_char('n');
+        assert_eq!(run(&mut editor, "w"), None);
+        assert_eq!(run(&mut editor, "x"), None);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "old\n");
+        assert!(editor.status_message.as_deref().unwrap().contains("'readonly' option is set"));
+        let copy = path.with_file_name("copy.txt");
+        run(&mut editor, &format!("w {}", copy.display()));
+        assert!(!copy.exists());
+        editor.options.apply("noro").unwrap();
+        assert_eq!(run(&mut editor, "x"), Some(EditorCommand::Quit));
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "nold\n");
+    }
+
+    #[test]
+    fn text_from_a_reader_is_a_modified_unnamed_buffer() {
+        let editor = Editor::with_reader("#!/bin/sh\necho hi".as_bytes()).unwrap();
+        let buffer = editor.current_buffer();
+        assert_eq!(text(&editor), "#!/bin/sh\necho hi");
+        assert_eq!(buffer.file_path, None);
+        assert!(buffer.modified);
+        assert!(!buffer.eol);
+        assert_eq!(buffer.filetype.as_deref(), Some("sh"));
+    }
+
+    #[test]
+    fn exit_does_not_write_an_unmodified_buffer() {
+        let path = temp_dir("exit-unmodified").join("f.txt");
+        std::fs::write(&path, "old\n").unwrap();
+        let mut editor = Editor::with_file(path.to_str().unwrap(), None).unwrap();
+        // Changed behind the editor's back: a write would put "old" back
+        std::fs::write(&path, "changed\n").unwrap();
+        assert_eq!(run(&mut editor, "exit"), Some(EditorCommand::Quit));
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "changed\n");
+    }
+
+    #[test]
+    fn exit_stays_open_when_the_write_fails() {
+        let dir = temp_dir("exit-fails");
+        let mut editor = Editor::with_new_file(dir.join("missing/f.txt").to_str().unwrap());
+        editor.insert_char('a');
+        assert_eq!(run(&mut editor, "x"), None);
+        assert!(editor.status_message.unwrap().starts_with("Error saving"));
+    }
+
+    /// Text of `count` numbered lines
+    fn numbered_lines(count: usize) -> String {
+        (0..count).map(|i| format!("line {}\n", i)).collect()
+    }
+
+    #[test]
+    fn large_scrolloff_keeps_the_cursor_centered() {
+        let mut editor = editor(&numbered_lines(100));
+        editor.options.scrolloff = 999;
+        for line in [30, 50, 51, 70] {
+            editor.cursor.line = line;
+            editor.adjust_viewport_with_height(21);
+            assert_eq!(editor.viewport_offset, line - 10);
+        }
+    }
+
+    #[test]
+    fn scrolloff_has_less_context_at_the_buffer_start() {
+        let mut editor = editor(&numbered_lines(100));
+        editor.options.scrolloff = 999;
+        editor.cursor.line = 3;
+        editor.adjust_viewport_with_height(21);
+        assert_eq!(editor.viewport_offset, 0);
+    }
+
+    #[test]
+    fn scrolloff_keeps_rows_below_the_cursor() {
+        let mut editor = editor(&numbered_lines(100));
+        editor.options.scrolloff = 3;
+        editor.cursor.line = 20;
+        editor.adjust_viewport_with_height(10);
+        // Rows 0..10 show lines 14..24: three below the cursor
+        assert_eq!(editor.viewport_offset, 14);
+    }
+
+    #[test]
+    fn line_cache_is_refreshed_after_an_edit_on_the_line() {
+        let mut editor = editor("abc def\n");
+        assert_eq!(&editor.current_line_chars().unwrap()[..3], ['a', 'b', 'c']);
+        editor.insert_char('x');
+        assert_eq!(&editor.current_line_chars().unwrap()[..3], ['x', 'a', 'b']);
+        editor.move_word_forward();
+        assert_eq!(editor.cursor.col, 5);
+    }
+
+    #[test]
+    fn line_cache_follows_the_cursor_line() {
+        let mut editor = editor("abc\nxyz\n");
+        assert_eq!(editor.current_line_chars().unwrap()[0], 'a');
+        editor.move_down();
+        assert_eq!(editor.current_line_chars().unwrap()[0], 'x');
+    }
+
+    /// Repeated `w` on a 1MB line, with the line cache and with it dropped before every
+    /// motion (as if each one collected the line again)
+    #[test]
+    fn word_motion_on_a_long_line_reuses_the_line() {
+        let line = "word ".repeat(200_000);
+        let motions = 20;
+        let mut editor = editor(&format!("{}\n", line));
+
+        let start = std::time::Instant::now();
+        for _ in 0..motions {
+            editor.move_word_forward();
+        }
+        let cached = start.elapsed();
+        assert_eq!(editor.cursor.col, motions * 5);
+
+        editor.cursor.col = 0;
+        let start = std::time::Instant::now();
+        for _ in 0..motions {
+            *editor.line_cache.borrow_mut() = None;
+            editor.move_word_forward();
+        }
+        let uncached = start.elapsed();
+        assert_eq!(editor.cursor.col, motions * 5);
+
+        // One collection of the line against one per motion
+        assert!(cached * 5 < uncached);
+    }
+
+    #[test]
+    fn retab_converts_leading_tabs_to_spaces() {
+        let mut editor = editor("\tone\n\t\ttwo\nthree\n");
+        editor.options.tabstop = 4;
+        editor.cursor = Cursor { line: 1, col: 2 };
+        run(&mut editor, "retab");
+        assert_eq!(text(&editor), "    one\n        two\nthree\n");
+        assert_eq!(editor.current_buffer().line_len(0), 7);
+        assert_eq!(editor.current_buffer().line_len(1), 11);
+        // The cursor stays on the same screen column, the `t` of "two"
+        assert_eq!(editor.cursor.col, 8);
+    }
+
+    #[test]
+    fn retab_with_a_range_and_new_tabstop() {
+        let mut editor = editor("\ta\n\tb\n\tc\n");
+        run(&mut editor, "2,3retab 2");
+        assert_eq!(text(&editor), "\ta\n        b\n        c\n");
+        assert_eq!(editor.options.tabstop, 2);
+    }
+
+    #[test]
+    fn retab_bang_turns_spaces_into_tabs_without_expandtab() {
+        let mut editor = editor("        x\n");
+        editor.options.expandtab = false;
+        run(&mut editor, "retab! 4");
+        assert_eq!(text(&editor), "\t\tx\n");
+    }
+
+    #[test]
+    fn backspace_at_line_start_joins_onto_the_previous_line() {
+        let mut editor = editor("abc\ndef\n");
+        editor.mode = Mode::Insert;
+        editor.cursor = Cursor { line: 1, col: 0 };
+        editor.backspace();
+        assert_eq!(text(&editor), "abcdef\n");
+        assert_eq!((editor.cursor.line, editor.cursor.col), (0, 3));
+    }
+
+    #[test]
+    fn backspace_at_buffer_start_does_nothing() {
+        let mut editor = editor("abc\n");
+        editor.mode = Mode::Insert;
+        editor.backspace();
+        assert_eq!(text(&editor), "abc\n");
+        assert_eq!((editor.cursor.line, editor.cursor.col), (0, 0));
+    }
+
+    const INDENTED: &str = "    first\nsecond line\n        third\n";
+
+    #[test]
+    fn line_motions_go_to_the_first_non_blank_with_startofline() {
+        let mut editor = editor(INDENTED);
+        editor.cursor.col = 6;
+        editor.move_to_last_line();
+        assert_eq!((editor.cursor.line, editor.cursor.col), (3, 0));
+        editor.move_to_first_line();
+        assert_eq!((editor.cursor.line, editor.cursor.col), (0, 4));
+        run(&mut editor, "3");
+        assert_eq!((editor.cursor.line, editor.cursor.col), (2, 8));
+        editor.cursor = Cursor { line: 1, col: 6 };
+        editor.delete_current_line();
+        assert_eq!((editor.cursor.line, editor.cursor.col), (1, 8));
+    }
+
+    #[test]
+    fn line_motions_keep_the_column_without_startofline() {
+        let mut editor = editor(INDENTED);
+        editor.options.startofline = false;
+        editor.cursor = Cursor { line: 1, col: 6 };
+        editor.move_to_first_line();
+        assert_eq!((editor.cursor.line, editor.cursor.col), (0, 6));
+        run(&mut editor, "3");
+        assert_eq!((editor.cursor.line, editor.cursor.col), (2, 6));
+        editor.cursor = Cursor { line: 1, col: 6 };
+        editor.delete_current_line();
+        assert_eq!((editor.cursor.line, editor.cursor.col), (1, 6));
+        // Clamped to a shorter line
+        editor.cursor = Cursor { line: 0, col: 8 };
+        editor.move_to_last_line();
+        assert_eq!((editor.cursor.line, editor.cursor.col), (2, 0));
+    }
+
+    #[test]
+    fn searchcenter_centers_the_match_after_n() {
+        let mut editor = editor(&format!("{}target\n{}", numbered_lines(60), numbered_lines(60)));
+        editor.options.searchcenter = true;
+        editor.last_search_pattern = Some("target".to_string());
+        editor.adjust_viewport_with_height(21);
+        assert!(editor.repeat_search_forward());
+        editor.adjust_viewport_with_height(21);
+        assert_eq!(editor.cursor.line, 60);
+        assert_eq!(editor.viewport_offset, 50);
+    }
+
+    #[test]
+    fn without_searchcenter_the_match_is_just_scrolled_into_view() {
+        let mut editor = editor(&format!("{}target\n{}", numbered_lines(60), numbered_lines(60)));
+        editor.last_search_pattern = Some("target".to_string());
+        editor.adjust_viewport_with_height(21);
+        assert!(editor.repeat_search_forward());
+        editor.adjust_viewport_with_height(21);
+        assert_eq!(editor.viewport_offset, 40);
+    }
+
+    #[test]
+    fn x_on_an_empty_line_does_not_join_lines() {
+        let mut editor = editor("a\n\nb\n");
+        editor.cursor.line = 1;
+        editor.delete_char_at_cursor();
+        assert_eq!(text(&editor), "a\n\nb\n");
+    }
+
+    #[test]
+    fn repeated_x_at_line_end_stops_at_the_line_start() {
+        let mut editor = editor("abc\nnext\n");
+        editor.cursor.col = 1;
+        for _ in 0..5 {
+            editor.delete_char_at_cursor();
+        }
+        assert_eq!(text(&editor), "\nnext\n");
+        assert_eq!((editor.cursor.line, editor.cursor.col), (0, 0));
+    }
+
+    #[test]
+    fn abbreviation_expands_after_a_non_keyword_character() {
+        let mut editor = editor("\n");
+        run(&mut editor, "iabbrev teh the");
+        type_keys(&mut editor, "teh cat, teh.");
+        assert_eq!(text(&editor), "the cat, the.\n");
+        assert_eq!(editor.cursor.col, 13);
+    }
+
+    #[test]
+    fn abbreviation_only_matches_whole_words() {
+        let mut editor = editor("\n");
+        run(&mut editor, "iab teh the");
+        type_keys(&mut editor, "steh tehx ");
+        assert_eq!(text(&editor), "steh tehx \n");
+    }
+
+    #[test]
+    fn unabbreviate_removes_an_abbreviation() {
+        let mut editor = editor("\n");
+        run(&mut editor, "iab teh the");
+        run(&mut editor, "unabbreviate teh");
+        type_keys(&mut editor, "teh ");
+        assert_eq!(text(&editor), "teh \n");
+        run(&mut editor, "una teh");
+        assert_eq!(editor.status_message.as_deref(), Some("No such abbreviation"));
+    }
+
+    #[test]
+    fn expanded_insert_is_undone_in_one_step() {
+        let mut editor = editor("\n");
+        run(&mut editor, "iab teh the");
+        type_keys(&mut editor, "teh ");
+        editor.enter_normal_mode();
+        editor.finish_undo_step();
+        editor.undo();
+        assert_eq!(text(&editor), "\n");
+    }
+
+    /// Columns visited by repeating `motion` until it stops moving or leaves the line
+    fn stops(editor: &mut Editor, motion: fn(&mut Editor)) -> Vec<usize> {
+        let mut stops = Vec::new();
+        loop {
+            let before = (editor.cursor.line, editor.cursor.col);
+            motion(editor);
+            if editor.cursor.line != before.0 || (editor.cursor.line, editor.cursor.col) == before {
+                return stops;
+            }
+            stops.push(editor.cursor.col);
+        }
+    }
+
+    #[test]
+    fn subword_motions_stop_at_camel_case_humps() {
+        let mut editor = editor("fooBarBaz\n");
+        assert_eq!(stops(&mut editor, Editor::move_subword_forward), vec![3, 6]);
+        editor.cursor = Cursor { line: 0, col: 8 };
+        assert_eq!(stops(&mut editor, Editor::move_subword_backward), vec![6, 3, 0]);
+    }
+
+    #[test]
+    fn subword_motions_stop_after_underscores() {
+        let mut editor = editor("foo_bar_baz\n");
+        assert_eq!(stops(&mut editor, Editor::move_subword_forward), vec![4, 8]);
+        editor.cursor = Cursor { line: 0, col: 10 };
+        assert_eq!(stops(&mut editor, Editor::move_subword_backward), vec![8, 4, 0]);
+    }
+
+    #[test]
+    fn subword_motion_continues_on_the_next_line() {
+        let mut editor = editor("fooBar\n  nextWord\n");
+        editor.cursor.col = 3;
+        editor.move_subword_forward();
+        assert_eq!((editor.cursor.line, editor.cursor.col), (1, 2));
+        editor.move_subword_backward();
+        assert_eq!((editor.cursor.line, editor.cursor.col), (0, 3));
+    }
+
+    #[test]
+    fn switching_buffers_restores_each_cursor_and_scroll() {
+        let dir = temp_dir("buffer-views");
+        let (a, b) = (dir.join("a.txt"), dir.join("b.txt"));
+        std::fs::write(&a, numbered_lines(100)).unwrap();
+        std::fs::write(&b, numbered_lines(100)).unwrap();
+        let mut editor = Editor::with_file(a.to_str().unwrap(), None).unwrap();
+        editor.cursor = Cursor { line: 50, col: 3 };
+        editor.viewport_offset = 40;
+        editor.open_file_into_new_buffer(b.to_str().unwrap()).unwrap();
+        assert_eq!((editor.cursor.line, editor.cursor.col, editor.viewport_offset), (0, 0, 0));
+        editor.cursor = Cursor { line: 7, col: 1 };
+        editor.prev_buf();
+        assert_eq!((editor.cursor.line, editor.cursor.col, editor.viewport_offset), (50, 3, 40));
+        editor.next_buf();
+        assert_eq!((editor.cursor.line, editor.cursor.col), (7, 1));
+    }
+
+    #[test]
+    fn whichwrap_lets_h_and_l_cross_lines() {
+        let mut editor = editor("ab\ncd");
+        editor.cursor = Cursor { line: 1, col: 0 };
+        editor.move_left_wrapping('h');
+        assert_eq!((editor.cursor.line, editor.cursor.col), (1, 0));
+        editor.options.apply("whichwrap=h,l").unwrap();
+        editor.move_left_wrapping('h');
+        assert_eq!((editor.cursor.line, editor.cursor.col), (0, 1));
+        editor.move_right_wrapping('l');
+        assert_eq!((editor.cursor.line, editor.cursor.col), (1, 0));
+        // Only the listed keys wrap
+        editor.move_left_wrapping('<');
+        assert_eq!((editor.cursor.line, editor.cursor.col), (1, 0));
+    }
+
+    #[test]
+    fn whichwrap_stops_at_the_buffer_edges() {
+        let mut editor = editor("ab\ncd");
+        editor.options.apply("ww=h,l").unwrap();
+        editor.move_left_wrapping('h');
+        assert_eq!((editor.cursor.line, editor.cursor.col), (0, 0));
+        editor.cursor = Cursor { line: 1, col: 1 };
+        editor.move_right_wrapping('l');
+        assert_eq!((editor.cursor.line, editor.cursor.col), (1, 1));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn write_to_command_pipes_the_buffer_into_the_shell() {
+        let mut editor = editor("one\ntwo\n");
+        editor.current_buffer_mut().modified = true;
+        run(&mut editor, "w !cat");
+        assert_eq!(editor.status_message.as_deref(), Some("one two"));
+        run(&mut editor, "w !wc -l");
+        assert_eq!(editor.status_message.as_deref(), Some("2"));
+        // Not a save
+        assert!(editor.current_buffer().modified);
+        assert!(editor.redraw_pending);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn write_to_command_reports_a_failing_exit_status() {
+        let mut editor = editor("x\n");
+        run(&mut editor, "w !exit 3");
+        assert_eq!(editor.status_message.as_deref(), Some("[exit 3]"));
+    }
+
+    #[test]
+    fn write_with_a_filename_still_saves_a_copy() {
+        let path = temp_dir("write-as").join("copy.txt");
+        let mut editor = editor("copy\n");
+        run(&mut editor, &format!("w {}", path.display()));
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "copy\n");
+    }
+
+    #[test]
+    fn char_info_formats_like_ga() {
+        assert_eq!(char_info('a'), "<a> 97, Hex 61, Octal 141");
+        assert_eq!(char_info('\t'), "<^I> 9, Hex 09, Octal 11");
+        assert_eq!(char_info('\u{7f}'), "<^?> 127, Hex 7f, Octal 177");
+        assert_eq!(char_info('é'), "<é> 233, Hex 00e9, Octal 351");
+        assert_eq!(char_info('😀'), "<😀> 128512, Hex 0001f600, Octal 373000");
+    }
+
+    #[test]
+    fn ga_on_an_empty_line_shows_nul() {
+        let mut editor = editor("\nxy");
+        assert_eq!(editor.char_info_at_cursor(), "NUL");
+        editor.cursor = Cursor { line: 1, col: 1 };
+        assert_eq!(editor.char_info_at_cursor(), "<y> 121, Hex 79, Octal 171");
+    }
+
+    #[test]
+    fn fixeol_write_does_not_record_an_undo_step() {
+        let dir = temp_dir("fixeol-undo");
+        let path = dir.join("f.txt");
+        std::fs::write(&path, "a").unwrap();
+        let mut editor = Editor::with_file(path.to_str().unwrap(), None).unwrap();
+        editor.options.apply("fixeol").unwrap();
+        run(&mut editor, "w");
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "a\n");
+        let copy = dir.join("copy.txt");
+        run(&mut editor, &format!("w {}", copy.display()));
+        assert_eq!(std::fs::read_to_string(&copy).unwrap(), "a\n");
+        editor.undo();
+        assert_eq!(editor.status_message.as_deref(), Some("Already at oldest change"));
+        assert!(!editor.current_buffer().modified);
+    }
+
+    #[test]
+    fn failed_fixeol_write_keeps_the_noeol_state() {
+        let dir = temp_dir("fixeol-failed");
+        let path = dir.join("f.txt");
+        std::fs::write(&path, "a").unwrap();
+        let mut editor = Editor::with_file(path.to_str().unwrap(), None).unwrap();
+        editor.options.apply("fixeol").unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        run(&mut editor, "w");
+        assert!(editor.status_message.as_deref().unwrap().starts_with("Error saving"));
+        let elsewhere = dir.join("missing").join("copy.txt");
+        run(&mut editor, &format!("w {}", elsewhere.display()));
+        assert!(editor.status_message.as_deref().unwrap().starts_with("Error saving"));
+        assert!(!editor.current_buffer().eol);
+
+        // Without fixeol the next successful write still leaves the line break out
+        std::fs::create_dir_all(&dir).unwrap();
+        editor.options.apply("nofixeol").unwrap();
+        run(&mut editor, &format!("w {}", path.display()));
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "a");
+    }
+
+    #[test]
+    fn opening_a_file_over_maxfilesize_is_refused() {
+        let path = temp_dir("mfs-open").join("big.txt");
+        std::fs::write(&path, vec![b'x'; 2 * 1024 * 1024]).unwrap();
+        let path = path.to_str().unwrap();
+        let mut editor = editor("");
+        editor.options.apply("maxfilesize=1").unwrap();
+        let e = editor.open_file_into_new_buffer(path).err().unwrap();
+        assert_eq!(e.to_string(), "file is too large (2 MB, maxfilesize is 1 MB)");
+        assert_eq!(editor.buffers.len(), 1);
+        editor.options.apply("maxfilesize=0").unwrap();
+        editor.open_file_into_new_buffer(path).unwrap();
+        assert_eq!(editor.buffers.len(), 2);
+    }
+
+    #[test]
+    fn bracket_s_moves_between_misspelled_words() {
+        let words = temp_dir("spell").join("words");
+        std::fs::write(&words, "the\nfox\njumps\n").unwrap();
+        let mut editor = editor("the quikc fox\njumsp");
+        editor.move_to_misspelled(true);
+        assert_eq!(editor.status_message.as_deref(), Some("Spell checking is off (:set spell)"));
+        editor.options.apply(&format!("spellfile={}", words.display())).unwrap();
+        editor.options.apply("spell").unwrap();
+        editor.move_to_misspelled(true);
+        assert_eq!((editor.cursor.line, editor.cursor.col), (0, 4));
+        editor.move_to_misspelled(true);
+        assert_eq!((editor.cursor.line, editor.cursor.col), (1, 0));
+        editor.move_to_misspelled(true);
+        assert_eq!(editor.status_message.as_deref(), Some("No more misspelled words"));
+        editor.move_to_misspelled(false);
+        assert_eq!((editor.cursor.line, editor.cursor.col), (0, 4));
+    }
+
+    #[test]
+    fn sort_command_over_a_range() {
+        let mut editor = editor("keep\nc\na\nb\na\nlast");
+        run(&mut editor, "2,5sort u");
+        assert_eq!(text(&editor), "keep\na\nb\nc\nlast");
+        run(&mut editor, "%sort!");
+        assert_eq!(text(&editor), "last\nkeep\nc\nb\na");
+        run(&mut editor, "sort q");
+        assert_eq!(editor.status_message.as_deref(), Some("Invalid argument: q"));
+    }
+
+    #[test]
+    fn numeric_sort_of_the_whole_buffer() {
+        let mut editor = editor("10\n-5\n2\n");
+        run(&mut editor, "sort n");
+        assert_eq!(text(&editor), "-5\n2\n10\n");
+    }
+
+    #[test]
+    fn smart_home_toggles_between_indent_and_column_zero() {
+        let mut editor = editor("    code here");
+        editor.cursor.col = 9;
+        editor.smart_home();
+        assert_eq!(editor.cursor.col, 4);
+        editor.smart_home();
+        assert_eq!(editor.cursor.col, 0);
+        editor.smart_home();
+        assert_eq!(editor.cursor.col, 4);
+        // From inside the indentation
+        editor.cursor.col = 2;
+        editor.smart_home();
+        assert_eq!(editor.cursor.col, 0);
+    }
+
+    #[test]
+    fn smart_home_on_unindented_and_blank_lines_stays_at_column_zero() {
+        let mut editor = editor("abc\n    ");
+        editor.cursor.col = 2;
+        editor.smart_home();
+        assert_eq!(editor.cursor.col, 0);
+        editor.smart_home();
+        assert_eq!(editor.cursor.col, 0);
+        editor.cursor = Cursor { line: 1, col: 3 };
+        editor.smart_home();
+        assert_eq!(editor.cursor.col, 0);
+    }
+
+    #[test]
+    fn modelines_apply_when_a_file_is_opened() {
+        let dir = temp_dir("modeline");
+        let path = dir.join("script");
+        std::fs::write(&path, "echo hi\n# vim: set ts=2 et ft=sh:\n").unwrap();
+        let mut editor = editor("");
+        editor.open_file_into_new_buffer(path.to_str().unwrap()).unwrap();
+        assert_eq!(editor.options.tabstop, 2);
+        assert!(editor.options.expandtab);
+        assert_eq!(editor.current_buffer().filetype.as_deref(), Some("sh"));
+
+        let other = dir.join("other.txt");
+        std::fs::write(&other, "# vim: ts=3\n").unwrap();
+        editor.options.apply("nomodeline").unwrap();
+        editor.open_file_into_new_buffer(other.to_str().unwrap()).unwrap();
+        assert_eq!(editor.options.tabstop, 2);
+    }
+
+    #[test]
+    fn cd_target_forms() {
+        let home = Some(Path::new("/home/me"));
+        let file = Some(Path::new("/src/app/main.rs"));
+        let prev = Some(Path::new("/old"));
+        let target = |arg| cd_target(arg, file, home, prev);
+        assert_eq!(target(""), Ok(PathBuf::from("/home/me")));
+        assert_eq!(target("~"), Ok(PathBuf::from("/home/me")));
+        assert_eq!(target("~/code"), Ok(PathBuf::from("/home/me/code")));
+        assert_eq!(target("-"), Ok(PathBuf::from("/old")));
+        assert_eq!(target("%"), Ok(PathBuf::from("/src/app")));
+        assert_eq!(target("../x"), Ok(PathBuf::from("../x")));
+    }
+
+    #[test]
+    fn cd_target_errors_and_bare_file_names() {
+        assert_eq!(cd_target("-", None, None, None), Err("No previous directory".to_string()));
+        assert_eq!(cd_target("~", None, None, None), Err("HOME is not set".to_string()));
+        assert_eq!(
+            cd_target("%", None, None, None),
+            Err("No file name to substitute for \"%\"".to_string())
+        );
+        // A file in the working directory: `%` stays here
+        assert_eq!(cd_target("%", Some(Path::new("f.rs")), None, None), Ok(PathBuf::from(".")));
+    }
+
+    #[test]
+    fn cd_to_a_missing_directory_reports_and_stays() {
+        let before = std::env::current_dir().unwrap();
+        let missing = temp_dir("cd-missing").join("nope");
+        let mut editor = editor("");
+        assert!(run(&mut editor, &format!("cd {}", missing.display())).is_none());
+        let status = editor.status_message.clone().unwrap_or_default();
+        assert!(status.starts_with("Cannot change directory to"), "{}", status);
+        assert_eq!(std::env::current_dir().unwrap(), before);
+        run(&mut editor, "cd -");
+        assert_eq!(editor.status_message.as_deref(), Some("No previous directory"));
+    }
+
+    #[test]
+    fn yank_lines_fills_the_register_line_wise() {
+        let mut editor = editor("a\nb\nc\n");
+        editor.cursor.line = 1;
+        run(&mut editor, "%y");
+        assert_eq!(editor.register, "a\nb\nc\n");
+        assert_eq!(editor.status_message.as_deref(), Some("3 lines yanked"));
+        assert_eq!(text(&editor), "a\nb\nc\n");
+        assert_eq!(editor.cursor.line, 1);
+        assert!(!editor.current_buffer().modified);
+        run(&mut editor, "y");
+        assert_eq!(editor.register, "b\n");
+        run(&mut editor, "1,2yank");
+        assert_eq!(editor.register, "a\nb\n");
+    }
+
+    #[test]
+    fn yank_adds_the_line_break_a_last_line_lacks() {
+        let mut editor = editor("a\nb");
+        run(&mut editor, "%y");
+        assert_eq!(editor.register, "a\nb\n");
+    }
+
+    #[test]
+    fn delete_all_lines_of_a_non_empty_buffer() {
+        let mut editor = editor(&numbered_lines(5));
+        editor.cursor.line = 3;
+        run(&mut editor, "%d");
+        assert_eq!(text(&editor), "");
+        assert_eq!(editor.register, numbered_lines(5));
+        assert_eq!(editor.status_message.as_deref(), Some("5 fewer lines"));
+        assert_eq!((editor.cursor.line, editor.cursor.col), (0, 0));
+        editor.undo();
+        assert_eq!(text(&editor), numbered_lines(5));
+    }
+
+    #[test]
+    fn ranges_past_the_end_are_clamped() {
+        let mut deleting = editor("a\nb\nc\n");
+        run(&mut deleting, "2,500d");
+        assert_eq!(text(&deleting), "a\n");
+        assert_eq!(deleting.register, "b\nc\n");
+        let mut yanking = editor("a\nb\nc\n");
+        run(&mut yanking, "50,60y");
+        assert_eq!(yanking.register, "c\n");
+    }
+
+    #[test]
+    fn delete_into_the_blackhole_register() {
+        let mut editor = editor("a\nb\nc\n");
+        run(&mut editor, "1y");
+        run(&mut editor, "2,3d _");
+        assert_eq!(text(&editor), "a\n");
+        assert_eq!(editor.register, "a\n");
+    }
+
+    #[test]
+    fn registers_shows_and_clears_the_unnamed_register() {
+        let mut editor = editor("a\nb\n");
+        run(&mut editor, "%y");
+        run(&mut editor, "reg");
+        assert_eq!(editor.status_message.as_deref(), Some("\"\" a^Jb^J"));
+        run(&mut editor, "registers clear");
+        assert_eq!(editor.register, "");
+        run(&mut editor, "put");
+        assert_eq!(editor.status_message.as_deref(), Some("Nothing in register \""));
+        run(&mut editor, "reg x");
+        assert_eq!(editor.status_message.as_deref(), Some("Invalid argument: x"));
+    }
+
+    #[test]
+    fn put_adds_the_register_below_the_line() {
+        let mut editor = editor("a\nb\nc\n");
+        run(&mut editor, "1y");
+        editor.cursor.line = 1;
+        run(&mut editor, "put");
+        assert_eq!(text(&editor), "a\nb\na\nc\n");
+        assert_eq!(editor.cursor.line, 2);
+        editor.finish_undo_step();
+        run(&mut editor, "$put \"");
+        assert_eq!(text(&editor), "a\nb\na\nc\na\n");
+        assert_eq!(editor.cursor.line, 4);
+        editor.undo();
+        assert_eq!(text(&editor), "a\nb\na\nc\n");
+    }
+
+    #[test]
+    fn put_of_text_from_within_a_line_is_line_wise() {
+        let mut editor = editor("  indented\nlast");
+        editor.register = "one\n  two".to_string();
+        run(&mut editor, "2pu");
+        assert_eq!(text(&editor), "  indented\nlast\none\n  two");
+        assert_eq!((editor.cursor.line, editor.cursor.col), (3, 2));
+    }
+
+    #[test]
+    fn zero_put_adds_the_register_above_the_first_line() {
+        let mut editor = editor("a\nb\n");
+        editor.cursor.line = 1;
+        editor.register = "x\ny\nz\n".to_string();
+        run(&mut editor, "0put");
+        assert_eq!(text(&editor), "x\ny\nz\na\nb\n");
+        assert_eq!(editor.cursor.line, 2);
+        assert_eq!(editor.status_message.as_deref(), Some("3 more lines"));
+    }
+
+    #[test]
+    fn put_from_an_empty_or_unknown_register() {
+        let mut editor = editor("a\n");
+        run(&mut editor, "put");
+        assert_eq!(editor.status_message.as_deref(), Some("Nothing in register \""));
+        editor.register = "x\n".to_string();
+        // There are no named registers yet
+        run(&mut editor, "put a");
+        assert_eq!(editor.status_message.as_deref(), Some("Invalid register name: a"));
+        assert_eq!(text(&editor), "a\n");
+        assert!(!editor.current_buffer().modified);
+    }
+
+    #[test]
+    fn whole_word_matches_skip_longer_words() {
+        let chars: Vec<char> = "foo food foo_bar (foo) foo".chars().collect();
+        let word: Vec<char> = "foo".chars().collect();
+        assert_eq!(whole_word_matches(&chars, &word), vec![0..3, 18..21, 23..26]);
+        assert!(whole_word_matches(&chars, &[]).is_empty());
+        assert!(whole_word_matches(&['a'], &word).is_empty());
+    }
+
+    #[test]
+    fn word_under_cursor_spans_the_whole_keyword() {
+        let mut editor = editor("let snake_case = 1;");
+        editor.cursor.col = 8;
+        assert_eq!(editor.word_under_cursor(), Some("snake_case".chars().collect()));
+        editor.cursor.col = 3;
+        assert_eq!(editor.word_under_cursor(), None);
+        editor.cursor.col = 0;
+        assert_eq!(editor.word_under_cursor(), Some("let".chars().collect()));
+    }
+
+    /// Two top-level blocks; the first has a nested block at lines 1..=2
+    const NESTED: &str = "a\n  b\n    c\n  d\ne\n  f\n";
+
+    #[test]
+    fn zm_and_zr_close_and_open_one_level_at_a_time() {
+        let mut editor = editor(NESTED);
+        editor.cursor.line = 2;
+        editor.fold_more();
+        // Only the innermost fold closes; the cursor moves to its header
+        assert_eq!(editor.folds.closed_at(2).map(|f| (f.start, f.end)), Some((1, 2)));
+        assert_eq!(editor.folds.closed_at(0), None);
+        assert_eq!(editor.cursor.line, 1);
+        editor.fold_more();
+        assert_eq!(editor.folds.closed_at(2).map(|f| (f.start, f.end)), Some((0, 3)));
+        assert!(editor.folds.closed_at(5).is_some());
+        editor.reduce_folding();
+        assert_eq!(editor.folds.closed_at(2).map(|f| (f.start, f.end)), Some((1, 2)));
+        assert_eq!(editor.folds.closed_at(5), None);
+    }
+
+    #[test]
+    fn zm_close_all_and_zr_open_all() {
+        let mut editor = editor(NESTED);
+        editor.cursor.line = 3;
+        editor.close_all_folds();
+        assert_eq!(editor.folds.closed_at(3).map(|f| (f.start, f.end)), Some((0, 3)));
+        assert!(editor.folds.closed_at(5).is_some());
+        assert_eq!(editor.cursor.line, 0);
+        editor.open_all_folds();
+        assert!(editor.folds.is_empty());
+        // Folding more after zR starts again from the innermost level
+        editor.fold_more();
+        assert_eq!(editor.folds.closed_at(2).map(|f| (f.start, f.end)), Some((1, 2)));
+    }
+
+    #[test]
+    fn fold_levels_without_folds_report() {
+        let mut editor = editor("a\nb\n");
+        editor.close_all_folds();
+        assert_eq!(editor.status_message.as_deref(), Some("No fold found"));
+    }
+
+    #[test]
+    fn search_count_format() {
+        assert_eq!(format_search_count(Some(3), 12, 999), "[3/12]");
+        assert_eq!(format_search_count(Some(3), 1000, 999), "[3/>999]");
+        assert_eq!(format_search_count(None, 1000, 999), "[>999/>999]");
+    }
+
+    #[test]
+    fn searchcount_shows_the_match_index_after_n_and_shift_n() {
+        let mut editor = editor("foo\nbar foo\nfoo\n");
+        editor.options.apply("searchcount").unwrap();
+        editor.command_buffer = "foo".to_string();
+        editor.search_forward();
+        assert_eq!(editor.status_message.as_deref(), Some("/foo [2/3]"));
+        editor.repeat_search_forward();
+        assert_eq!(editor.status_message.as_deref(), Some("/foo [3/3]"));
+        editor.repeat_search_backward();
+        assert_eq!(editor.status_message.as_deref(), Some("?foo [2/3]"));
+        editor.options.apply("nosearchcount").unwrap();
+        editor.clear_status();
+        editor.repeat_search_forward();
+        assert_eq!(editor.status_message, None);
+    }
+
+    #[test]
+    fn open_files_opens_in_order_and_reports() {
+        let dir = temp_dir("open-files");
+        let (a, b) = (dir.join("a.txt"), dir.join("b.txt"));
+        std::fs::write(&a, "a\n").unwrap();
+        std::fs::write(&b, "b\n").unwrap();
+        let mut editor = editor("");
+        editor.open_files(&[a.clone(), dir.clone(), b.clone()]);
+        assert_eq!(editor.buffers.len(), 3);
+        assert_eq!(editor.current_buffer().file_path.as_deref(), Some(b.as_path()));
+        assert_eq!(editor.status_message.as_deref(), Some("Opened 2 files"));
+        // Already open: switches to it without a new buffer
+        editor.open_files(std::slice::from_ref(&a));
+        assert_eq!(editor.buffers.len(), 3);
+        assert_eq!(editor.status_message.as_deref(), Some("Opened 1 file"));
+    }
+
+    #[test]
+    fn open_files_reports_the_last_failure() {
+        let dir = temp_dir("open-files-missing");
+        let (good, missing) = (dir.join("good.txt"), dir.join("missing.txt"));
+        std::fs::write(&good, "x\n").unwrap();
+        let mut editor = editor("");
+        editor.open_files(&[missing.clone(), good]);
+        let status = editor.status_message.clone().unwrap_or_default();
+        let expected = format!("Opened 1 file; {}: ", missing.display());
+        assert!(status.starts_with(&expected), "{}", status);
+    }
+
+    #[test]
+    fn edit_reloads_a_changed_file_and_u_brings_back_the_old_text() {
+        let path = temp_dir("reload").join("f.txt");
+        std::fs::write(&path, "old\n").unwrap();
+        let mut editor = Editor::with_file(path.to_str().unwrap(), None).unwrap();
+        std::fs::write(&path, "new\ntext\n").unwrap();
+        run(&mut editor, "e");
+        assert_eq!(text(&editor), "new\ntext\n");
+        assert_eq!(editor.status_message.as_deref(), Some("\"f.txt\" reloaded"));
+        assert!(!editor.current_buffer().modified);
+        editor.undo();
+        assert_eq!(text(&editor), "old\n");
+        assert!(editor.current_buffer().modified);
+    }
+
+    #[test]
+    fn edit_keeps_unsaved_changes_unless_forced() {
+        let path = temp_dir("reload-force").join("f.txt");
+        std::fs::write(&path, "disk\n").unwrap();
+        let mut editor = Editor::with_file(path.to_str().unwrap(), None).unwrap();
+        editor.current_buffer_mut().insert_str(0, 0, "mine ");
+        run(&mut editor, "e");
+        assert_eq!(
+            editor.status_message.as_deref(),
+            Some("No write since last change (add ! to override)")
+        );
+        assert_eq!(text(&editor), "mine disk\n");
+        run(&mut editor, "e!");
+        assert_eq!(text(&editor), "disk\n");
+        editor.undo();
+        assert_eq!(text(&editor), "mine disk\n");
+    }
+
+    #[test]
+    fn noundoreload_starts_the_history_over() {
+        let path = temp_dir("reload-noundo").join("f.txt");
+        std::fs::write(&path, "disk\n").unwrap();
+        let mut editor = Editor::with_file(path.to_str().unwrap(), None).unwrap();
+        editor.options.apply("noundoreload").unwrap();
+        editor.current_buffer_mut().insert_str(0, 0, "mine ");
+        run(&mut editor, "e!");
+        assert_eq!(text(&editor), "disk\n");
+        editor.undo();
+        assert_eq!(editor.status_message.as_deref(), Some("Already at oldest change"));
+    }
+
+    #[test]
+    fn edit_without_a_file_name_reports() {
+        let mut editor = editor("x");
+        run(&mut editor, "e");
+        assert_eq!(editor.status_message.as_deref(), Some("No file name"));
+    }
+
+    #[test]
+    fn page_down_and_up_move_the_viewport_by_a_window_less_two_lines() {
+        let mut editor = editor(&numbered_lines(100));
+        editor.options.scrolloff = 0;
+        editor.adjust_viewport_with_height(10);
+        editor.page_down();
+        editor.adjust_viewport_with_height(10);
+        assert_eq!((editor.viewport_offset, editor.cursor.line), (8, 8));
+        editor.page_down();
+        editor.page_down();
+        editor.adjust_viewport_with_height(10);
+        assert_eq!((editor.viewport_offset, editor.cursor.line), (24, 24));
+        editor.page_up();
+        editor.adjust_viewport_with_height(10);
+        // The cursor is still on screen, so it stays
+        assert_eq!((editor.viewport_offset, editor.cursor.line), (16, 24));
+        editor.page_up();
+        editor.adjust_viewport_with_height(10);
+        assert_eq!((editor.viewport_offset, editor.cursor.line), (8, 17));
+    }
+
+    #[test]
+    fn page_down_stops_at_the_last_line() {
+        let mut editor = editor(&numbered_lines(12));
+        editor.options.scrolloff = 0;
+        for _ in 0..5 {
+            editor.page_down();
+        }
+        editor.adjust_viewport_with_height(10);
+        assert_eq!(editor.viewport_offset, 12);
+        assert_eq!(editor.cursor.line, 12);
+    }
 }
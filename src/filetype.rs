@@ -0,0 +1,201 @@
+//! Filetype detection: maps file names, extensions and shebang lines to a canonical
+//! filetype name (e.g. "rust", "python", "sh") used by the status line and other features.
+
+use std::path::Path;
+
+/// Filetypes for files known by their full name (no or unhelpful extension)
+const FILENAMES: &[(&str, &str)] = &[
+    ("Makefile", "make"),
+    ("makefile", "make"),
+    ("GNUmakefile", "make"),
+    ("Dockerfile", "dockerfile"),
+    ("Containerfile", "dockerfile"),
+    ("CMakeLists.txt", "cmake"),
+    ("Cargo.lock", "toml"),
+    (".bashrc", "sh"),
+    (".bash_profile", "sh"),
+    (".profile", "sh"),
+    (".zshrc", "zsh"),
+    (".gitignore", "gitignore"),
+    (".vimrc", "vim"),
+];
+
+/// Filetypes by file extension
+const EXTENSIONS: &[(&str, &str)] = &[
+    ("rs", "rust"),
+    ("py", "python"),
+    ("pyw", "python"),
+    ("c", "c"),
+    ("h", "c"),
+    ("cc", "cpp"),
+    ("cpp", "cpp"),
+    ("cxx", "cpp"),
+    ("hpp", "cpp"),
+    ("go", "go"),
+    ("java", "java"),
+    ("js", "javascript"),
+    ("mjs", "javascript"),
+    ("cjs", "javascript"),
+    ("jsx", "javascriptreact"),
+    ("ts", "typescript"),
+    ("tsx", "typescriptreact"),
+    ("rb", "ruby"),
+    ("lua", "lua"),
+    ("sh", "sh"),
+    ("bash", "sh"),
+    ("zsh", "zsh"),
+    ("fish", "fish"),
+    ("toml", "toml"),
+    ("yaml", "yaml"),
+    ("yml", "yaml"),
+    ("json", "json"),
+    ("md", "markdown"),
+    ("markdown", "markdown"),
+    ("html", "html"),
+    ("htm", "html"),
+    ("css", "css"),
+    ("scss", "scss"),
+    ("sql", "sql"),
+    ("vim", "vim"),
+    ("hs", "haskell"),
+    ("ex", "elixir"),
+    ("exs", "elixir"),
+    ("swift", "swift"),
+    ("kt", "kotlin"),
+    ("cs", "cs"),
+    ("php", "php"),
+    ("pl", "perl"),
+    ("mk", "make"),
+    ("txt", "text"),
+];
+
+/// Interpreters recognized in a `#!` line
+const INTERPRETERS: &[(&str, &str)] = &[
+    ("sh", "sh"),
+    ("bash", "sh"),
+    ("dash", "sh"),
+    ("zsh", "zsh"),
+    ("fish", "fish"),
+    ("python", "python"),
+    ("python2", "python"),
+    ("python3", "python"),
+    ("node", "javascript"),
+    ("ruby", "ruby"),
+    ("perl", "perl"),
+    ("lua", "lua"),
+    ("php", "php"),
+];
+
+/// Detect the filetype from the file name, then the extension, then the first line's shebang.
+pub fn detect(path: Option<&Path>, first_line: Option<&str>) -> Option<String> {
+    if let Some(path) = path {
+        if let Some(ft) = from_path(path) {
+            return Some(ft.to_string());
+        }
+    }
+    first_line.and_then(from_shebang).map(|ft| ft.to_string())
+}
+
+/// Filetype from a known file name or extension
+fn from_path(path: &Path) -> Option<&'static str> {
+    let name = path.file_name()?.to_str()?;
+    if let Some((_, ft)) = FILENAMES.iter().find(|(n, _)| *n == name) {
+        return Some(ft);
+    }
+    if name.starts_with("Dockerfile.") {
+        return Some("dockerfile");
+    }
+    let ext = path.extension()?.to_str()?;
+    EXTENSIONS
+        .iter()
+        .find(|(e, _)| e.eq_ignore_ascii_case(ext))
+        .map(|(_, ft)| *ft)
+}
+
+/// Filetype from a `#!/path/to/interp` or `#!/usr/bin/env [-S] interp` line
+fn from_shebang(line: &str) -> Option<&'static str> {
+    let rest = line.strip_prefix("#!")?.trim();
+    let mut words = rest.split_whitespace();
+    let mut program = words.next()?.rsplit('/').next()?;
+    if program == "env" {
+        program = words.find(|w| !w.starts_with('-'))?;
+    }
+    // python3.11 -> python3, python -> python
+    let base = program.trim_end_matches(|c: char| c.is_ascii_digit() || c == '.');
+    INTERPRETERS
+        .iter()
+        .find(|(i, _)| *i == program || *i == base)
+        .map(|(_, ft)| *ft)
+}
+
+/// Line comment markers for a filetype as (prefix, suffix); suffix is empty for line comments.
+pub fn comment_string(filetype: &str) -> Option<(&'static str, &'static str)> {
+    let cs = match filetype {
+        "rust" | "c" | "cpp" | "go" | "java" | "javascript" | "javascriptreact" | "typescript"
+        | "typescriptreact" | "swift" | "kotlin" | "cs" | "php" | "scss" => ("//", ""),
+        "python" | "sh" | "zsh" | "fish" | "ruby" | "perl" | "toml" | "yaml" | "make"
+        | "dockerfile" | "cmake" | "elixir" | "gitignore" => ("#", ""),
+        "lua" | "haskell" | "sql" => ("--", ""),
+        "vim" => ("\"", ""),
+        "html" | "markdown" => ("<!--", "-->"),
+        "css" => ("/*", "*/"),
+        _ => return None,
+    };
+    Some(cs)
+}
+
+/// Keyword pairs the `%` motion jumps between, as (openers, closer)
+pub fn keyword_pairs(filetype: &str) -> &'static [(&'static [&'static str], &'static str)] {
+    match filetype {
+        "sh" | "zsh" => &[(&["if"], "fi"), (&["case"], "esac"), (&["do"], "done")],
+        "vim" => &[
+            (&["if"], "endif"),
+            (&["for"], "endfor"),
+            (&["while"], "endwhile"),
+            (&["function"], "endfunction"),
+            (&["try"], "endtry"),
+        ],
+        "make" => &[(&["ifeq", "ifneq", "ifdef", "ifndef"], "endif"), (&["define"], "endef")],
+        _ => &[],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn detect_path(path: &str) -> Option<String> {
+        detect(Some(Path::new(path)), None)
+    }
+
+    #[test]
+    fn by_extension() {
+        assert_eq!(detect_path("src/main.rs").as_deref(), Some("rust"));
+        assert_eq!(detect_path("script.PY").as_deref(), Some("python"));
+        assert_eq!(detect_path("notes.unknown"), None);
+    }
+
+    #[test]
+    fn by_file_name() {
+        assert_eq!(detect_path("Makefile").as_deref(), Some("make"));
+        assert_eq!(detect_path("/repo/Dockerfile").as_deref(), Some("dockerfile"));
+        assert_eq!(detect_path("Dockerfile.dev").as_deref(), Some("dockerfile"));
+        assert_eq!(detect_path("CMakeLists.txt").as_deref(), Some("cmake"));
+    }
+
+    #[test]
+    fn by_shebang() {
+        let detect_line = |line| detect(Some(Path::new("run")), Some(line));
+        assert_eq!(detect_line("#!/bin/bash").as_deref(), Some("sh"));
+        assert_eq!(detect_line("#!/usr/bin/env python3.11").as_deref(), Some("python"));
+        assert_eq!(detect_line("#!/usr/bin/env -S node --flag").as_deref(), Some("javascript"));
+        assert_eq!(detect_line("#!/usr/bin/unknown"), None);
+        assert_eq!(detect_line("echo hi"), None);
+    }
+
+    #[test]
+    fn path_wins_over_shebang() {
+        let ft = detect(Some(Path::new("tool.rb")), Some("#!/bin/sh"));
+        assert_eq!(ft.as_deref(), Some("ruby"));
+    }
+}
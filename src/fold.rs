@@ -0,0 +1,253 @@
+//! Indentation-based folding: fold ranges are computed from leading whitespace,
+//! and closed folds are tracked per editor so rendering and j/k can skip hidden lines.
+
+use crate::buffer::Buffer;
+
+/// A fold over lines `start..=end`. `start` is the header line that stays visible
+/// (rendered as a summary when closed); lines `start + 1..=end` are hidden.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Fold {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Fold {
+    /// True if `line` is the header or one of the hidden lines
+    pub fn contains(&self, line: usize) -> bool {
+        line >= self.start && line <= self.end
+    }
+
+    /// Number of lines hidden behind the header
+    pub fn hidden_len(&self) -> usize {
+        self.end - self.start
+    }
+}
+
+/// Width of a line's leading whitespace, or None for blank lines.
+pub fn indent_level(buffer: &Buffer, line_idx: usize) -> Option<usize> {
+    let line = buffer.line(line_idx)?;
+    let mut width = 0;
+    for c in line.chars() {
+        match c {
+            ' ' | '\t' => width += 1,
+            '\n' | '\r' => return None,
+            _ => return Some(width),
+        }
+    }
+    None
+}
+
+/// The fold headed by `line_idx`: the block of more-indented lines directly beneath it.
+/// Blank lines inside the block belong to it; trailing blank lines do not.
+pub fn fold_range_at(buffer: &Buffer, line_idx: usize) -> Option<Fold> {
+    let header_indent = indent_level(buffer, line_idx)?;
+    let mut end = line_idx;
+    for l in (line_idx + 1)..buffer.line_count() {
+        match indent_level(buffer, l) {
+            Some(indent) if indent > header_indent => end = l,
+            Some(_) => break,
+            None => {}
+        }
+    }
+    if end > line_idx {
+        Some(Fold { start: line_idx, end })
+    } else {
+        None
+    }
+}
+
+/// The fold that `line_idx` belongs to: the one it heads, else the one headed by the
+/// nearest less-indented line above it.
+pub fn enclosing_fold_range(buffer: &Buffer, line_idx: usize) -> Option<Fold> {
+    if let Some(fold) = fold_range_at(buffer, line_idx) {
+        return Some(fold);
+    }
+    let mut min_indent = indent_level(buffer, line_idx).unwrap_or(usize::MAX);
+    for l in (0..line_idx).rev() {
+        let Some(indent) = indent_level(buffer, l) else {
+            continue;
+        };
+        if indent < min_indent {
+            if let Some(fold) = fold_range_at(buffer, l).filter(|f| f.contains(line_idx)) {
+                return Some(fold);
+            }
+            if indent == 0 {
+                return None;
+            }
+            min_indent = indent;
+        }
+    }
+    None
+}
+
+/// Every fold in the buffer with its nesting depth (0 for outermost folds), in order of
+/// their header lines. Each fold matches `fold_range_at` for its header.
+pub fn all_folds(buffer: &Buffer) -> Vec<(Fold, usize)> {
+    let mut folds = Vec::new();
+    // Lines that may head a fold: (line, indent), each more indented than the one below it
+    let mut open: Vec<(usize, usize)> = Vec::new();
+    let mut last_text_line = 0;
+    let mut finish = |open: &mut Vec<(usize, usize)>, end: usize, indent: Option<usize>| {
+        while let Some(&(start, header_indent)) = open.last() {
+            if indent.is_some_and(|i| i > header_indent) {
+                break;
+            }
+            open.pop();
+            if end > start {
+                folds.push((Fold { start, end }, open.len()));
+            }
+        }
+    };
+    for l in 0..buffer.line_count() {
+        let Some(indent) = indent_level(buffer, l) else {
+            continue;
+        };
+        finish(&mut open, last_text_line, Some(indent));
+        open.push((l, indent));
+        last_text_line = l;
+    }
+    finish(&mut open, last_text_line, None);
+    folds.sort_by_key(|(f, _)| f.start);
+    folds
+}
+
+/// Closed folds for the current buffer. Folds are dropped when the buffer's line count
+/// changes, since their line ranges would no longer line up with the text.
+#[derive(Debug, Default)]
+pub struct FoldState {
+    closed: Vec<Fold>,
+    line_count: usize,
+    /// Fold level set by zm/zr/zM (folds nested this deep or deeper are closed), or None
+    /// while it hasn't been set since all folds were last opened
+    level: Option<usize>,
+}
+
+impl FoldState {
+    /// Drop all folds if the buffer gained or lost lines since they were closed
+    pub fn sync(&mut self, line_count: usize) {
+        if self.line_count != line_count {
+            self.closed.clear();
+            self.line_count = line_count;
+        }
+    }
+
+    /// Open every fold
+    pub fn clear(&mut self) {
+        self.closed.clear();
+        self.level = None;
+    }
+
+    pub fn level(&self) -> Option<usize> {
+        self.level
+    }
+
+    /// Close exactly the folds in `folds` nested `level` or more deep, opening the rest
+    pub fn set_level(&mut self, folds: &[(Fold, usize)], level: usize, line_count: usize) {
+        self.closed = folds
+            .iter()
+            .filter(|(_, depth)| *depth >= level)
+            .map(|(f, _)| *f)
+            .collect();
+        self.line_count = line_count;
+        self.level = Some(level);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.closed.is_empty()
+    }
+
+    /// The outermost closed fold containing `line`, if any
+    pub fn closed_at(&self, line: usize) -> Option<Fold> {
+        self.closed
+            .iter()
+            .filter(|f| f.contains(line))
+            .max_by_key(|f| f.hidden_len())
+            .copied()
+    }
+
+    /// Close a fold (no-op if already closed)
+    pub fn close(&mut self, fold: Fold, line_count: usize) {
+        self.sync(line_count);
+        if !self.closed.contains(&fold) {
+            self.closed.push(fold);
+        }
+    }
+
+    /// Open the outermost closed fold containing `line`. Returns false if there was none.
+    pub fn open_at(&mut self, line: usize) -> bool {
+        match self.closed_at(line) {
+            Some(fold) => {
+                self.closed.retain(|f| *f != fold);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::buffer;
+
+    const NESTED: &str = "fn main() {\n    if x {\n        a();\n\n        b();\n    }\n\n    c();\n}\n";
+
+    #[test]
+    fn fold_range_covers_more_indented_lines() {
+        let buffer = buffer(NESTED);
+        assert_eq!(fold_range_at(&buffer, 0), Some(Fold { start: 0, end: 7 }));
+        // The blank line inside the block belongs to it
+        assert_eq!(fold_range_at(&buffer, 1), Some(Fold { start: 1, end: 4 }));
+    }
+
+    #[test]
+    fn no_fold_without_deeper_lines() {
+        let buffer = buffer(NESTED);
+        assert_eq!(fold_range_at(&buffer, 2), None);
+        assert_eq!(fold_range_at(&buffer, 7), None);
+        // Blank lines never head a fold
+        assert_eq!(fold_range_at(&buffer, 3), None);
+        assert_eq!(fold_range_at(&buffer, 100), None);
+    }
+
+    #[test]
+    fn trailing_blank_lines_are_not_folded() {
+        let buffer = buffer("a:\n  b\n\n\nc\n");
+        assert_eq!(fold_range_at(&buffer, 0), Some(Fold { start: 0, end: 1 }));
+    }
+
+    #[test]
+    fn enclosing_fold_of_a_body_line() {
+        let buffer = buffer(NESTED);
+        assert_eq!(enclosing_fold_range(&buffer, 2), Some(Fold { start: 1, end: 4 }));
+        assert_eq!(enclosing_fold_range(&buffer, 3), Some(Fold { start: 1, end: 4 }));
+        assert_eq!(enclosing_fold_range(&buffer, 7), Some(Fold { start: 0, end: 7 }));
+        // A header is enclosed by the fold it heads
+        assert_eq!(enclosing_fold_range(&buffer, 1), Some(Fold { start: 1, end: 4 }));
+    }
+
+    #[test]
+    fn top_level_line_outside_folds_has_no_enclosing_fold() {
+        let buffer = buffer("a\nb\n  c\n");
+        assert_eq!(enclosing_fold_range(&buffer, 0), None);
+    }
+
+    #[test]
+    fn all_folds_with_depths() {
+        let buffer = buffer(NESTED);
+        assert_eq!(
+            all_folds(&buffer),
+            vec![(Fold { start: 0, end: 7 }, 0), (Fold { start: 1, end: 4 }, 1)]
+        );
+    }
+
+    #[test]
+    fn all_folds_match_fold_range_at() {
+        let buffer = buffer("a\n  b\n    c\n  d\n    e\n\nf\n  g\n");
+        let folds = all_folds(&buffer);
+        assert_eq!(folds.len(), 4);
+        for (fold, _) in folds {
+            assert_eq!(fold_range_at(&buffer, fold.start), Some(fold));
+        }
+    }
+}
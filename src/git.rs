@@ -0,0 +1,30 @@
+//! Minimal git integration: reads the current branch from `.git/HEAD` without shelling out.
+
+use std::path::{Path, PathBuf};
+
+/// Find the git directory for `start` by walking up parent directories.
+/// Handles worktrees/submodules where `.git` is a file containing `gitdir: <path>`.
+fn find_git_dir(start: &Path) -> Option<PathBuf> {
+    for dir in start.ancestors() {
+        let candidate = dir.join(".git");
+        if candidate.is_dir() {
+            return Some(candidate);
+        }
+        if candidate.is_file() {
+            let contents = std::fs::read_to_string(&candidate).ok()?;
+            let target = contents.trim().strip_prefix("gitdir:")?.trim();
+            return Some(dir.join(target));
+        }
+    }
+    None
+}
+
+/// Name of the branch checked out in the repository containing `start`.
+/// Returns None outside a repository and for a detached HEAD.
+pub fn current_branch(start: &Path) -> Option<String> {
+    let git_dir = find_git_dir(start)?;
+    let head = std::fs::read_to_string(git_dir.join("HEAD")).ok()?;
+    head.trim()
+        .strip_prefix("ref: refs/heads/")
+        .map(|name| name.to_string())
+}
@@ -0,0 +1,192 @@
+//! Undo history for a buffer: every text change is kept as a reversible edit, and the
+//! edits made by one command (or one whole insert-mode session) form a single undo step.
+
+/// Undo steps kept; older ones are dropped
+const MAX_STEPS: usize = 1000;
+
+/// One text change: `removed` was replaced by `inserted` at char index `at`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Edit {
+    pub at: usize,
+    pub removed: String,
+    pub inserted: String,
+}
+
+impl Edit {
+    /// The edit that undoes this one
+    pub fn inverse(&self) -> Edit {
+        Edit {
+            at: self.at,
+            removed: self.inserted.clone(),
+            inserted: self.removed.clone(),
+        }
+    }
+}
+
+/// Past and undone steps of a buffer, plus the edits of the step in progress
+#[derive(Debug)]
+pub struct History {
+    undo: Vec<Vec<Edit>>,
+    redo: Vec<Vec<Edit>>,
+    pending: Vec<Edit>,
+    /// Number of undo steps when the buffer was last saved (None once that state is gone)
+    saved: Option<usize>,
+}
+
+impl Default for History {
+    /// An empty history for text as it is on disk
+    fn default() -> Self {
+        Self {
+            undo: Vec::new(),
+            redo: Vec::new(),
+            pending: Vec::new(),
+            saved: Some(0),
+        }
+    }
+}
+
+impl History {
+    /// Record a change as part of the step in progress. Typing extends the previous insert
+    /// instead of adding an edit per character.
+    pub fn record(&mut self, edit: Edit) {
+        if let Some(last) = self.pending.last_mut() {
+            if edit.removed.is_empty()
+                && last.removed.is_empty()
+                && edit.at == last.at + last.inserted.chars().count()
+            {
+                last.inserted.push_str(&edit.inserted);
+                return;
+            }
+        }
+        self.pending.push(edit);
+    }
+
+    /// Close the step in progress, if it changed anything. Starts a new line of history:
+    /// undone steps can no longer be redone.
+    pub fn commit(&mut self) {
+        if self.pending.is_empty() {
+            return;
+        }
+        self.undo.push(std::mem::take(&mut self.pending));
+        if self.saved.is_some_and(|saved| saved >= self.undo.len()) {
+            self.saved = None;
+        }
+        self.redo.clear();
+        if self.undo.len() > MAX_STEPS {
+            self.undo.remove(0);
+            self.saved = self.saved.and_then(|saved| saved.checked_sub(1));
+        }
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty() || !self.pending.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    /// Take the last step for undoing, moving it to the redo list. Its edits are returned in
+    /// the order they were made; the caller applies their inverses in reverse.
+    pub fn undo(&mut self) -> Option<Vec<Edit>> {
+        self.commit();
+        let step = self.undo.pop()?;
+        self.redo.push(step.clone());
+        Some(step)
+    }
+
+    /// Take the last undone step for redoing, moving it back to the undo list
+    pub fn redo(&mut self) -> Option<Vec<Edit>> {
+        let step = self.redo.pop()?;
+        self.undo.push(step.clone());
+        Some(step)
+    }
+
+    /// Remember the current state as the one on disk
+    pub fn mark_saved(&mut self) {
+        self.commit();
+        self.saved = Some(self.undo.len());
+    }
+
+    /// Whether the text is back to what was last saved
+    pub fn at_saved(&self) -> bool {
+        self.pending.is_empty() && self.saved == Some(self.undo.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn insert(at: usize, text: &str) -> Edit {
+        Edit {
+            at,
+            removed: String::new(),
+            inserted: text.to_string(),
+        }
+    }
+
+    #[test]
+    fn typing_merges_into_one_edit() {
+        let mut history = History::default();
+        history.record(insert(0, "a"));
+        history.record(insert(1, "b"));
+        history.record(insert(5, "c"));
+        assert_eq!(history.undo(), Some(vec![insert(0, "ab"), insert(5, "c")]));
+    }
+
+    #[test]
+    fn a_new_step_drops_the_redo_list() {
+        let mut history = History::default();
+        history.record(insert(0, "a"));
+        history.commit();
+        history.undo();
+        assert!(history.can_redo());
+        history.record(insert(0, "b"));
+        history.commit();
+        assert!(!history.can_redo());
+        assert_eq!(history.redo(), None);
+    }
+
+    #[test]
+    fn saved_state_follows_undo_and_redo() {
+        let mut history = History::default();
+        assert!(history.at_saved());
+        history.record(insert(0, "a"));
+        assert!(!history.at_saved());
+        history.mark_saved();
+        assert!(history.at_saved());
+        history.undo();
+        assert!(!history.at_saved());
+        history.redo();
+        assert!(history.at_saved());
+    }
+
+    #[test]
+    fn saved_state_is_lost_when_its_step_is_replaced() {
+        let mut history = History::default();
+        history.record(insert(0, "a"));
+        history.mark_saved();
+        history.undo();
+        history.record(insert(0, "b"));
+        history.commit();
+        history.undo();
+        // Back to the text before "a", which was never saved
+        assert!(!history.at_saved());
+        assert!(!history.can_undo());
+    }
+
+    #[test]
+    fn old_steps_are_dropped_past_the_limit() {
+        let mut history = History::default();
+        for i in 0..MAX_STEPS + 5 {
+            history.record(insert(i, "x"));
+            history.commit();
+        }
+        let mut undone = 0;
+        while history.undo().is_some() {
+            undone += 1;
+        }
+        assert_eq!(undone, MAX_STEPS);
+    }
+}
@@ -0,0 +1,68 @@
+//! Idle detection for the cursor-hold hook: fires once after `updatetime` ms without input.
+//! Time is passed in by the caller (see `App::clock`) so the logic does not read the clock itself.
+
+use std::time::{Duration, Instant};
+
+/// Tracks the time of the last input and whether the current idle period was already reported
+pub struct IdleTimer {
+    last_input: Instant,
+    fired: bool,
+}
+
+impl IdleTimer {
+    pub fn new(now: Instant) -> Self {
+        Self {
+            last_input: now,
+            fired: false,
+        }
+    }
+
+    /// Record user input, starting a new idle period
+    pub fn input(&mut self, now: Instant) {
+        self.last_input = now;
+        self.fired = false;
+    }
+
+    /// True once per idle period, as soon as `timeout` has passed since the last input
+    pub fn check(&mut self, now: Instant, timeout: Duration) -> bool {
+        if self.fired || now.saturating_duration_since(self.last_input) < timeout {
+            return false;
+        }
+        self.fired = true;
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TIMEOUT: Duration = Duration::from_millis(100);
+
+    #[test]
+    fn fires_once_per_idle_period() {
+        let start = Instant::now();
+        let mut timer = IdleTimer::new(start);
+        assert!(!timer.check(start + Duration::from_millis(99), TIMEOUT));
+        assert!(timer.check(start + TIMEOUT, TIMEOUT));
+        assert!(!timer.check(start + Duration::from_secs(10), TIMEOUT));
+    }
+
+    #[test]
+    fn input_starts_a_new_period() {
+        let start = Instant::now();
+        let mut timer = IdleTimer::new(start);
+        timer.input(start + Duration::from_millis(80));
+        assert!(!timer.check(start + Duration::from_millis(150), TIMEOUT));
+        assert!(timer.check(start + Duration::from_millis(180), TIMEOUT));
+        timer.input(start + Duration::from_millis(200));
+        assert!(timer.check(start + Duration::from_millis(300), TIMEOUT));
+    }
+
+    #[test]
+    fn a_clock_behind_the_last_input_never_fires() {
+        let start = Instant::now() + Duration::from_secs(1);
+        let mut timer = IdleTimer::new(start);
+        assert!(!timer.check(start - Duration::from_millis(500), TIMEOUT));
+    }
+}
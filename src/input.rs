@@ -3,7 +3,8 @@ use crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers};
 use ratatui_explorer::Input as ExplorerInput;
 
 use crate::app::App;
-use crate::editor::{Editor, EditorCommand, PendingNormal};
+use crate::dir::DirectoryState;
+use crate::editor::{Editor, EditorCommand, LiteralInput, PendingNormal};
 use crate::mode::Mode;
 
 /// The result of handling an input event
@@ -16,15 +17,35 @@ pub enum InputResult {
 
 /// Handle a key event; dispatches to file explorer or editor based on focus.
 pub fn handle_key_event(app: &mut App, key: KeyEvent) -> InputResult {
-    // Space then E (in normal mode): toggle sidebar visibility or open current directory
+    // F1 shows the key cheat sheet; the next key only dismisses it
+    if app.help_visible {
+        app.help_visible = false;
+        return InputResult::Continue;
+    }
+    if key.code == KeyCode::F(1) {
+        app.help_visible = true;
+        return InputResult::Continue;
+    }
+
+    // Space then E (in normal mode): toggle sidebar visibility or open current directory;
+    // Space then F: reveal the current file in the explorer
     if app.pending_space_e {
         app.pending_space_e = false;
-        if matches!(key.code, KeyCode::Char('e') | KeyCode::Char('E'))
-            && !key.modifiers.intersects(KeyModifiers::CONTROL | KeyModifiers::ALT | KeyModifiers::SUPER)
-            && app.editor.mode == Mode::Normal
-        {
-            app.toggle_sidebar_or_open_current_dir();
-            return InputResult::Continue;
+        let plain = !key
+            .modifiers
+            .intersects(KeyModifiers::CONTROL | KeyModifiers::ALT | KeyModifiers::SUPER);
+        if plain && app.editor.mode == Mode::Normal {
+            match key.code {
+                KeyCode::Char('e') | KeyCode::Char('E') => {
+                    app.toggle_sidebar_or_open_current_dir();
+                    return InputResult::Continue;
+                }
+                KeyCode::Char('f') | KeyCode::Char('F') => {
+                    app.reveal_current_file();
+                    return InputResult::Continue;
+                }
+                _ => {}
+            }
         }
     }
 
@@ -38,9 +59,7 @@ pub fn handle_key_event(app: &mut App, key: KeyEvent) -> InputResult {
     if app.pending_ctrl_w {
         app.pending_ctrl_w = false;
         if key.code == KeyCode::Char('w') && !key.modifiers.intersects(KeyModifiers::CONTROL | KeyModifiers::ALT | KeyModifiers::SUPER) {
-            if app.directory_state.is_some() {
-                app.focus_on_explorer = !app.focus_on_explorer;
-            }
+            app.toggle_explorer_focus();
             return InputResult::Continue;
         }
     }
@@ -56,6 +75,35 @@ pub fn handle_key_event(app: &mut App, key: KeyEvent) -> InputResult {
             }
             return InputResult::Continue;
         }
+        // m: mark or unmark the entry; t: open the marked files (or the selected one)
+        // without leaving the explorer
+        match key.code {
+            KeyCode::Char('m') => {
+                if let Some(ref mut dir) = app.directory_state {
+                    dir.toggle_mark();
+                }
+                return InputResult::Continue;
+            }
+            KeyCode::Char('t') => {
+                app.open_explorer_files();
+                return InputResult::Continue;
+            }
+            _ => {}
+        }
+        // h, Left, Backspace or -: parent directory; ~: home directory
+        let nav: Option<fn(&mut DirectoryState) -> std::io::Result<()>> = match key.code {
+            KeyCode::Char('h' | '-') | KeyCode::Left | KeyCode::Backspace => {
+                Some(DirectoryState::go_to_parent)
+            }
+            KeyCode::Char('~') => Some(DirectoryState::go_home),
+            _ => None,
+        };
+        if let (Some(nav), Some(dir)) = (nav, app.directory_state.as_mut()) {
+            if let Err(e) = nav(dir) {
+                app.editor.set_status(&format!("{}", e));
+            }
+            return InputResult::Continue;
+        }
         let is_enter = matches!(key.code, KeyCode::Enter | KeyCode::Char('l') | KeyCode::Right);
         if is_enter {
             if let Some(ref dir) = app.directory_state {
@@ -106,15 +154,56 @@ pub fn handle_key_event(app: &mut App, key: KeyEvent) -> InputResult {
     handle_editor(app, key)
 }
 
+/// Handle text pasted into the terminal (a bracketed paste). In the editor it is inserted
+/// at the cursor as a whole, in normal mode too (like vim); on the command line only its
+/// first line is taken. The explorer ignores pastes.
+pub fn handle_paste(app: &mut App, text: &str) {
+    app.help_visible = false;
+    app.pending_space_e = false;
+    app.pending_ctrl_w = false;
+    if app.focus_on_explorer {
+        return;
+    }
+    let editor = &mut app.editor;
+    match editor.mode {
+        Mode::Normal | Mode::Insert => {
+            editor.clear_pending_normal();
+            editor.insert_literal = None;
+            let (buf, revision) = (editor.current_buf, editor.current_buffer().revision());
+            editor.paste_text(text);
+            if editor.current_buf == buf && editor.current_buffer().revision() != revision {
+                editor.record_change();
+            }
+            editor.finish_undo_step();
+        }
+        Mode::Visual => {}
+        Mode::Command | Mode::Search => {
+            let first_line = text.lines().next().unwrap_or("");
+            editor.command_buffer.push_str(first_line);
+        }
+    }
+}
+
 /// Handle key event for the editor (when focus is on the editor pane).
 fn handle_editor(app: &mut App, key: KeyEvent) -> InputResult {
     let editor = &mut app.editor;
-    match editor.mode {
+    let was_insert = editor.mode == Mode::Insert;
+    let (buf, revision) = (editor.current_buf, editor.current_buffer().revision());
+    let result = match editor.mode {
         Mode::Normal => handle_normal_mode(editor, key),
         Mode::Insert => handle_insert_mode(editor, key),
         Mode::Command => handle_command_mode(app, key),
         Mode::Search => handle_search_mode(editor, key),
+        Mode::Visual => handle_visual_mode(editor, key),
+    };
+    if !was_insert {
+        app.editor.finish_insert_normal_command();
     }
+    app.editor.finish_undo_step();
+    if app.editor.current_buf == buf && app.editor.current_buffer().revision() != revision {
+        app.editor.record_change();
+    }
+    result
 }
 
 /// Handle key events in normal mode
@@ -124,18 +213,91 @@ fn handle_normal_mode(editor: &mut Editor, key: KeyEvent) -> InputResult {
 
     // Handle or cancel pending two-key / replace action
     match editor.pending_normal {
+        PendingNormal::SecondG if key.code == KeyCode::Char('c') => {
+            editor.pending_normal = PendingNormal::CommentLine;
+            return InputResult::Continue;
+        }
+        PendingNormal::SecondG if key.code == KeyCode::Char('S') => {
+            editor.clear_pending_normal();
+            editor.break_line_at_cursor();
+            return InputResult::Continue;
+        }
+        PendingNormal::SecondG if key.code == KeyCode::Char('a') => {
+            editor.clear_pending_normal();
+            let info = editor.char_info_at_cursor();
+            editor.set_status(&info);
+            return InputResult::Continue;
+        }
+        PendingNormal::SecondG if matches!(key.code, KeyCode::Char(';') | KeyCode::Char(',')) => {
+            editor.clear_pending_normal();
+            if key.code == KeyCode::Char(';') {
+                editor.older_change();
+            } else {
+                editor.newer_change();
+            }
+            return InputResult::Continue;
+        }
         PendingNormal::SecondG if key.code != KeyCode::Char('g') => {
             editor.clear_pending_normal();
         }
+        PendingNormal::CommentLine => {
+            editor.clear_pending_normal();
+            if key.code == KeyCode::Char('c') {
+                let line = editor.cursor.line;
+                editor.toggle_comment_lines(line, line);
+                return InputResult::Continue;
+            }
+        }
         PendingNormal::SecondD if key.code != KeyCode::Char('d') => {
             editor.clear_pending_normal();
         }
+        PendingNormal::SecondZ => {
+            editor.clear_pending_normal();
+            match key.code {
+                KeyCode::Char('a') => editor.toggle_fold(),
+                KeyCode::Char('o') => editor.open_fold(),
+                KeyCode::Char('c') => editor.close_fold(),
+                KeyCode::Char('R') => editor.open_all_folds(),
+                KeyCode::Char('M') => editor.close_all_folds(),
+                KeyCode::Char('r') => editor.reduce_folding(),
+                KeyCode::Char('m') => editor.fold_more(),
+                _ => {}
+            }
+            return InputResult::Continue;
+        }
+        PendingNormal::SecondUpperZ => {
+            editor.clear_pending_normal();
+            let command = match key.code {
+                KeyCode::Char('Z') => editor.write_if_modified_and_quit(),
+                KeyCode::Char('Q') => Some(EditorCommand::ForceQuit),
+                _ => None,
+            };
+            return match command {
+                Some(command) => apply_editor_command(editor, command),
+                None => InputResult::Continue,
+            };
+        }
+        PendingNormal::SecondRightBracket | PendingNormal::SecondLeftBracket => {
+            let forward = editor.pending_normal == PendingNormal::SecondRightBracket;
+            editor.clear_pending_normal();
+            match key.code {
+                KeyCode::Char('w') if forward => editor.move_subword_forward(),
+                KeyCode::Char('w') => editor.move_subword_backward(),
+                KeyCode::Char('s') => editor.move_to_misspelled(forward),
+                KeyCode::Char('}') if forward => editor.move_to_enclosing_brace(true),
+                KeyCode::Char('{') if !forward => editor.move_to_enclosing_brace(false),
+                _ => {}
+            }
+            return InputResult::Continue;
+        }
         PendingNormal::ReplaceChar => {
-            if let KeyCode::Char(c) = key.code {
-                editor.replace_char_at_cursor(c);
+            match key.code {
+                KeyCode::Char(c) => editor.replace_char_at_cursor(c),
+                KeyCode::Enter => editor.replace_char_with_line_break(),
+                _ => {}
             }
             editor.clear_pending_normal();
-            if matches!(key.code, KeyCode::Char(_)) {
+            if matches!(key.code, KeyCode::Char(_) | KeyCode::Enter) {
                 return InputResult::Continue;
             }
         }
@@ -143,11 +305,24 @@ fn handle_normal_mode(editor: &mut Editor, key: KeyEvent) -> InputResult {
     }
 
     match key.code {
+        // Ctrl+l repaints the whole screen
+        KeyCode::Char('l') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+            editor.redraw_pending = true;
+        }
+
+        // Page scrolling
+        KeyCode::Char('f') if key.modifiers.contains(KeyModifiers::CONTROL) => editor.page_down(),
+        KeyCode::Char('b') if key.modifiers.contains(KeyModifiers::CONTROL) => editor.page_up(),
+        KeyCode::PageDown => editor.page_down(),
+        KeyCode::PageUp => editor.page_up(),
+
         // Movement keys
-        KeyCode::Char('h') | KeyCode::Left => editor.move_left(),
+        KeyCode::Char('h') => editor.move_left_wrapping('h'),
+        KeyCode::Left => editor.move_left_wrapping('<'),
         KeyCode::Char('j') | KeyCode::Down => editor.move_down(),
         KeyCode::Char('k') | KeyCode::Up => editor.move_up(),
-        KeyCode::Char('l') | KeyCode::Right => editor.move_right(),
+        KeyCode::Char('l') => editor.move_right_wrapping('l'),
+        KeyCode::Right => editor.move_right_wrapping('>'),
 
         // Word movement
         KeyCode::Char('w') => editor.move_word_forward(),
@@ -156,14 +331,18 @@ fn handle_normal_mode(editor: &mut Editor, key: KeyEvent) -> InputResult {
         KeyCode::Char('W') => editor.move_word_forward(),
         KeyCode::Char('B') => editor.move_word_backward(),
         KeyCode::Char('E') => editor.move_to_end_of_word(),
+        KeyCode::Char(']') => editor.pending_normal = PendingNormal::SecondRightBracket,
+        KeyCode::Char('[') => editor.pending_normal = PendingNormal::SecondLeftBracket,
 
         // Line movement
+        KeyCode::Home => editor.smart_home(),
         KeyCode::Char('0') => editor.move_to_line_start(),
-        KeyCode::Char('$') => editor.move_to_line_end(),
+        KeyCode::Char('$') | KeyCode::End => editor.move_to_line_end(),
         KeyCode::Char('^') => editor.move_to_first_non_blank(),
         KeyCode::Char('G') => editor.move_to_last_line(),
         KeyCode::Char('{') => editor.move_paragraph_prev(),
         KeyCode::Char('}') => editor.move_paragraph_next(),
+        KeyCode::Char('%') => editor.move_to_match(),
         KeyCode::Char('g') => {
             if editor.pending_normal == PendingNormal::SecondG {
                 editor.move_to_first_line();
@@ -193,7 +372,18 @@ fn handle_normal_mode(editor: &mut Editor, key: KeyEvent) -> InputResult {
                 editor.pending_normal = PendingNormal::SecondD;
             }
         }
+        KeyCode::Char('r') if key.modifiers.contains(KeyModifiers::CONTROL) => editor.redo(),
         KeyCode::Char('r') => editor.pending_normal = PendingNormal::ReplaceChar,
+        KeyCode::Char('u') => editor.undo(),
+
+        // Folding (za, zo, zc)
+        KeyCode::Char('z') => editor.pending_normal = PendingNormal::SecondZ,
+
+        // ZZ (write if modified and quit), ZQ (quit without saving)
+        KeyCode::Char('Z') => editor.pending_normal = PendingNormal::SecondUpperZ,
+
+        // Enter visual mode
+        KeyCode::Char('v') => editor.enter_visual_mode(),
 
         // Enter command mode
         KeyCode::Char(':') => editor.enter_command_mode(),
@@ -218,17 +408,104 @@ fn handle_normal_mode(editor: &mut Editor, key: KeyEvent) -> InputResult {
     InputResult::Continue
 }
 
+/// Handle key events in visual mode: motions extend the selection from the anchor
+fn handle_visual_mode(editor: &mut Editor, key: KeyEvent) -> InputResult {
+    editor.clear_status();
+
+    match editor.pending_normal {
+        PendingNormal::SecondG => {
+            editor.clear_pending_normal();
+            if key.code == KeyCode::Char('g') {
+                editor.move_to_first_line();
+            }
+            return InputResult::Continue;
+        }
+        PendingNormal::Register => {
+            editor.clear_pending_normal();
+            match key.code {
+                KeyCode::Char('_') => editor.pending_normal = PendingNormal::Blackhole,
+                // The unnamed register is the default anyway
+                KeyCode::Char('"') => {}
+                KeyCode::Char(c) => editor.set_status(&format!("Invalid register name: {}", c)),
+                _ => {}
+            }
+            return InputResult::Continue;
+        }
+        PendingNormal::Blackhole => {
+            editor.clear_pending_normal();
+            if matches!(key.code, KeyCode::Char('d' | 'x')) {
+                editor.delete_selection(true);
+                return InputResult::Continue;
+            }
+        }
+        _ => {}
+    }
+
+    match key.code {
+        KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+            editor.enter_normal_mode();
+        }
+        KeyCode::Esc | KeyCode::Char('v') => editor.enter_normal_mode(),
+        KeyCode::Char('f') if key.modifiers.contains(KeyModifiers::CONTROL) => editor.page_down(),
+        KeyCode::Char('b') if key.modifiers.contains(KeyModifiers::CONTROL) => editor.page_up(),
+        KeyCode::PageDown => editor.page_down(),
+        KeyCode::PageUp => editor.page_up(),
+
+        // Motions
+        KeyCode::Char('h') => editor.move_left_wrapping('h'),
+        KeyCode::Left => editor.move_left_wrapping('<'),
+        KeyCode::Char('j') | KeyCode::Down => editor.move_down(),
+        KeyCode::Char('k') | KeyCode::Up => editor.move_up(),
+        KeyCode::Char('l') => editor.move_right_wrapping('l'),
+        KeyCode::Right => editor.move_right_wrapping('>'),
+        KeyCode::Char('w' | 'W') => editor.move_word_forward(),
+        KeyCode::Char('b' | 'B') => editor.move_word_backward(),
+        KeyCode::Char('e' | 'E') => editor.move_to_end_of_word(),
+        KeyCode::Home => editor.smart_home(),
+        KeyCode::Char('0') => editor.move_to_line_start(),
+        KeyCode::Char('$') | KeyCode::End => editor.move_to_line_end(),
+        KeyCode::Char('^') => editor.move_to_first_non_blank(),
+        KeyCode::Char('G') => editor.move_to_last_line(),
+        KeyCode::Char('g') => editor.pending_normal = PendingNormal::SecondG,
+        KeyCode::Char('{') => editor.move_paragraph_prev(),
+        KeyCode::Char('}') => editor.move_paragraph_next(),
+        KeyCode::Char('%') => editor.move_to_match(),
+        KeyCode::Char('o') => editor.swap_visual_ends(),
+
+        // Operators; `"_` (or Space) before d/x deletes without overwriting the register
+        KeyCode::Char('d' | 'x') => editor.delete_selection(false),
+        KeyCode::Char('y') => editor.yank_selection(),
+        KeyCode::Char('"') => editor.pending_normal = PendingNormal::Register,
+        KeyCode::Char(' ') => editor.pending_normal = PendingNormal::Blackhole,
+
+        _ => {}
+    }
+    InputResult::Continue
+}
+
 /// Handle key events in insert mode
 fn handle_insert_mode(editor: &mut Editor, key: KeyEvent) -> InputResult {
+    if let Some(literal) = editor.insert_literal.take() {
+        if handle_insert_literal(editor, literal, key) {
+            return InputResult::Continue;
+        }
+    }
+
     match key.code {
         // Exit insert mode
-        KeyCode::Esc => editor.enter_normal_mode(),
+        KeyCode::Esc => {
+            editor.expand_abbreviation();
+            editor.enter_normal_mode();
+        }
 
         // Backspace
         KeyCode::Backspace => editor.backspace(),
 
         // Enter/Return
-        KeyCode::Enter => editor.insert_newline(),
+        KeyCode::Enter => {
+            editor.expand_abbreviation();
+            editor.insert_newline();
+        }
 
         // Regular character input
         KeyCode::Char(c) => {
@@ -236,20 +513,33 @@ fn handle_insert_mode(editor: &mut Editor, key: KeyEvent) -> InputResult {
             if c == 'c' && key.modifiers.contains(KeyModifiers::CONTROL) {
                return return_to_normal_mode(editor);
             }
-            editor.insert_char(c);
+            // Ctrl+o: run one normal-mode command, then come back to insert
+            if c == 'o' && key.modifiers.contains(KeyModifiers::CONTROL) {
+                editor.begin_insert_normal_command();
+                return InputResult::Continue;
+            }
+            // Ctrl+v: insert the next key (or a character code) literally
+            if c == 'v' && key.modifiers.contains(KeyModifiers::CONTROL) {
+                editor.insert_literal = Some(LiteralInput::Key);
+                return InputResult::Continue;
+            }
+            editor.insert_typed_char(c);
         }
 
         // Arrow keys work in insert mode too
-        KeyCode::Left => editor.move_left(),
-        KeyCode::Right => editor.move_right(),
+        KeyCode::Left => editor.move_left_wrapping('['),
+        KeyCode::Right => editor.move_right_wrapping(']'),
         KeyCode::Up => editor.move_up(),
         KeyCode::Down => editor.move_down(),
+        KeyCode::Home => editor.smart_home(),
+        KeyCode::End => editor.move_to_line_end(),
+        KeyCode::PageDown => editor.page_down(),
+        KeyCode::PageUp => editor.page_up(),
 
-        // Tab inserts spaces (4 spaces)
+        // Tab inserts a tab, or spaces to the next tab stop with expandtab
         KeyCode::Tab => {
-            for _ in 0..4 {
-                editor.insert_char(' ');
-            }
+            editor.expand_abbreviation();
+            editor.insert_tab();
         }
 
         _ => {}
@@ -258,6 +548,83 @@ fn handle_insert_mode(editor: &mut Editor, key: KeyEvent) -> InputResult {
     InputResult::Continue
 }
 
+/// Handle the key after an insert-mode Ctrl+v: insert it literally (Tab, Esc and Ctrl+letter
+/// become control characters) or read a character code: up to 3 decimal digits, `x` + 2 hex,
+/// `o` + 3 octal, `u` + 4 hex or `U` + 8 hex digits. A key that ends a code early is not
+/// part of it; returns false for such a key so it is handled as usual afterwards.
+fn handle_insert_literal(editor: &mut Editor, literal: LiteralInput, key: KeyEvent) -> bool {
+    let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+    match literal {
+        LiteralInput::Key => {
+            let ch = match key.code {
+                KeyCode::Char(c) if ctrl && c.is_ascii_alphabetic() => {
+                    Some((c.to_ascii_uppercase() as u8 - b'@') as char)
+                }
+                KeyCode::Char(c) if !ctrl => {
+                    let (prefix, radix, max_len) = match c {
+                        '0'..='9' => (None, 10, 3),
+                        'x' | 'X' => (Some(c), 16, 2),
+                        'o' | 'O' => (Some(c), 8, 3),
+                        'u' => (Some(c), 16, 4),
+                        'U' => (Some(c), 16, 8),
+                        _ => {
+                            editor.insert_literal_char(c);
+                            return true;
+                        }
+                    };
+                    // A decimal code starts with the key itself
+                    let digits = if prefix.is_none() { c.to_string() } else { String::new() };
+                    editor.insert_literal = Some(LiteralInput::Code {
+                        prefix,
+                        digits,
+                        radix,
+                        max_len,
+                    });
+                    return true;
+                }
+                KeyCode::Tab => Some('\t'),
+                KeyCode::Esc => Some('\u{1b}'),
+                KeyCode::Backspace => Some('\u{8}'),
+                KeyCode::Enter => Some('\r'),
+                _ => None,
+            };
+            if let Some(ch) = ch {
+                editor.insert_literal_char(ch);
+            }
+            true
+        }
+        LiteralInput::Code {
+            prefix,
+            mut digits,
+            radix,
+            max_len,
+        } => {
+            let digit = match key.code {
+                KeyCode::Char(c) if !ctrl && c.is_digit(radix) => Some(c),
+                _ => None,
+            };
+            if let Some(c) = digit {
+                digits.push(c);
+                if digits.len() < max_len {
+                    editor.insert_literal = Some(LiteralInput::Code {
+                        prefix,
+                        digits,
+                        radix,
+                        max_len,
+                    });
+                    return true;
+                }
+            }
+            if !digits.is_empty() {
+                editor.insert_char_code(&digits, radix);
+            } else if let Some(prefix) = prefix {
+                editor.insert_literal_char(prefix);
+            }
+            digit.is_some()
+        }
+    }
+}
+
 /// Handle key events in search mode (vim /)
 fn handle_search_mode(editor: &mut Editor, key: KeyEvent) -> InputResult {
     match key.code {
@@ -289,37 +656,34 @@ fn handle_command_mode(app: &mut App, key: KeyEvent) -> InputResult {
 
         // Execute command
         KeyCode::Enter => {
-            let (is_toggle_sidebar, cmd_result) = {
+            let (app_command, cmd_result) = {
                 let editor = &mut app.editor;
-                let cmd = editor.command_buffer.trim();
-                let is_toggle = cmd == "e." || cmd == "Explore" || cmd == "Lexplore";
-                if is_toggle {
+                // Commands acting on the explorer are handled by App, not the editor
+                let app_command: Option<fn(&mut App)> = match editor.command_buffer.trim() {
+                    "e." | "Explore" | "Lexplore" => Some(App::toggle_sidebar_or_open_current_dir),
+                    "focus" => Some(App::toggle_explorer_focus),
+                    "reveal" => Some(App::reveal_current_file),
+                    _ => None,
+                };
+                if app_command.is_some() {
                     editor.command_buffer.clear();
                     editor.mode = Mode::Normal;
-                    (true, None)
+                    (app_command, None)
                 } else {
                     let result = editor.execute_command();
-                    (false, result)
+                    (None, result)
                 }
             };
-            if is_toggle_sidebar {
-                app.toggle_sidebar_or_open_current_dir();
+            if let Some(app_command) = app_command {
+                app_command(app);
+                return InputResult::Continue;
+            }
+            if let Some(EditorCommand::ChangedDirectory(dir)) = &cmd_result {
+                app.follow_directory(dir);
                 return InputResult::Continue;
             }
             if let Some(cmd_result) = cmd_result {
-                let editor = &mut app.editor;
-                match cmd_result {
-                    EditorCommand::Quit => {
-                        if editor.current_buffer().modified {
-                            editor.set_status("No write since last change (add ! to override)");
-                            return InputResult::Continue;
-                        }
-                        return InputResult::Exit;
-                    }
-                    EditorCommand::ForceQuit => {
-                        return InputResult::Exit;
-                    }
-                }
+                return apply_editor_command(&mut app.editor, cmd_result);
             }
         }
 
@@ -343,8 +707,624 @@ fn handle_command_mode(app: &mut App, key: KeyEvent) -> InputResult {
 
     InputResult::Continue
 }
+
+/// Turn an editor command (from `:` or a normal-mode key like ZZ) into an input result
+fn apply_editor_command(editor: &mut Editor, command: EditorCommand) -> InputResult {
+    match command {
+        EditorCommand::Quit => {
+            if editor.current_buffer().modified {
+                editor.set_status("No write since last change (add ! to override)");
+                return InputResult::Continue;
+            }
+            InputResult::Exit
+        }
+        EditorCommand::ForceQuit => InputResult::Exit,
+        EditorCommand::ChangedDirectory(_) => InputResult::Continue,
+    }
+}
+
 fn return_to_normal_mode(editor: &mut Editor) ->InputResult {
     editor.command_buffer.clear();
     editor.enter_normal_mode();
-    return InputResult::Continue;
+    InputResult::Continue
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::{temp_dir, temp_file};
+
+    /// Press each character of `keys` without modifiers; returns the result of the last one
+    fn press(app: &mut App, keys: &str) -> InputResult {
+        let mut result = InputResult::Continue;
+        for c in keys.chars() {
+            result = handle_key_event(app, KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE));
+        }
+        result
+    }
+
+    /// Run an ex command by typing it after `:`
+    fn command(app: &mut App, cmd: &str) -> InputResult {
+        press(app, &format!(":{}", cmd));
+        handle_key_event(app, KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE))
+    }
+
+    /// Press Ctrl+`c`
+    fn ctrl(app: &mut App, c: char) -> InputResult {
+        handle_key_event(app, KeyEvent::new(KeyCode::Char(c), KeyModifiers::CONTROL))
+    }
+
+    #[test]
+    fn zz_writes_a_modified_buffer_and_exits() {
+        let path = temp_file("zz", "abc\n");
+        let mut app = App::with_file(path.to_str().unwrap(), None).unwrap();
+        press(&mut app, "x");
+        assert!(app.editor.current_buffer().modified);
+        assert!(matches!(press(&mut app, "ZZ"), InputResult::Exit));
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "bc\n");
+    }
+
+    #[test]
+    fn zq_exits_without_writing() {
+        let path = temp_file("zq", "abc\n");
+        let mut app = App::with_file(path.to_str().unwrap(), None).unwrap();
+        press(&mut app, "x");
+        assert!(matches!(press(&mut app, "ZQ"), InputResult::Exit));
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "abc\n");
+    }
+
+    #[test]
+    fn z_then_another_key_cancels() {
+        let mut app = App::new();
+        assert!(matches!(press(&mut app, "Zx"), InputResult::Continue));
+        assert_eq!(app.editor.pending_normal, PendingNormal::None);
+        // The pending state is gone: a single Z waits again instead of quitting
+        assert!(matches!(press(&mut app, "Z"), InputResult::Continue));
+    }
+
+    #[test]
+    fn ctrl_o_runs_one_normal_command_and_stays_in_insert() {
+        let mut app = App::new();
+        press(&mut app, "ihello");
+        app.editor.cursor.col = 1;
+        ctrl(&mut app, 'o');
+        press(&mut app, "$");
+        assert_eq!(app.editor.mode, Mode::Insert);
+        // In insert mode $ goes past the last character
+        assert_eq!(app.editor.cursor.col, 5);
+        press(&mut app, "!");
+        assert_eq!(app.editor.current_buffer().line(0).unwrap().to_string(), "hello!");
+    }
+
+    #[test]
+    fn focus_command_moves_focus_to_the_explorer() {
+        let mut app = App::with_directory(&temp_dir("focus")).unwrap();
+        assert!(app.focus_on_explorer);
+        ctrl(&mut app, 'w');
+        press(&mut app, "w");
+        assert!(!app.focus_on_explorer);
+        command(&mut app, "focus");
+        assert!(app.focus_on_explorer);
+        assert_eq!(app.editor.mode, Mode::Normal);
+    }
+
+    #[test]
+    fn focus_command_without_an_explorer_only_reports() {
+        let mut app = App::new();
+        command(&mut app, "focus");
+        assert!(!app.focus_on_explorer);
+        assert_eq!(app.editor.status_message.as_deref(), Some("No file explorer open"));
+    }
+
+    #[test]
+    fn focus_command_with_the_sidebar_hidden_only_reports() {
+        let mut app = App::with_directory(&temp_dir("focus-hidden")).unwrap();
+        app.focus_on_explorer = false;
+        app.toggle_sidebar();
+        command(&mut app, "focus");
+        assert!(!app.focus_on_explorer);
+    }
+
+    #[test]
+    fn f1_shows_the_cheat_sheet_and_the_next_key_only_closes_it() {
+        let mut app = App::new();
+        handle_key_event(&mut app, KeyEvent::new(KeyCode::F(1), KeyModifiers::NONE));
+        assert!(app.help_visible);
+        press(&mut app, "i");
+        assert!(!app.help_visible);
+        assert_eq!(app.editor.mode, Mode::Normal);
+    }
+
+    #[test]
+    fn r_enter_replaces_the_character_with_a_line_break() {
+        let mut app = App::new();
+        press(&mut app, "ihello world");
+        handle_key_event(&mut app, KeyEvent::new(KeyCode::Esc, KeyModifiers::NONE));
+        app.editor.cursor.col = 5;
+        press(&mut app, "r");
+        handle_key_event(&mut app, KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE));
+        let buffer = app.editor.current_buffer();
+        assert_eq!(buffer.line(0).unwrap().to_string(), "hello\n");
+        assert_eq!(buffer.line(1).unwrap().to_string(), "world");
+        assert_eq!((app.editor.cursor.line, app.editor.cursor.col), (1, 0));
+        assert_eq!(app.editor.mode, Mode::Normal);
+        assert_eq!(app.editor.pending_normal, PendingNormal::None);
+    }
+
+    #[test]
+    fn g_upper_s_splits_the_line_without_deleting() {
+        let mut app = app_with("hello world\n");
+        app.editor.cursor.col = 5;
+        press(&mut app, "gS");
+        assert_eq!(buffer_text(&app), "hello\n world\n");
+        assert_eq!((app.editor.cursor.line, app.editor.cursor.col), (1, 0));
+        assert_eq!(app.editor.mode, Mode::Normal);
+        assert_eq!(app.editor.pending_normal, PendingNormal::None);
+        // One undo step, which J would also reverse
+        press(&mut app, "u");
+        assert_eq!(buffer_text(&app), "hello world\n");
+    }
+
+    #[test]
+    fn g_upper_s_at_the_ends_of_a_line() {
+        // At column 0 the whole line moves down below an empty one
+        let mut app = app_with("abc\n");
+        press(&mut app, "gS");
+        assert_eq!(buffer_text(&app), "\nabc\n");
+        assert_eq!((app.editor.cursor.line, app.editor.cursor.col), (1, 0));
+
+        // On the last character only that character moves
+        let mut app = app_with("abc\n");
+        press(&mut app, "$gS");
+        assert_eq!(buffer_text(&app), "ab\nc\n");
+        assert_eq!((app.editor.cursor.line, app.editor.cursor.col), (1, 0));
+
+        // Past the end (Ctrl+o from insert mode at the line end) an empty line opens below
+        let mut app = app_with("abc\n");
+        press(&mut app, "A");
+        ctrl(&mut app, 'o');
+        press(&mut app, "gS");
+        assert_eq!(buffer_text(&app), "abc\n\n");
+        assert_eq!((app.editor.cursor.line, app.editor.cursor.col), (1, 0));
+        assert_eq!(app.editor.mode, Mode::Insert);
+    }
+
+    #[test]
+    fn tab_with_expandtab_inserts_spaces_to_the_next_tab_stop() {
+        let mut app = App::new();
+        app.editor.options.apply("ts=4").unwrap();
+        let tab = |app: &mut App| {
+            handle_key_event(app, KeyEvent::new(KeyCode::Tab, KeyModifiers::NONE))
+        };
+        press(&mut app, "iab");
+        tab(&mut app);
+        assert_eq!(buffer_text(&app), "ab  ");
+        tab(&mut app);
+        press(&mut app, "c");
+        assert_eq!(buffer_text(&app), "ab      c");
+        assert_eq!(app.editor.cursor.col, 9);
+        // One undo step for the whole insert, like typed spaces
+        handle_key_event(&mut app, KeyEvent::new(KeyCode::Esc, KeyModifiers::NONE));
+        press(&mut app, "u");
+        assert_eq!(buffer_text(&app), "");
+    }
+
+    #[test]
+    fn tab_without_expandtab_inserts_a_tab_character() {
+        let mut app = App::new();
+        app.editor.options.apply("noexpandtab").unwrap();
+        app.editor.options.apply("ts=4").unwrap();
+        press(&mut app, "iab");
+        handle_key_event(&mut app, KeyEvent::new(KeyCode::Tab, KeyModifiers::NONE));
+        press(&mut app, "c");
+        assert_eq!(buffer_text(&app), "ab\tc");
+        assert_eq!(app.editor.cursor.col, 4);
+        // The cursor is drawn after the tab, at the tab stop
+        assert_eq!(app.editor.cursor_screen_col(), 5);
+    }
+
+    #[test]
+    fn r_enter_on_an_empty_line_does_nothing() {
+        let mut app = App::new();
+        press(&mut app, "r");
+        handle_key_event(&mut app, KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE));
+        assert_eq!(app.editor.current_buffer().line_count(), 1);
+        assert_eq!(app.editor.pending_normal, PendingNormal::None);
+    }
+
+    #[test]
+    fn g_semicolon_and_g_comma_walk_the_change_list() {
+        let mut app = App::new();
+        app.editor.current_buffer_mut().insert_str(0, 0, "aaa\nbbb\nccc\nddd");
+        for line in [0, 2, 3] {
+            app.editor.cursor.line = line;
+            app.editor.cursor.col = 1;
+            press(&mut app, "x");
+        }
+        assert_eq!(app.editor.current_buffer().changes, vec![(0, 1), (2, 1), (3, 1)]);
+        app.editor.cursor.line = 1;
+        press(&mut app, "g;");
+        assert_eq!(app.editor.cursor.line, 3);
+        press(&mut app, "g;g;");
+        assert_eq!(app.editor.cursor.line, 0);
+        press(&mut app, "g;");
+        assert_eq!(app.editor.status_message.as_deref(), Some("At start of change list"));
+        press(&mut app, "g,");
+        assert_eq!(app.editor.cursor.line, 2);
+        press(&mut app, "g,g,");
+        assert_eq!(app.editor.status_message.as_deref(), Some("At end of change list"));
+        assert_eq!(app.editor.cursor.line, 3);
+    }
+
+    #[test]
+    fn changes_on_one_line_leave_a_single_entry() {
+        let mut app = App::new();
+        press(&mut app, "ione two");
+        handle_key_event(&mut app, KeyEvent::new(KeyCode::Esc, KeyModifiers::NONE));
+        assert_eq!(app.editor.current_buffer().changes.len(), 1);
+        press(&mut app, "g;g;");
+        assert_eq!(app.editor.status_message.as_deref(), Some("At start of change list"));
+    }
+
+    #[test]
+    fn empty_change_list_reports() {
+        let mut app = App::new();
+        press(&mut app, "g;");
+        assert_eq!(app.editor.status_message.as_deref(), Some("Change list is empty"));
+    }
+
+    #[test]
+    fn bracket_brace_motions_jump_to_the_enclosing_block() {
+        let mut app = App::new();
+        app.editor.current_buffer_mut().insert_str(0, 0, "{\n  a\n}");
+        app.editor.cursor.line = 1;
+        press(&mut app, "]}");
+        assert_eq!((app.editor.cursor.line, app.editor.cursor.col), (2, 0));
+        press(&mut app, "[{");
+        assert_eq!((app.editor.cursor.line, app.editor.cursor.col), (0, 0));
+        press(&mut app, "[{");
+        assert_eq!(app.editor.status_message.as_deref(), Some("No enclosing block"));
+    }
+
+    /// Type `keys` after Ctrl+v in insert mode on an empty buffer; returns the first line
+    fn literal(app: &mut App, keys: &str) -> String {
+        press(app, "i");
+        ctrl(app, 'v');
+        press(app, keys);
+        app.editor.current_buffer().line(0).unwrap().to_string()
+    }
+
+    #[test]
+    fn ctrl_v_tab_inserts_a_real_tab_with_expandtab() {
+        let mut app = App::new();
+        app.editor.options.apply("expandtab").unwrap();
+        press(&mut app, "i");
+        ctrl(&mut app, 'v');
+        handle_key_event(&mut app, KeyEvent::new(KeyCode::Tab, KeyModifiers::NONE));
+        assert_eq!(app.editor.current_buffer().line(0).unwrap().to_string(), "\t");
+    }
+
+    #[test]
+    fn ctrl_v_reads_character_codes() {
+        assert_eq!(literal(&mut App::new(), "u00e9"), "é");
+        assert_eq!(literal(&mut App::new(), "065"), "A");
+        assert_eq!(literal(&mut App::new(), "x41"), "A");
+        assert_eq!(literal(&mut App::new(), "o101"), "A");
+        assert_eq!(literal(&mut App::new(), "U0001f600"), "😀");
+    }
+
+    #[test]
+    fn ctrl_v_code_ends_at_a_non_digit() {
+        // The non-digit is typed as usual after the code
+        assert_eq!(literal(&mut App::new(), "65z"), "Az");
+        // A prefix without digits is inserted itself
+        assert_eq!(literal(&mut App::new(), "ug"), "ug");
+    }
+
+    #[test]
+    fn ctrl_v_skips_autopairs_and_maps_ctrl_letters() {
+        let mut app = App::new();
+        app.editor.options.apply("autopairs").unwrap();
+        assert_eq!(literal(&mut app, "("), "(");
+        ctrl(&mut app, 'v');
+        ctrl(&mut app, 'a');
+        assert_eq!(app.editor.current_buffer().line(0).unwrap().to_string(), "(\u{1}");
+    }
+
+    #[test]
+    fn ctrl_v_refuses_line_break_characters() {
+        let mut app = App::new();
+        press(&mut app, "i");
+        ctrl(&mut app, 'v');
+        handle_key_event(&mut app, KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE));
+        assert_eq!(app.editor.current_buffer().line_count(), 1);
+        assert_eq!(
+            app.editor.status_message.as_deref(),
+            Some("Cannot insert a line break character literally")
+        );
+    }
+
+    #[test]
+    fn ctrl_l_and_redraw_request_a_full_repaint() {
+        let mut app = App::new();
+        ctrl(&mut app, 'l');
+        assert!(app.editor.redraw_pending);
+        assert_eq!(app.editor.current_buffer().line_count(), 1);
+        app.editor.redraw_pending = false;
+        for cmd in ["redraw", "redr!"] {
+            command(&mut app, cmd);
+            assert!(app.editor.redraw_pending, ":{}", cmd);
+            app.editor.redraw_pending = false;
+        }
+    }
+
+    #[test]
+    fn dash_in_the_explorer_goes_to_the_parent_directory() {
+        let root = temp_dir("explorer-dash");
+        std::fs::create_dir(root.join("sub")).unwrap();
+        let mut app = App::with_directory(&root.join("sub")).unwrap();
+        press(&mut app, "-");
+        let cwd = app.directory_state.as_ref().unwrap().file_explorer().cwd().clone();
+        assert_eq!(cwd, root.canonicalize().unwrap());
+    }
+
+    #[test]
+    fn space_f_reveals_the_current_file_in_the_explorer() {
+        let path = temp_file("reveal", "x\n");
+        let mut app = App::with_file(path.to_str().unwrap(), None).unwrap();
+        assert!(app.directory_state.is_none());
+        press(&mut app, " f");
+        assert!(app.sidebar_visible && app.focus_on_explorer);
+        let explorer = app.directory_state.as_ref().unwrap().file_explorer();
+        assert_eq!(explorer.current().path(), &path);
+    }
+
+    #[test]
+    fn large_paste_is_a_single_buffer_operation() {
+        let mut app = App::new();
+        press(&mut app, "i");
+        let text = "pasted line\r\n".repeat(10_000);
+        let revision = app.editor.current_buffer().revision();
+        handle_paste(&mut app, &text);
+        let buffer = app.editor.current_buffer();
+        assert_eq!(buffer.revision(), revision + 1);
+        assert_eq!(buffer.line_count(), 10_001);
+        assert_eq!(buffer.line(9_999).unwrap().to_string(), "pasted line\n");
+        assert_eq!((app.editor.cursor.line, app.editor.cursor.col), (10_000, 0));
+        assert_eq!(app.editor.current_buffer().changes.len(), 1);
+
+        handle_key_event(&mut app, KeyEvent::new(KeyCode::Esc, KeyModifiers::NONE));
+        press(&mut app, "u");
+        assert_eq!(app.editor.current_buffer().line_count(), 1);
+        assert_eq!(app.editor.current_buffer().line(0).unwrap().to_string(), "");
+    }
+
+    #[test]
+    fn paste_skips_autopairs_and_abbreviations() {
+        let mut app = App::new();
+        app.editor.options.apply("autopairs").unwrap();
+        command(&mut app, "iabbrev teh the");
+        press(&mut app, "i");
+        handle_paste(&mut app, "teh (x ");
+        assert_eq!(app.editor.current_buffer().line(0).unwrap().to_string(), "teh (x ");
+    }
+
+    #[test]
+    fn paste_on_the_command_line_takes_the_first_line() {
+        let mut app = App::new();
+        press(&mut app, ":");
+        handle_paste(&mut app, "set ts=3\nq!\n");
+        handle_key_event(&mut app, KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE));
+        assert_eq!(app.editor.options.tabstop, 3);
+    }
+
+    /// A bracketed paste against typing the same text one key at a time
+    #[test]
+    fn paste_is_faster_than_typing() {
+        let text = "some pasted text\n".repeat(2_000);
+
+        let mut app = App::new();
+        press(&mut app, "i");
+        let start = std::time::Instant::now();
+        handle_paste(&mut app, &text);
+        let pasted = start.elapsed();
+
+        let mut typed_app = App::new();
+        press(&mut typed_app, "i");
+        let start = std::time::Instant::now();
+        for c in text.chars() {
+            let code = if c == '\n' { KeyCode::Enter } else { KeyCode::Char(c) };
+            handle_key_event(&mut typed_app, KeyEvent::new(code, KeyModifiers::NONE));
+        }
+        let typed = start.elapsed();
+        assert_eq!(
+            app.editor.current_buffer().line_count(),
+            typed_app.editor.current_buffer().line_count()
+        );
+
+        assert!(pasted * 10 < typed);
+    }
+
+    #[test]
+    fn explorer_m_marks_and_t_opens_the_marked_files() {
+        let dir = temp_dir("explorer-marks");
+        for name in ["a.txt", "b.txt", "c.txt"] {
+            std::fs::write(dir.join(name), name).unwrap();
+        }
+        let mut app = App::with_directory(&dir).unwrap();
+        let root = app.directory_state.as_ref().unwrap().file_explorer().cwd().clone();
+        for name in ["c.txt", "a.txt"] {
+            app.directory_state.as_mut().unwrap().select(&root.join(name));
+            press(&mut app, "m");
+        }
+        press(&mut app, "t");
+        assert!(app.focus_on_explorer);
+        let opened: Vec<_> = app
+            .editor
+            .buffers
+            .iter()
+            .filter_map(|b| b.file_path.as_ref()?.file_name()?.to_str().map(str::to_string))
+            .collect();
+        assert_eq!(opened, ["c.txt", "a.txt"]);
+        // The marks are used up; t now opens the selected entry
+        app.directory_state.as_mut().unwrap().select(&root.join("b.txt"));
+        press(&mut app, "t");
+        assert_eq!(app.editor.status_message.as_deref(), Some("Opened 1 file"));
+    }
+
+    #[test]
+    fn u_and_ctrl_r_undo_and_redo_whole_commands() {
+        let mut app = App::new();
+        press(&mut app, "ione two");
+        handle_key_event(&mut app, KeyEvent::new(KeyCode::Esc, KeyModifiers::NONE));
+        press(&mut app, "0x");
+        let line = |app: &App| app.editor.current_buffer().line(0).unwrap().to_string();
+        assert_eq!(line(&app), "ne two");
+        press(&mut app, "u");
+        assert_eq!(line(&app), "one two");
+        // The whole insert session is one step
+        press(&mut app, "u");
+        assert_eq!(line(&app), "");
+        assert!(!app.editor.current_buffer().modified);
+        press(&mut app, "u");
+        assert_eq!(app.editor.status_message.as_deref(), Some("Already at oldest change"));
+        ctrl(&mut app, 'r');
+        ctrl(&mut app, 'r');
+        assert_eq!(line(&app), "ne two");
+        ctrl(&mut app, 'r');
+        assert_eq!(app.editor.status_message.as_deref(), Some("Already at newest change"));
+    }
+
+    /// An app holding `text`, in normal mode at the start
+    fn app_with(text: &str) -> App {
+        let mut app = App::new();
+        app.editor.current_buffer_mut().insert_str(0, 0, text);
+        app.editor.current_buffer_mut().commit_undo_step();
+        app
+    }
+
+    fn buffer_text(app: &App) -> String {
+        let buffer = app.editor.current_buffer();
+        (0..buffer.line_count()).filter_map(|l| buffer.line(l)).map(|l| l.to_string()).collect()
+    }
+
+    #[test]
+    fn visual_y_yanks_the_selection_including_the_cursor_char() {
+        let mut app = app_with("hello world\n");
+        press(&mut app, "wvlly");
+        assert_eq!(app.editor.register, "wor");
+        assert_eq!(app.editor.mode, Mode::Normal);
+        assert_eq!(app.editor.cursor.col, 6);
+        assert_eq!(buffer_text(&app), "hello world\n");
+    }
+
+    #[test]
+    fn visual_d_and_x_delete_backwards_selections_too() {
+        let mut app = app_with("hello world\n");
+        press(&mut app, "$vhhd");
+        assert_eq!(buffer_text(&app), "hello wo\n");
+        assert_eq!(app.editor.register, "rld");
+        press(&mut app, "0vex");
+        assert_eq!(buffer_text(&app), " wo\n");
+        press(&mut app, "u");
+        assert_eq!(buffer_text(&app), "hello wo\n");
+    }
+
+    #[test]
+    fn visual_delete_across_lines_joins_them() {
+        let mut app = app_with("one\ntwo\nthree\nfour\n");
+        press(&mut app, "lvjjd");
+        assert_eq!(buffer_text(&app), "oree\nfour\n");
+        assert_eq!(app.editor.register, "ne\ntwo\nth");
+        assert_eq!((app.editor.cursor.line, app.editor.cursor.col), (0, 1));
+    }
+
+    #[test]
+    fn blackhole_deletes_keep_the_unnamed_register() {
+        let mut app = app_with("hello big world\n");
+        press(&mut app, "vlllly");
+        assert_eq!(app.editor.register, "hello");
+        press(&mut app, "wvlll\"_d");
+        assert_eq!(buffer_text(&app), "hello world\n");
+        assert_eq!(app.editor.register, "hello");
+        // Space is the shortcut for "_
+        assert_eq!(app.editor.cursor.col, 6);
+        press(&mut app, "v$ x");
+        assert_eq!(buffer_text(&app), "hello \n");
+        assert_eq!(app.editor.register, "hello");
+        assert_eq!(app.editor.mode, Mode::Normal);
+        // The next delete fills the register again
+        press(&mut app, "0vd");
+        assert_eq!(app.editor.register, "h");
+    }
+
+    #[test]
+    fn register_prefix_only_applies_to_the_next_key() {
+        let mut app = app_with("abc\n");
+        press(&mut app, "vy");
+        press(&mut app, "v ld");
+        // Space then l moves, and the d that follows deletes into the register as usual
+        assert_eq!(buffer_text(&app), "c\n");
+        assert_eq!(app.editor.register, "ab");
+        press(&mut app, "v\"\"x");
+        assert_eq!(app.editor.register, "c");
+        press(&mut app, "v\"a");
+        assert_eq!(app.editor.status_message.as_deref(), Some("Invalid register name: a"));
+        assert_eq!(app.editor.mode, Mode::Visual);
+    }
+
+    #[test]
+    fn visual_o_swaps_ends_and_esc_leaves() {
+        let mut app = app_with("abcdef\n");
+        press(&mut app, "llvllo");
+        assert_eq!(app.editor.cursor.col, 2);
+        press(&mut app, "hy");
+        assert_eq!(app.editor.register, "bcde");
+        press(&mut app, "v");
+        handle_key_event(&mut app, KeyEvent::new(KeyCode::Esc, KeyModifiers::NONE));
+        assert_eq!(app.editor.mode, Mode::Normal);
+        assert_eq!(buffer_text(&app), "abcdef\n");
+    }
+
+    #[test]
+    fn home_and_end_in_normal_and_insert_mode() {
+        let mut app = app_with("  abc\n");
+        let key = |app: &mut App, code| {
+            handle_key_event(app, KeyEvent::new(code, KeyModifiers::NONE))
+        };
+        key(&mut app, KeyCode::End);
+        assert_eq!(app.editor.cursor.col, 4);
+        key(&mut app, KeyCode::Home);
+        assert_eq!(app.editor.cursor.col, 2);
+        press(&mut app, "i");
+        key(&mut app, KeyCode::End);
+        // Insert mode goes past the last character
+        assert_eq!(app.editor.cursor.col, 5);
+        key(&mut app, KeyCode::Home);
+        assert_eq!(app.editor.cursor.col, 2);
+        assert_eq!(app.editor.mode, Mode::Insert);
+    }
+
+    #[test]
+    fn page_keys_scroll_in_normal_and_insert_mode() {
+        let text: String = (0..100).map(|i| format!("{}\n", i)).collect();
+        let mut app = app_with(&text);
+        app.editor.options.scrolloff = 0;
+        let key = |app: &mut App, code| {
+            handle_key_event(app, KeyEvent::new(code, KeyModifiers::NONE))
+        };
+        key(&mut app, KeyCode::PageDown);
+        app.editor.adjust_viewport_with_height(10);
+        assert_eq!(app.editor.viewport_offset, 8);
+        press(&mut app, "i");
+        key(&mut app, KeyCode::PageDown);
+        app.editor.adjust_viewport_with_height(10);
+        assert_eq!(app.editor.viewport_offset, 16);
+        key(&mut app, KeyCode::PageUp);
+        key(&mut app, KeyCode::PageUp);
+        app.editor.adjust_viewport_with_height(10);
+        assert_eq!(app.editor.viewport_offset, 0);
+        assert_eq!(app.editor.mode, Mode::Insert);
+        assert_eq!(buffer_text(&app), text);
+    }
 }
@@ -1,23 +1,73 @@
 mod app;
 mod buffer;
+mod burst;
+mod cli;
+mod config;
 mod dir;
 mod editor;
+mod filetype;
+mod fold;
+mod git;
+mod history;
+mod idle;
 mod input;
+mod matchpair;
+mod modeline;
 mod mode;
+mod options;
+mod positions;
+mod range;
+mod sort;
+mod spell;
+mod statusline;
+mod tabs;
+#[cfg(test)]
+mod testutil;
 mod ui;
 
 use std::io::{self, stdout};
 use std::panic;
+use std::sync::atomic::{AtomicBool, Ordering};
 
 use crossterm::{
+    event::{DisableBracketedPaste, EnableBracketedPaste},
     execute,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
 use ratatui::{backend::CrosstermBackend, Terminal};
 
 use app::App;
+use cli::{CliAction, CliArgs};
+
+/// Raw mode is on (so `restore_terminal` knows what to undo; it may run from the panic hook)
+static RAW_MODE: AtomicBool = AtomicBool::new(false);
+/// The alternate screen is active
+static ALTERNATE_SCREEN: AtomicBool = AtomicBool::new(false);
+/// Bracketed paste is on (pastes arrive as one event instead of one key per character)
+static BRACKETED_PASTE: AtomicBool = AtomicBool::new(false);
 
 fn main() -> io::Result<()> {
+    // Handle --help/--version (and bad arguments) before touching the terminal
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let cli = match cli::parse_args(&args) {
+        CliAction::Help => {
+            println!("{}", cli::usage());
+            return Ok(());
+        }
+        CliAction::Version => {
+            println!("{}", cli::version());
+            return Ok(());
+        }
+        CliAction::Error(msg) => {
+            eprintln!("{}\n\n{}", msg, cli::usage());
+            std::process::exit(2);
+        }
+        CliAction::Run(cli) => cli,
+    };
+
+    // Read stdin (`vibeVim -`) while the terminal is still in its normal mode
+    let stdin_app = cli.stdin.then(App::with_stdin);
+
     // Set up panic handler to restore terminal on panic
     let original_hook = panic::take_hook();
     panic::set_hook(Box::new(move |panic_info| {
@@ -27,21 +77,36 @@ fn main() -> io::Result<()> {
         original_hook(panic_info);
     }));
 
-    // Initialize terminal
-    let terminal = setup_terminal()?;
-    let mut terminal = terminal;
+    // Initialize terminal (undoing a partial setup if it fails halfway)
+    let mut terminal = match setup_terminal(&cli) {
+        Ok(terminal) => terminal,
+        Err(e) => {
+            let _ = restore_terminal();
+            return Err(e);
+        }
+    };
+
+    // Read user options from config.toml (unless started with --clean) before opening the
+    // file, since some of them (maxfilesize) apply to loading it
+    let (options, config_errors) = config::startup_options(cli.no_config);
 
     // Create the application
-    let args: Vec<String> = std::env::args().collect();
-    let mut app = if args.len() > 1 {
-        let path_arg = &args[1];
+    let mut start_location = None;
+    let mut app = if let Some(result) = stdin_app {
+        result.unwrap_or_else(|e| {
+            let mut app = App::new();
+            app.editor.set_status(&format!("Cannot read stdin: {}", e));
+            app
+        })
+    } else if let Some(arg) = cli.path.as_deref() {
+        let (path_arg, location) = split_location(arg);
         let (path_opt, path_error) = if path_arg == "." {
             match std::env::current_dir() {
                 Ok(p) => (Some(p), None),
                 Err(e) => (None, Some(e)),
             }
         } else {
-            (Some(std::path::PathBuf::from(path_arg)), None)
+            (Some(std::path::PathBuf::from(&path_arg)), None)
         };
 
         if let Some(path) = path_opt {
@@ -55,16 +120,11 @@ fn main() -> io::Result<()> {
                     }
                 }
             } else {
-                match App::with_file(path_arg) {
-                    Ok(app) => app,
-                    Err(_) => {
-                        let mut app = App::new();
-                        app.editor.current_buffer_mut().file_path =
-                            Some(std::path::PathBuf::from(path_arg));
-                        app.editor.set_status(&format!("New file: {}", path_arg));
-                        app
-                    }
+                let (app, read) = app_for_file(&path_arg, options.max_file_size());
+                if read {
+                    start_location = location;
                 }
+                app
             }
         } else {
             let mut app = App::new();
@@ -76,28 +136,253 @@ fn main() -> io::Result<()> {
         App::new()
     };
 
+    app.editor.options = options;
+    app.editor.options.readonly |= cli.read_only;
+    if let Some(first) = config_errors.first() {
+        app.editor.set_status(first);
+    }
+    app.editor.apply_modelines();
+
+    // An explicit +N or file:line:col wins over the remembered position (which needs the
+    // options)
+    if let Some(line) = cli.line {
+        start_location = Some((line, 1));
+    }
+    match start_location {
+        Some((line, col)) => {
+            app.editor.goto_position(line.saturating_sub(1), col.saturating_sub(1))
+        }
+        None => app.editor.restore_position(),
+    }
+
     // Run the application
     let result = app.run(&mut terminal);
 
+    // Without the alternate screen the last frame stays; put the shell prompt below it
+    // (best effort: failing here must not skip restoring the terminal)
+    if cli.no_altscreen {
+        if let Ok(size) = terminal.size() {
+            let _ = terminal.set_cursor_position((0, size.height.saturating_sub(1)));
+        }
+        let _ = terminal.show_cursor();
+    }
+
     // Restore terminal
     restore_terminal()?;
+    if cli.no_altscreen {
+        println!();
+    }
 
     result
 }
 
-/// Set up the terminal for TUI rendering
-fn setup_terminal() -> io::Result<Terminal<CrosstermBackend<io::Stdout>>> {
-    enable_raw_mode()?;
-    let mut stdout = stdout();
-    execute!(stdout, EnterAlternateScreen)?;
-    let backend = CrosstermBackend::new(stdout);
-    let terminal = Terminal::new(backend)?;
+/// The app for a file argument, and whether the file was read. A file that doesn't exist yet
+/// opens as a new file; any other error leaves an empty buffer with the error shown.
+fn app_for_file(path: &str, max_size: Option<u64>) -> (App, bool) {
+    match App::with_file(path, max_size) {
+        Ok(app) => (app, true),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            let mut app = App::with_new_file(path);
+            app.editor.set_status(&format!("\"{}\" [New]", path));
+            (app, false)
+        }
+        Err(e) => {
+            // Don't attach the path: a later :w must not clobber a file we couldn't read
+            let mut app = App::new();
+            app.editor.set_status(&format!("Cannot open \"{}\": {}", path, e));
+            (app, false)
+        }
+    }
+}
+
+/// Split a trailing `:line` or `:line:col` suffix (as emitted by compilers and grep) off a
+/// path argument. The suffix is only recognized when the argument itself is not an existing
+/// file, so names that really contain colons still open as-is. Line and column are 1-based.
+fn split_location(arg: &str) -> (String, Option<(usize, usize)>) {
+    if std::path::Path::new(arg).exists() {
+        return (arg.to_string(), None);
+    }
+    let Some((head, last)) = arg.rsplit_once(':') else {
+        return (arg.to_string(), None);
+    };
+    let Ok(last) = last.parse::<usize>() else {
+        return (arg.to_string(), None);
+    };
+    if let Some((path, line)) = head.rsplit_once(':') {
+        if let (false, Ok(line)) = (path.is_empty(), line.parse::<usize>()) {
+            return (path.to_string(), Some((line, last)));
+        }
+    }
+    if head.is_empty() {
+        return (arg.to_string(), None);
+    }
+    (head.to_string(), Some((last, 1)))
+}
+
+/// One change `setup_terminal` makes to the terminal
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SetupStep {
+    RawMode,
+    AlternateScreen,
+    BracketedPaste,
+    /// Clear the main screen before drawing over it
+    ClearScreen,
+}
+
+/// What `setup_terminal` does for the given arguments, in order: the TUI draws in the
+/// alternate screen unless started with `--no-altscreen`, when the main screen is cleared
+/// and drawn over instead
+fn setup_steps(cli: &CliArgs) -> Vec<SetupStep> {
+    use SetupStep::*;
+    if cli.no_altscreen {
+        vec![RawMode, BracketedPaste, ClearScreen]
+    } else {
+        vec![RawMode, AlternateScreen, BracketedPaste]
+    }
+}
+
+/// Set up the terminal for TUI rendering (see `setup_steps`)
+fn setup_terminal(cli: &CliArgs) -> io::Result<Terminal<CrosstermBackend<io::Stdout>>> {
+    let mut terminal = Terminal::new(CrosstermBackend::new(stdout()))?;
+    for step in setup_steps(cli) {
+        match step {
+            SetupStep::RawMode => {
+                enable_raw_mode()?;
+                RAW_MODE.store(true, Ordering::SeqCst);
+            }
+            SetupStep::AlternateScreen => {
+                execute!(terminal.backend_mut(), EnterAlternateScreen)?;
+                ALTERNATE_SCREEN.store(true, Ordering::SeqCst);
+            }
+            SetupStep::BracketedPaste => {
+                execute!(terminal.backend_mut(), EnableBracketedPaste)?;
+                BRACKETED_PASTE.store(true, Ordering::SeqCst);
+            }
+            SetupStep::ClearScreen => terminal.clear()?,
+        }
+    }
     Ok(terminal)
 }
 
-/// Restore the terminal to its original state
+/// Restore the terminal to its original state. Only undoes what `setup_terminal` actually did,
+/// so it is safe to call more than once and after a failed or partial setup.
 fn restore_terminal() -> io::Result<()> {
-    disable_raw_mode()?;
-    execute!(stdout(), LeaveAlternateScreen)?;
+    if RAW_MODE.swap(false, Ordering::SeqCst) {
+        disable_raw_mode()?;
+    }
+    if BRACKETED_PASTE.swap(false, Ordering::SeqCst) {
+        execute!(stdout(), DisableBracketedPaste)?;
+    }
+    if ALTERNATE_SCREEN.swap(false, Ordering::SeqCst) {
+        execute!(stdout(), LeaveAlternateScreen)?;
+    }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use testutil::temp_dir;
+
+    #[test]
+    fn location_with_line() {
+        assert_eq!(
+            split_location("no/such/file.rs:42"),
+            ("no/such/file.rs".to_string(), Some((42, 1)))
+        );
+    }
+
+    #[test]
+    fn location_with_line_and_column() {
+        assert_eq!(
+            split_location("no/such/file.rs:42:10"),
+            ("no/such/file.rs".to_string(), Some((42, 10)))
+        );
+    }
+
+    #[test]
+    fn no_location_suffix() {
+        assert_eq!(split_location("no/such/file.rs"), ("no/such/file.rs".to_string(), None));
+        assert_eq!(split_location("no/such:file"), ("no/such:file".to_string(), None));
+        assert_eq!(split_location(":42"), (":42".to_string(), None));
+    }
+
+    #[test]
+    fn existing_file_with_colons_opens_as_is() {
+        let path = temp_dir("colons").join("notes:12");
+        std::fs::write(&path, "").unwrap();
+        let arg = path.to_str().unwrap();
+        assert_eq!(split_location(arg), (arg.to_string(), None));
+    }
+
+    #[test]
+    fn missing_file_opens_as_a_new_file() {
+        let path = temp_dir("new-file").join("new.rs");
+        let path = path.to_str().unwrap();
+        let (app, read) = app_for_file(path, None);
+        assert!(!read);
+        let buffer = app.editor.current_buffer();
+        assert!(buffer.is_new);
+        assert_eq!(buffer.file_path.as_deref(), Some(std::path::Path::new(path)));
+        assert_eq!(buffer.filetype.as_deref(), Some("rust"));
+        assert_eq!(app.editor.status_message, Some(format!("\"{}\" [New]", path)));
+    }
+
+    #[test]
+    fn unreadable_path_is_an_error_not_a_new_file() {
+        // A file used as a directory fails with something other than "not found"
+        let file = temp_dir("open-error").join("file");
+        std::fs::write(&file, "").unwrap();
+        let path = file.join("child");
+        let (app, read) = app_for_file(path.to_str().unwrap(), None);
+        assert!(!read);
+        let buffer = app.editor.current_buffer();
+        assert!(!buffer.is_new);
+        assert_eq!(buffer.file_path, None);
+        assert!(app.editor.status_message.unwrap().starts_with("Cannot open"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn permission_denied_is_an_error_not_a_new_file() {
+        use std::os::unix::fs::PermissionsExt;
+        let path = temp_dir("denied").join("secret");
+        std::fs::write(&path, "x").unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o000)).unwrap();
+        if std::fs::File::open(&path).is_ok() {
+            // Running as root: permissions are not enforced
+            return;
+        }
+        let (app, read) = app_for_file(path.to_str().unwrap(), None);
+        assert!(!read);
+        assert_eq!(app.editor.current_buffer().file_path, None);
+        assert!(app.editor.status_message.unwrap().contains("ermission denied"));
+    }
+
+    #[test]
+    fn no_altscreen_draws_over_the_main_screen() {
+        use SetupStep::*;
+        let args = |args: &[&str]| {
+            let args: Vec<String> = args.iter().map(|a| a.to_string()).collect();
+            match cli::parse_args(&args) {
+                CliAction::Run(cli) => cli,
+                other => panic!("not a run: {:?}", other),
+            }
+        };
+        assert_eq!(setup_steps(&args(&["f"])), [RawMode, AlternateScreen, BracketedPaste]);
+        assert_eq!(
+            setup_steps(&args(&["--no-altscreen", "f"])),
+            [RawMode, BracketedPaste, ClearScreen]
+        );
+    }
+
+    #[test]
+    fn restoring_without_setup_is_a_no_op() {
+        // Nothing was enabled, so nothing is written to the terminal, however often it runs
+        assert!(restore_terminal().is_ok());
+        assert!(restore_terminal().is_ok());
+        assert!(!RAW_MODE.load(Ordering::SeqCst));
+        assert!(!ALTERNATE_SCREEN.load(Ordering::SeqCst));
+        assert!(!BRACKETED_PASTE.load(Ordering::SeqCst));
+    }
+}
@@ -0,0 +1,218 @@
+//! The `%` motion: jump between matching brackets, or between keyword pairs such as
+//! shell `if`/`fi` for filetypes that define them (see `filetype::keyword_pairs`).
+//! Also the `[{`/`]}` motions to the brackets enclosing the cursor.
+
+use crate::buffer::Buffer;
+
+/// A bracket or keyword found in the text, with its position
+enum Token {
+    Bracket(char),
+    Word(String),
+}
+
+/// Chars of a line without its line break
+fn line_chars(buffer: &Buffer, line: usize) -> Vec<char> {
+    let len = buffer.line_len(line);
+    buffer
+        .line(line)
+        .map(|l| l.chars().take(len).collect())
+        .unwrap_or_default()
+}
+
+fn is_word_char(ch: char) -> bool {
+    ch.is_alphanumeric() || ch == '_'
+}
+
+/// Brackets and words of one line, as (column, token)
+fn tokens(chars: &[char]) -> Vec<(usize, Token)> {
+    let mut out = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        if is_word_char(chars[i]) {
+            let start = i;
+            while i < chars.len() && is_word_char(chars[i]) {
+                i += 1;
+            }
+            out.push((start, Token::Word(chars[start..i].iter().collect())));
+            continue;
+        }
+        if matches!(chars[i], '(' | ')' | '[' | ']' | '{' | '}') {
+            out.push((i, Token::Bracket(chars[i])));
+        }
+        i += 1;
+    }
+    out
+}
+
+/// Find the match for the bracket or keyword at (line, col). Keywords from `pairs`
+/// (openers, closer) are tried first when the cursor is on one; otherwise, like vim, the
+/// first bracket at or after the cursor on its line is matched. Nesting is counted across lines.
+pub fn find_match(
+    buffer: &Buffer,
+    line: usize,
+    col: usize,
+    pairs: &[(&[&str], &str)],
+) -> Option<(usize, usize)> {
+    let chars = line_chars(buffer, line);
+    let line_tokens = tokens(&chars);
+
+    // Keyword under the cursor
+    let word = line_tokens.iter().find_map(|(start, token)| match token {
+        Token::Word(w) if col >= *start && col < start + w.chars().count() => Some((*start, w)),
+        _ => None,
+    });
+    if let Some((start, word)) = word {
+        for (openers, closer) in pairs {
+            let is_open = |w: &str| openers.contains(&w);
+            let is_close = |w: &str| w == *closer;
+            if is_open(word) {
+                return scan(buffer, line, start, true, |t| match t {
+                    Token::Word(w) if is_open(w) => 1,
+                    Token::Word(w) if is_close(w) => -1,
+                    _ => 0,
+                });
+            }
+            if is_close(word) {
+                return scan(buffer, line, start, false, |t| match t {
+                    Token::Word(w) if is_close(w) => 1,
+                    Token::Word(w) if is_open(w) => -1,
+                    _ => 0,
+                });
+            }
+        }
+    }
+
+    // First bracket at or after the cursor
+    let (start, bracket) = line_tokens.iter().find_map(|(start, token)| match token {
+        Token::Bracket(b) if *start >= col => Some((*start, *b)),
+        _ => None,
+    })?;
+    let (open, close, forward) = match bracket {
+        '(' => ('(', ')', true),
+        '[' => ('[', ']', true),
+        '{' => ('{', '}', true),
+        ')' => ('(', ')', false),
+        ']' => ('[', ']', false),
+        _ => ('{', '}', false),
+    };
+    find_unmatched(buffer, line, start, (open, close), forward)
+}
+
+/// The unmatched `open` (backward) or `close` (forward) bracket of the pair enclosing
+/// (line, col), skipping nested pairs across lines. A bracket under the cursor is not counted,
+/// so from a `{` the enclosing `{` is found, like vim's `[{`.
+pub fn find_unmatched(
+    buffer: &Buffer,
+    line: usize,
+    col: usize,
+    (open, close): (char, char),
+    forward: bool,
+) -> Option<(usize, usize)> {
+    let (same, other) = if forward { (open, close) } else { (close, open) };
+    scan(buffer, line, col, forward, |t| match t {
+        Token::Bracket(b) if *b == same => 1,
+        Token::Bracket(b) if *b == other => -1,
+        _ => 0,
+    })
+}
+
+/// Walk tokens from the one at (line, col), exclusive, in the given direction. `weight`
+/// returns +1 for a nested opener, -1 for a closer; the closer at depth 0 is the match.
+fn scan(
+    buffer: &Buffer,
+    line: usize,
+    col: usize,
+    forward: bool,
+    weight: impl Fn(&Token) -> i32,
+) -> Option<(usize, usize)> {
+    let mut depth = 0;
+    let lines: Box<dyn Iterator<Item = usize>> = if forward {
+        Box::new(line..buffer.line_count())
+    } else {
+        Box::new((0..=line).rev())
+    };
+    for l in lines {
+        let mut line_tokens = tokens(&line_chars(buffer, l));
+        if !forward {
+            line_tokens.reverse();
+        }
+        for (start, token) in line_tokens {
+            let past_cursor = l != line || if forward { start > col } else { start < col };
+            if !past_cursor {
+                continue;
+            }
+            match weight(&token) {
+                1 => depth += 1,
+                -1 if depth == 0 => return Some((l, start)),
+                -1 => depth -= 1,
+                _ => {}
+            }
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filetype::keyword_pairs;
+    use crate::testutil::buffer;
+
+    #[test]
+    fn brackets_match_across_lines_and_nesting() {
+        let b = buffer("fn f() {\n    if (a[0]) {}\n}");
+        assert_eq!(find_match(&b, 0, 7, &[]), Some((2, 0)));
+        assert_eq!(find_match(&b, 2, 0, &[]), Some((0, 7)));
+        assert_eq!(find_match(&b, 1, 7, &[]), Some((1, 12)));
+        assert_eq!(find_match(&b, 1, 9, &[]), Some((1, 11)));
+        assert_eq!(find_match(&b, 1, 12, &[]), Some((1, 7)));
+    }
+
+    #[test]
+    fn cursor_before_a_bracket_jumps_from_the_next_one() {
+        let b = buffer("let x = (1);");
+        assert_eq!(find_match(&b, 0, 0, &[]), Some((0, 10)));
+        assert_eq!(find_match(&b, 0, 11, &[]), None);
+    }
+
+    #[test]
+    fn shell_keyword_pairs_match_with_nesting() {
+        let b = buffer("if a; then\n  if b; then x; fi\nfi");
+        let pairs = keyword_pairs("sh");
+        assert_eq!(find_match(&b, 0, 1, pairs), Some((2, 0)));
+        assert_eq!(find_match(&b, 2, 1, pairs), Some((0, 0)));
+        assert_eq!(find_match(&b, 1, 2, pairs), Some((1, 16)));
+    }
+
+    #[test]
+    fn makefile_pairs_accept_several_openers() {
+        let b = buffer("ifdef A\nifeq ($(B),1)\nendif\nendif");
+        let pairs = keyword_pairs("make");
+        assert_eq!(find_match(&b, 0, 0, pairs), Some((3, 0)));
+        assert_eq!(find_match(&b, 2, 0, pairs), Some((1, 0)));
+    }
+
+    #[test]
+    fn keywords_are_plain_words_without_a_filetype() {
+        let b = buffer("if (x) fi");
+        assert_eq!(find_match(&b, 0, 0, &[]), Some((0, 5)));
+    }
+
+    #[test]
+    fn enclosing_braces_skip_nested_blocks() {
+        let b = buffer("fn f() {\n    if x { y(); }\n    z();\n}");
+        assert_eq!(find_unmatched(&b, 2, 4, ('{', '}'), false), Some((0, 7)));
+        assert_eq!(find_unmatched(&b, 2, 4, ('{', '}'), true), Some((3, 0)));
+        // Inside the inner block
+        assert_eq!(find_unmatched(&b, 1, 11, ('{', '}'), false), Some((1, 9)));
+        assert_eq!(find_unmatched(&b, 1, 11, ('{', '}'), true), Some((1, 16)));
+    }
+
+    #[test]
+    fn a_brace_under_the_cursor_is_not_its_own_match() {
+        let b = buffer("{ a { b } }");
+        assert_eq!(find_unmatched(&b, 0, 4, ('{', '}'), false), Some((0, 0)));
+        assert_eq!(find_unmatched(&b, 0, 8, ('{', '}'), true), Some((0, 10)));
+        assert_eq!(find_unmatched(&b, 0, 0, ('{', '}'), false), None);
+    }
+}
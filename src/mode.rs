@@ -1,7 +1,8 @@
 /// The editing modes of the editor
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
 pub enum Mode {
     /// Normal mode - for navigation and commands
+    #[default]
     Normal,
     /// Insert mode - for typing text
     Insert,
@@ -9,6 +10,8 @@ pub enum Mode {
     Command,
     /// Search mode - for searching in buffer
     Search,
+    /// Visual mode - for selecting text character by character (after pressing v)
+    Visual,
 }
 
 impl Mode {
@@ -19,12 +22,7 @@ impl Mode {
             Mode::Insert => "INSERT",
             Mode::Command => "COMMAND",
             Mode::Search => "SEARCH",
+            Mode::Visual => "VISUAL",
         }
     }
 }
-
-impl Default for Mode {
-    fn default() -> Self {
-        Mode::Normal
-    }
-}
@@ -0,0 +1,129 @@
+//! Modelines: per-file settings in the first or last lines of a file, like vim's
+//! `// vim: set ts=2 et:` or `# vim: ts=4 noet`.
+//!
+//! Only options that are harmless for a file to choose are applied (see `is_allowed`); the
+//! rest, including options the editor doesn't have, are ignored.
+
+use crate::buffer::Buffer;
+
+/// Option names a modeline may set (with or without a `no`/`inv` prefix or `=value`)
+fn is_allowed(name: &str) -> bool {
+    matches!(
+        name,
+        "tabstop" | "ts" | "expandtab" | "et" | "filetype" | "ft" | "fixendofline" | "fixeol"
+    )
+}
+
+/// Name of the option an argument like `ts=4`, `noet` or `invet` refers to
+fn option_name(arg: &str) -> &str {
+    let name = arg.split_once('=').map_or(arg, |(name, _)| name);
+    let name = name.trim_end_matches('!');
+    ["no", "inv"]
+        .iter()
+        .find_map(|prefix| name.strip_prefix(prefix).filter(|n| is_allowed(n)))
+        .unwrap_or(name)
+}
+
+/// `:set`-style arguments from one line, if it is a modeline. Two forms are recognized:
+/// `vim: set ts=4 et:` (options end at the next `:`; trailing text such as `*/` is ignored)
+/// and `vim: ts=4:et` (options separated by spaces or colons up to the end of the line).
+/// The marker (`vi:`, `vim:`, `Vim:` or `ex:`) must start the line or follow whitespace.
+pub fn parse(line: &str) -> Option<Vec<String>> {
+    let rest = ["vi:", "vim:", "Vim:", "ex:"].iter().find_map(|marker| {
+        line.match_indices(marker).find_map(|(idx, _)| {
+            let after_blank = line[..idx].ends_with(char::is_whitespace);
+            (after_blank || (idx == 0 && *marker != "ex:")).then(|| &line[idx + marker.len()..])
+        })
+    })?;
+    let rest = rest.trim_start();
+    let set_form = rest
+        .strip_prefix("set ")
+        .or_else(|| rest.strip_prefix("se "));
+    let args: Vec<String> = match set_form {
+        // Everything up to the closing `:` (an escaped `\:` does not end it)
+        Some(options) => {
+            let mut end = options.len();
+            let mut prev = ' ';
+            for (i, c) in options.char_indices() {
+                if c == ':' && prev != '\\' {
+                    end = i;
+                    break;
+                }
+                prev = c;
+            }
+            options[..end]
+                .replace("\\:", ":")
+                .split_whitespace()
+                .map(str::to_string)
+                .collect()
+        }
+        None => rest
+            .split(|c: char| c == ':' || c.is_whitespace())
+            .filter(|arg| !arg.is_empty())
+            .map(str::to_string)
+            .collect(),
+    };
+    (!args.is_empty()).then_some(args)
+}
+
+/// Allowed option arguments from the modelines in the first and last `count` lines of
+/// `buffer`, in file order (later ones win when applied)
+pub fn find(buffer: &Buffer, count: usize) -> Vec<String> {
+    let line_count = buffer.last_text_line() + 1;
+    let head = 0..count.min(line_count);
+    let tail = line_count.saturating_sub(count).max(head.end)..line_count;
+    head.chain(tail)
+        .filter_map(|l| parse(&buffer.line(l)?.to_string()))
+        .flatten()
+        .filter(|arg| is_allowed(option_name(arg)) && !arg.ends_with('?'))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::buffer;
+
+    fn strings(items: &[&str]) -> Option<Vec<String>> {
+        Some(items.iter().map(|s| s.to_string()).collect())
+    }
+
+    #[test]
+    fn set_form_stops_at_the_closing_colon() {
+        assert_eq!(parse("/* vim: set ts=2 et: */"), strings(&["ts=2", "et"]));
+        assert_eq!(parse("# vi: se noet:"), strings(&["noet"]));
+        assert_eq!(parse("// vim: set ft=a\\:b:"), strings(&["ft=a:b"]));
+    }
+
+    #[test]
+    fn plain_form_runs_to_the_end_of_the_line() {
+        assert_eq!(parse("# vim: ts=4:et noet"), strings(&["ts=4", "et", "noet"]));
+        assert_eq!(parse("vim:ts=8"), strings(&["ts=8"]));
+    }
+
+    #[test]
+    fn marker_must_start_the_line_or_follow_whitespace() {
+        assert_eq!(parse("novim: ts=2"), None);
+        assert_eq!(parse("ex: ts=2"), None);
+        assert_eq!(parse("  ex: ts=2"), strings(&["ts=2"]));
+        assert_eq!(parse("just text"), None);
+        assert_eq!(parse("# vim:"), None);
+    }
+
+    #[test]
+    fn find_keeps_allowed_options_from_head_and_tail_lines() {
+        let buffer = buffer("# vim: ts=2 spell\nx\ny\nz\n# vim: set noet ft=sh ts?:\n");
+        assert_eq!(find(&buffer, 1), ["ts=2", "noet", "ft=sh"]);
+        // Lines in the middle are not looked at
+        assert_eq!(find(&buffer, 0), Vec::<String>::new());
+    }
+
+    #[test]
+    fn option_names_drop_prefixes_and_values() {
+        assert_eq!(option_name("noet"), "et");
+        assert_eq!(option_name("invfixeol"), "fixeol");
+        assert_eq!(option_name("ts=4"), "ts");
+        // Not an allowed option with a prefix: the name is kept as is
+        assert_eq!(option_name("nonsense"), "nonsense");
+    }
+}
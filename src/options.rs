@@ -0,0 +1,495 @@
+//! Editor options: changed at runtime with `:set` and loaded at startup from `config.toml`.
+
+use std::path::{Path, PathBuf};
+
+use ratatui::style::Color;
+
+/// Default status bar format (matches the original hardcoded layout)
+pub const DEFAULT_STATUSLINE: &str = " %f%m  %b%=%l:%c%n ";
+
+/// How `%f` shows the file in the status bar (the `statusline_path` option)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PathDisplay {
+    /// File name only
+    Name,
+    /// Path relative to the working directory (absolute when outside it)
+    Relative,
+    /// Absolute path
+    Absolute,
+}
+
+impl PathDisplay {
+    fn parse(value: &str) -> Result<Self, String> {
+        match value {
+            "name" => Ok(Self::Name),
+            "relative" => Ok(Self::Relative),
+            "absolute" => Ok(Self::Absolute),
+            _ => Err(format!("Invalid value for statusline_path: {}", value)),
+        }
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Name => "name",
+            Self::Relative => "relative",
+            Self::Absolute => "absolute",
+        }
+    }
+}
+
+/// What happens when the cursor rests for `updatetime` ms (the `cursorhold` option)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CursorHold {
+    /// Nothing
+    None,
+    /// Show the character under the cursor in the status line, like `ga`
+    CharInfo,
+}
+
+impl CursorHold {
+    fn parse(value: &str) -> Result<Self, String> {
+        match value {
+            "none" => Ok(Self::None),
+            "charinfo" => Ok(Self::CharInfo),
+            _ => Err(format!("Invalid value for cursorhold: {}", value)),
+        }
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::None => "none",
+            Self::CharInfo => "charinfo",
+        }
+    }
+}
+
+/// User-tunable editor options
+#[derive(Debug, Clone)]
+pub struct Options {
+    /// Status bar format string (see `statusline::expand` for placeholders)
+    pub statusline: String,
+    /// Whether `%f` shows the file name, relative path or absolute path
+    pub statusline_path: PathDisplay,
+    /// Insert the closing bracket/quote when typing an opening one
+    pub autopairs: bool,
+    /// Minimum screen rows kept above and below the cursor (999 keeps it centered)
+    pub scrolloff: usize,
+    /// Screen columns per tab stop
+    pub tabstop: usize,
+    /// Use spaces instead of tab characters when generating indentation (e.g. `:retab`, or
+    /// Tab in insert mode)
+    pub expandtab: bool,
+    /// Line motions (G, gg, dd, :N) put the cursor on the first non-blank instead of keeping the column
+    pub startofline: bool,
+    /// Center the match in the window after n/N (like mapping `n` to `nzz`)
+    pub searchcenter: bool,
+    /// Reopen files at the cursor position they were left at (see `positions`)
+    pub restorecursor: bool,
+    /// Keys that move to the previous/next line at the start/end of a line, comma-separated like
+    /// vim: `h`, `l`, `<`/`>` (arrows in normal mode), `[`/`]` (arrows in insert mode)
+    pub whichwrap: String,
+    /// Milliseconds without input before the cursor-hold action runs
+    pub updatetime: usize,
+    /// Action run when the cursor rests (see `CursorHold`)
+    pub cursorhold: CursorHold,
+    /// Add a final line break when writing files that were read without one (off: such files
+    /// are written back without it)
+    pub fixeol: bool,
+    /// Largest file (in MB) that is opened; bigger ones are refused instead of read into
+    /// memory. 0 means no limit
+    pub maxfilesize: usize,
+    /// Highlight words missing from the `spellfile` word list
+    pub spell: bool,
+    /// Word list used by `spell`, one word per line
+    pub spellfile: String,
+    /// Apply `vim: set ...:` modelines of files when they are opened (see `modeline`)
+    pub modeline: bool,
+    /// Number of lines at the start and at the end of a file searched for modelines
+    pub modelines: usize,
+    /// Underline the other occurrences of the word under the cursor on screen
+    pub cursorword: bool,
+    /// Highlight the background of the cursor's line
+    pub cursorline: bool,
+    /// Background of the `cursorline` highlight: a color name (`darkgray`), an ANSI index
+    /// (`236`) or `#rrggbb`
+    pub cursorlinecolor: Color,
+    /// Text columns (1-based) marked with a vertical ruler, set as a comma-separated list
+    pub colorcolumn: Vec<usize>,
+    /// Show the match index and count (`[3/12]`) after / and n/N
+    pub searchcount: bool,
+    /// Save by writing a temporary file and renaming it over the original, so a crash
+    /// mid-write cannot leave a truncated file
+    pub atomicsave: bool,
+    /// Copy the previous version of a file to a backup before overwriting it
+    pub backup: bool,
+    /// Appended to the file name to name its backup
+    pub backupext: String,
+    /// Directory for backups; empty keeps each next to its file
+    pub backupdir: String,
+    /// Treat keys that arrive faster than anyone types as a paste, inserting them without
+    /// abbreviations or autopairs (for terminals without bracketed paste)
+    pub pasteburst: bool,
+    /// Make `:e!` an undoable change (`u` brings back the discarded edits) instead of
+    /// starting the undo history over
+    pub undoreload: bool,
+    /// Refuse to write files (set by `vibeVim -R`)
+    pub readonly: bool,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            statusline: DEFAULT_STATUSLINE.to_string(),
+            statusline_path: PathDisplay::Name,
+            autopairs: false,
+            scrolloff: 0,
+            tabstop: 8,
+            expandtab: true,
+            startofline: true,
+            searchcenter: false,
+            restorecursor: false,
+            whichwrap: String::new(),
+            updatetime: 4000,
+            cursorhold: CursorHold::None,
+            fixeol: false,
+            maxfilesize: 1024,
+            spell: false,
+            spellfile: "/usr/share/dict/words".to_string(),
+            modeline: true,
+            modelines: 5,
+            cursorword: false,
+            cursorline: false,
+            cursorlinecolor: Color::Indexed(236),
+            colorcolumn: Vec::new(),
+            searchcount: true,
+            atomicsave: true,
+            backup: false,
+            backupext: "~".to_string(),
+            backupdir: String::new(),
+            pasteburst: false,
+            undoreload: true,
+            readonly: false,
+        }
+    }
+}
+
+impl Options {
+    /// Apply one `:set` argument: `name`, `noname`, `invname`/`name!`, `name=value` or `name?`.
+    /// Returns Ok(Some(text)) when the argument is a query whose answer should be shown.
+    pub fn apply(&mut self, arg: &str) -> Result<Option<String>, String> {
+        if let Some((name, value)) = arg.split_once('=') {
+            return self.set_value(name, value).map(|_| None);
+        }
+        if let Some(name) = arg.strip_suffix('?') {
+            return self
+                .value_of(name)
+                .map(|v| Some(format!("{}={}", name, v)))
+                .ok_or_else(|| format!("Unknown option: {}", name));
+        }
+        if let Some(name) = arg.strip_suffix('!').or_else(|| arg.strip_prefix("inv")) {
+            if let Some(flag) = self.bool_mut(name) {
+                *flag = !*flag;
+                return Ok(None);
+            }
+        }
+        if let Some(flag) = self.bool_mut(arg) {
+            *flag = true;
+            return Ok(None);
+        }
+        if let Some(name) = arg.strip_prefix("no") {
+            if let Some(flag) = self.bool_mut(name) {
+                *flag = false;
+                return Ok(None);
+            }
+        }
+        // `:set name` on a non-boolean option shows its value, like vim
+        match self.value_of(arg) {
+            Some(v) => Ok(Some(format!("{}={}", arg, v))),
+            None => Err(format!("Unknown option: {}", arg)),
+        }
+    }
+
+    /// Whether `whichwrap` lets `key` wrap to the adjacent line
+    pub fn wraps(&self, key: char) -> bool {
+        self.whichwrap.split(',').any(|flag| flag.len() == 1 && flag.starts_with(key))
+    }
+
+    /// `maxfilesize` in bytes, or None when there is no limit
+    pub fn max_file_size(&self) -> Option<u64> {
+        (self.maxfilesize > 0).then(|| self.maxfilesize as u64 * 1024 * 1024)
+    }
+
+    /// Where to back up `file` before overwriting it, or None when `backup` is off
+    pub fn backup_path(&self, file: &Path) -> Option<PathBuf> {
+        if !self.backup {
+            return None;
+        }
+        let mut name = file.file_name()?.to_os_string();
+        name.push(&self.backupext);
+        let dir = match self.backupdir.as_str() {
+            "" => file.parent().unwrap_or(Path::new("")),
+            dir => Path::new(dir),
+        };
+        Some(dir.join(name))
+    }
+
+    /// Set a valued option from its string form
+    fn set_value(&mut self, name: &str, value: &str) -> Result<(), String> {
+        match name {
+            "statusline" | "stl" => self.statusline = value.to_string(),
+            "statusline_path" => self.statusline_path = PathDisplay::parse(value)?,
+            "updatetime" | "ut" => self.updatetime = parse_number(name, value)?,
+            "cursorhold" => self.cursorhold = CursorHold::parse(value)?,
+            "maxfilesize" | "mfs" => self.maxfilesize = parse_number(name, value)?,
+            "spellfile" | "spf" => self.spellfile = value.to_string(),
+            "modelines" | "mls" => self.modelines = parse_number(name, value)?,
+            "cursorlinecolor" => {
+                self.cursorlinecolor = value
+                    .parse()
+                    .map_err(|_| format!("Invalid color for {}: {}", name, value))?
+            }
+            "colorcolumn" | "cc" => {
+                self.colorcolumn = value
+                    .split(',')
+                    .filter(|col| !col.is_empty())
+                    .map(|col| parse_positive(name, col))
+                    .collect::<Result<_, _>>()?
+            }
+            "backupext" | "bex" => {
+                if value.is_empty() {
+                    return Err("backupext must not be empty".to_string());
+                }
+                self.backupext = value.to_string();
+            }
+            "backupdir" | "bdir" => self.backupdir = value.to_string(),
+            "scrolloff" | "so" => self.scrolloff = parse_number(name, value)?,
+            "tabstop" | "ts" => self.tabstop = parse_positive(name, value)?,
+            "whichwrap" | "ww" => {
+                let known = |flag: &str| matches!(flag, "" | "h" | "l" | "<" | ">" | "[" | "]");
+                if let Some(bad) = value.split(',').find(|flag| !known(flag)) {
+                    return Err(format!("Invalid value for whichwrap: {}", bad));
+                }
+                self.whichwrap = value.to_string();
+            }
+            _ => return Err(format!("Unknown option: {}", name)),
+        }
+        Ok(())
+    }
+
+    /// Current value of an option as a string
+    fn value_of(&self, name: &str) -> Option<String> {
+        if let Some(flag) = self.flag(name) {
+            return Some(flag.to_string());
+        }
+        match name {
+            "statusline" | "stl" => Some(self.statusline.clone()),
+            "statusline_path" => Some(self.statusline_path.as_str().to_string()),
+            "updatetime" | "ut" => Some(self.updatetime.to_string()),
+            "cursorhold" => Some(self.cursorhold.as_str().to_string()),
+            "maxfilesize" | "mfs" => Some(self.maxfilesize.to_string()),
+            "spellfile" | "spf" => Some(self.spellfile.clone()),
+            "modelines" | "mls" => Some(self.modelines.to_string()),
+            "cursorlinecolor" => Some(self.cursorlinecolor.to_string()),
+            "colorcolumn" | "cc" => Some(
+                self.colorcolumn
+                    .iter()
+                    .map(usize::to_string)
+                    .collect::<Vec<_>>()
+                    .join(","),
+            ),
+            "backupext" | "bex" => Some(self.backupext.clone()),
+            "backupdir" | "bdir" => Some(self.backupdir.clone()),
+            "scrolloff" | "so" => Some(self.scrolloff.to_string()),
+            "tabstop" | "ts" => Some(self.tabstop.to_string()),
+            "whichwrap" | "ww" => Some(self.whichwrap.clone()),
+            _ => None,
+        }
+    }
+}
+
+/// Boolean options: the field and every name `:set` knows it by. `bool_mut` and `flag` are
+/// both generated from this one list, so a new flag is added here and nowhere else.
+macro_rules! boolean_options {
+    ($($field:ident: $($name:literal)|+,)*) => {
+        impl Options {
+            /// Boolean option by name (None if the option is not boolean or unknown)
+            fn bool_mut(&mut self, name: &str) -> Option<&mut bool> {
+                match name {
+                    $($($name)|+ => Some(&mut self.$field),)*
+                    _ => None,
+                }
+            }
+
+            /// Current value of a boolean option (None if not boolean or unknown)
+            fn flag(&self, name: &str) -> Option<bool> {
+                match name {
+                    $($($name)|+ => Some(self.$field),)*
+                    _ => None,
+                }
+            }
+        }
+    };
+}
+
+boolean_options! {
+    autopairs: "autopairs" | "ap",
+    expandtab: "expandtab" | "et",
+    startofline: "startofline" | "sol",
+    searchcenter: "searchcenter",
+    restorecursor: "restorecursor",
+    fixeol: "fixendofline" | "fixeol",
+    spell: "spell",
+    modeline: "modeline" | "ml",
+    cursorword: "cursorword",
+    cursorline: "cursorline" | "cul",
+    searchcount: "searchcount",
+    atomicsave: "atomicsave",
+    backup: "backup" | "bk",
+    pasteburst: "pasteburst",
+    undoreload: "undoreload" | "ur",
+    readonly: "readonly" | "ro",
+}
+
+/// Parse a numeric option value
+fn parse_number(name: &str, value: &str) -> Result<usize, String> {
+    value
+        .parse()
+        .map_err(|_| format!("Invalid number for {}: {}", name, value))
+}
+
+/// Parse a numeric option value that must be at least 1
+pub fn parse_positive(name: &str, value: &str) -> Result<usize, String> {
+    match parse_number(name, value)? {
+        0 => Err(format!("{} must be positive", name)),
+        n => Ok(n),
+    }
+}
+
+/// Split `:set` arguments on whitespace, honoring backslash-escaped spaces (`:set stl=%f\ %m`).
+pub fn split_set_args(args: &str) -> Vec<String> {
+    let mut out = Vec::new();
+    let mut current = String::new();
+    let mut chars = args.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some(next) => current.push(next),
+                None => current.push('\\'),
+            },
+            c if c.is_whitespace() => {
+                if !current.is_empty() {
+                    out.push(std::mem::take(&mut current));
+                }
+            }
+            c => current.push(c),
+        }
+    }
+    if !current.is_empty() {
+        out.push(current);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn boolean_option_forms() {
+        let mut options = Options::default();
+        options.apply("autopairs").unwrap();
+        assert!(options.autopairs);
+        options.apply("noautopairs").unwrap();
+        assert!(!options.autopairs);
+        options.apply("invautopairs").unwrap();
+        assert!(options.autopairs);
+        options.apply("autopairs!").unwrap();
+        assert!(!options.autopairs);
+        assert_eq!(options.apply("autopairs?"), Ok(Some("autopairs=false".to_string())));
+    }
+
+    #[test]
+    fn short_names_set_the_same_flag() {
+        let mut options = Options::default();
+        options.apply("nofixeol").unwrap();
+        options.apply("fixendofline").unwrap();
+        assert!(options.fixeol);
+        options.apply("noet").unwrap();
+        assert_eq!(options.apply("expandtab?"), Ok(Some("expandtab=false".to_string())));
+    }
+
+    #[test]
+    fn valued_options() {
+        let mut options = Options::default();
+        options.apply("ts=4").unwrap();
+        assert_eq!(options.tabstop, 4);
+        assert_eq!(options.apply("tabstop"), Ok(Some("tabstop=4".to_string())));
+        options.apply("cc=80,100").unwrap();
+        assert_eq!(options.colorcolumn, vec![80, 100]);
+        assert!(options.apply("ts=0").is_err());
+        assert!(options.apply("ts=x").is_err());
+    }
+
+    #[test]
+    fn unknown_options_are_errors() {
+        let mut options = Options::default();
+        assert_eq!(options.apply("nosuch"), Err("Unknown option: nosuch".to_string()));
+        assert!(options.apply("nosuch=1").is_err());
+        assert!(options.apply("notabstop").is_err());
+    }
+
+    #[test]
+    fn set_args_split_on_unescaped_spaces() {
+        assert_eq!(split_set_args(r"stl=%f\ %m ts=4"), vec!["stl=%f %m", "ts=4"]);
+    }
+
+    #[test]
+    fn whichwrap_flags() {
+        let mut options = Options::default();
+        assert!(!options.wraps('h'));
+        options.apply("ww=h,<,]").unwrap();
+        assert!(options.wraps('h') && options.wraps('<') && options.wraps(']'));
+        assert!(!options.wraps('l') && !options.wraps('['));
+        assert_eq!(options.apply("whichwrap=h,x"), Err("Invalid value for whichwrap: x".to_string()));
+        assert_eq!(options.whichwrap, "h,<,]");
+    }
+
+    #[test]
+    fn cursorlinecolor_accepts_names_indexes_and_hex() {
+        let mut options = Options::default();
+        options.apply("cursorlinecolor=darkgray").unwrap();
+        assert_eq!(options.cursorlinecolor, Color::DarkGray);
+        options.apply("cursorlinecolor=17").unwrap();
+        assert_eq!(options.cursorlinecolor, Color::Indexed(17));
+        options.apply("cursorlinecolor=#ff0000").unwrap();
+        assert_eq!(options.cursorlinecolor, Color::Rgb(255, 0, 0));
+        assert_eq!(
+            options.apply("cursorlinecolor=nope"),
+            Err("Invalid color for cursorlinecolor: nope".to_string())
+        );
+    }
+
+    #[test]
+    fn colorcolumn_list() {
+        let mut options = Options::default();
+        options.apply("cc=80,120").unwrap();
+        assert_eq!(options.colorcolumn, [80, 120]);
+        assert_eq!(options.apply("colorcolumn?"), Ok(Some("colorcolumn=80,120".to_string())));
+        options.apply("cc=").unwrap();
+        assert!(options.colorcolumn.is_empty());
+        assert!(options.apply("cc=0").is_err());
+    }
+
+    #[test]
+    fn backup_path_uses_backupext_and_backupdir() {
+        let mut options = Options::default();
+        let file = Path::new("/src/main.rs");
+        assert_eq!(options.backup_path(file), None);
+        options.apply("backup").unwrap();
+        assert_eq!(options.backup_path(file), Some(PathBuf::from("/src/main.rs~")));
+        options.apply("bex=.bak").unwrap();
+        options.apply("backupdir=/tmp/backups").unwrap();
+        assert_eq!(options.backup_path(file), Some(PathBuf::from("/tmp/backups/main.rs.bak")));
+        assert!(options.apply("backupext=").is_err());
+    }
+}
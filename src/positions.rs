@@ -0,0 +1,117 @@
+//! Last cursor position per file (like vim's viminfo `"` mark), kept in
+//! `$XDG_DATA_HOME/vibevim/positions` (or `~/.local/share/vibevim/positions`).
+//!
+//! One `line<TAB>col<TAB>path` entry per line (0-based line and column, absolute path),
+//! most recently used first.
+
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Number of files remembered; older entries are dropped
+const MAX_ENTRIES: usize = 100;
+
+/// Location of the position store
+pub fn store_path() -> Option<PathBuf> {
+    let base = std::env::var_os("XDG_DATA_HOME")
+        .filter(|v| !v.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|h| PathBuf::from(h).join(".local/share")))?;
+    Some(base.join("vibevim").join("positions"))
+}
+
+/// Parse the store's contents, skipping malformed lines
+fn parse(contents: &str) -> Vec<(PathBuf, usize, usize)> {
+    contents
+        .lines()
+        .filter_map(|entry| {
+            let mut fields = entry.splitn(3, '\t');
+            let line = fields.next()?.parse().ok()?;
+            let col = fields.next()?.parse().ok()?;
+            let path = fields.next().filter(|p| !p.is_empty())?;
+            Some((PathBuf::from(path), line, col))
+        })
+        .collect()
+}
+
+/// Remembered (line, col) for `file` in the store at `store`
+pub fn load(store: &Path, file: &Path) -> Option<(usize, usize)> {
+    let contents = std::fs::read_to_string(store).ok()?;
+    parse(&contents)
+        .into_iter()
+        .find(|(path, _, _)| path == file)
+        .map(|(_, line, col)| (line, col))
+}
+
+/// Record (line, col) for `file` in the store at `store`, creating it if needed
+pub fn save(store: &Path, file: &Path, line: usize, col: usize) -> io::Result<()> {
+    let mut entries = match std::fs::read_to_string(store) {
+        Ok(contents) => parse(&contents),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Vec::new(),
+        Err(e) => return Err(e),
+    };
+    entries.retain(|(path, _, _)| path != file);
+    entries.insert(0, (file.to_path_buf(), line, col));
+    entries.truncate(MAX_ENTRIES);
+
+    let mut out = String::new();
+    for (path, line, col) in &entries {
+        out.push_str(&format!("{}\t{}\t{}\n", line, col, path.display()));
+    }
+    if let Some(dir) = store.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    std::fs::write(store, out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::temp_dir;
+
+    /// Store path inside a fresh scratch directory (the store itself does not exist yet)
+    fn store(name: &str) -> PathBuf {
+        temp_dir(name).join("vibevim").join("positions")
+    }
+
+    #[test]
+    fn save_then_load() {
+        let store = store("positions-roundtrip");
+        save(&store, Path::new("/a.txt"), 10, 4).unwrap();
+        save(&store, Path::new("/b.txt"), 2, 0).unwrap();
+        assert_eq!(load(&store, Path::new("/a.txt")), Some((10, 4)));
+        assert_eq!(load(&store, Path::new("/b.txt")), Some((2, 0)));
+        assert_eq!(load(&store, Path::new("/c.txt")), None);
+    }
+
+    #[test]
+    fn saving_again_replaces_the_entry_and_moves_it_first() {
+        let store = store("positions-replace");
+        save(&store, Path::new("/a.txt"), 1, 1).unwrap();
+        save(&store, Path::new("/b.txt"), 2, 2).unwrap();
+        save(&store, Path::new("/a.txt"), 3, 3).unwrap();
+        let contents = std::fs::read_to_string(&store).unwrap();
+        assert_eq!(contents, "3\t3\t/a.txt\n2\t2\t/b.txt\n");
+    }
+
+    #[test]
+    fn old_entries_are_dropped() {
+        let store = store("positions-limit");
+        for i in 0..=MAX_ENTRIES {
+            save(&store, Path::new(&format!("/f{}", i)), i, 0).unwrap();
+        }
+        assert_eq!(load(&store, Path::new("/f0")), None);
+        assert_eq!(load(&store, Path::new("/f1")), Some((1, 0)));
+    }
+
+    #[test]
+    fn malformed_lines_are_skipped() {
+        let entries = parse("1\t2\t/ok\nnot a line\nx\t2\t/bad\n3\t4\t\n5\t6\t/tab\tin name\n");
+        assert_eq!(
+            entries,
+            vec![
+                (PathBuf::from("/ok"), 1, 2),
+                (PathBuf::from("/tab\tin name"), 5, 6)
+            ]
+        );
+    }
+}
@@ -0,0 +1,112 @@
+//! Ex command line ranges (`:5,20cmd`, `:%cmd`, `:.,$cmd`, `:.+1cmd`).
+
+/// An inclusive, 0-based range of buffer lines
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LineRange {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl LineRange {
+    /// The whole buffer
+    pub fn all(line_count: usize) -> Self {
+        Self {
+            start: 0,
+            end: line_count.saturating_sub(1),
+        }
+    }
+}
+
+/// Parse a single address (`N`, `.`, `$`, optionally followed by `+N`/`-N`) at the start of
+/// `s`. Returns the 0-based line and the rest of the input, or None if there is no address.
+/// Numbers too large for the arithmetic saturate, so they end up clamped to the buffer.
+fn parse_address(s: &str, cursor_line: usize, line_count: usize) -> Option<(usize, &str)> {
+    let last = line_count.saturating_sub(1);
+    let digits = s.chars().take_while(|c| c.is_ascii_digit()).count();
+    let (mut line, mut rest) = if digits > 0 {
+        let n: usize = s[..digits].parse().unwrap_or(usize::MAX);
+        (isize::try_from(n.saturating_sub(1)).unwrap_or(isize::MAX), &s[digits..])
+    } else if let Some(rest) = s.strip_prefix('.') {
+        (cursor_line as isize, rest)
+    } else if let Some(rest) = s.strip_prefix('$') {
+        (last as isize, rest)
+    } else if s.starts_with(['+', '-']) {
+        // A bare offset is relative to the cursor line
+        (cursor_line as isize, s)
+    } else {
+        return None;
+    };
+    while let Some(sign) = rest.chars().next().filter(|c| *c == '+' || *c == '-') {
+        let after = &rest[1..];
+        let digits = after.chars().take_while(|c| c.is_ascii_digit()).count();
+        let n: isize = if digits == 0 { 1 } else { after[..digits].parse().unwrap_or(isize::MAX) };
+        line = if sign == '+' { line.saturating_add(n) } else { line.saturating_sub(n) };
+        rest = &after[digits..];
+    }
+    Some((line.clamp(0, last as isize) as usize, rest))
+}
+
+/// Split a leading range off an ex command. Returns the range (None if the command has no
+/// range) and the remaining command text. A reversed range (`:20,5`) is swapped, like vim.
+pub fn parse_range(cmd: &str, cursor_line: usize, line_count: usize) -> (Option<LineRange>, &str) {
+    if let Some(rest) = cmd.strip_prefix('%') {
+        return (Some(LineRange::all(line_count)), rest.trim_start());
+    }
+    let Some((start, rest)) = parse_address(cmd, cursor_line, line_count) else {
+        return (None, cmd);
+    };
+    let (end, rest) = match rest.strip_prefix(',') {
+        Some(after) => parse_address(after, cursor_line, line_count).unwrap_or((start, after)),
+        None => (start, rest),
+    };
+    let range = LineRange {
+        start: start.min(end),
+        end: start.max(end),
+    };
+    (Some(range), rest.trim_start())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn range(start: usize, end: usize) -> Option<LineRange> {
+        Some(LineRange { start, end })
+    }
+
+    #[test]
+    fn numeric_and_whole_buffer_ranges() {
+        assert_eq!(parse_range("5,20retab", 0, 100), (range(4, 19), "retab"));
+        assert_eq!(parse_range("%retab!", 0, 100), (range(0, 99), "retab!"));
+        assert_eq!(parse_range("7d", 0, 100), (range(6, 6), "d"));
+    }
+
+    #[test]
+    fn cursor_and_last_line_addresses() {
+        assert_eq!(parse_range(".,$sort", 9, 100), (range(9, 99), "sort"));
+        assert_eq!(parse_range(".+1,.+3d", 9, 100), (range(10, 12), "d"));
+        assert_eq!(parse_range("-2,+d", 9, 100), (range(7, 10), "d"));
+    }
+
+    #[test]
+    fn reversed_and_out_of_bounds_ranges() {
+        assert_eq!(parse_range("20,5retab", 0, 100), (range(4, 19), "retab"));
+        assert_eq!(parse_range("50,500d", 0, 100), (range(49, 99), "d"));
+        assert_eq!(parse_range("0d", 0, 100), (range(0, 0), "d"));
+    }
+
+    #[test]
+    fn huge_numbers_clamp_instead_of_overflowing() {
+        assert_eq!(parse_range("+9223372036854775807+1d", 9, 100), (range(99, 99), "d"));
+        assert_eq!(parse_range("18446744073709551615d", 9, 100), (range(99, 99), "d"));
+        assert_eq!(parse_range("99999999999999999999999d", 9, 100), (range(99, 99), "d"));
+        assert_eq!(parse_range("-99999999999999999999999d", 9, 100), (range(0, 0), "d"));
+        assert_eq!(parse_range("$-9223372036854775807-9d", 9, 100), (range(0, 0), "d"));
+    }
+
+    #[test]
+    fn no_range() {
+        assert_eq!(parse_range("retab", 3, 100), (None, "retab"));
+        assert_eq!(parse_range("w !cat", 3, 100), (None, "w !cat"));
+    }
+}
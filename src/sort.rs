@@ -0,0 +1,119 @@
+//! Line ordering for `:sort`: `!` reverses, `n` compares the first number on each line and
+//! `u` keeps only the first of equal lines.
+
+use std::cmp::Ordering;
+
+/// Options of a `:sort` command
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SortFlags {
+    pub reverse: bool,
+    pub numeric: bool,
+    pub unique: bool,
+}
+
+impl SortFlags {
+    /// Flags from `:sort[!] [n][u]` (the bang and the argument text)
+    pub fn parse(bang: bool, args: &str) -> Result<Self, String> {
+        let mut flags = Self {
+            reverse: bang,
+            ..Self::default()
+        };
+        for c in args.chars().filter(|c| !c.is_whitespace()) {
+            match c {
+                'n' => flags.numeric = true,
+                'u' => flags.unique = true,
+                _ => return Err(format!("Invalid argument: {}", args.trim())),
+            }
+        }
+        Ok(flags)
+    }
+}
+
+/// First decimal number in `line`, including a `-` right before it. Numbers too large for
+/// an i64 saturate.
+pub fn first_number(line: &str) -> Option<i64> {
+    let start = line.find(|c: char| c.is_ascii_digit())?;
+    let digits = line[start..]
+        .find(|c: char| !c.is_ascii_digit())
+        .map_or(&line[start..], |end| &line[start..start + end]);
+    let negative = line[..start].ends_with('-');
+    let number = match digits.parse::<i64>() {
+        Ok(n) if negative => -n,
+        Ok(n) => n,
+        Err(_) if negative => i64::MIN,
+        Err(_) => i64::MAX,
+    };
+    Some(number)
+}
+
+/// Sort `lines` by `flags`. The sort is stable; with `numeric`, lines without a number come
+/// first (in their original order), like vim.
+pub fn sort_lines(lines: &mut Vec<String>, flags: SortFlags) {
+    let compare = |a: &String, b: &String| -> Ordering {
+        let ord = if flags.numeric {
+            first_number(a).cmp(&first_number(b))
+        } else {
+            a.cmp(b)
+        };
+        if flags.reverse {
+            ord.reverse()
+        } else {
+            ord
+        }
+    };
+    lines.sort_by(compare);
+    if flags.unique {
+        lines.dedup_by(|b, a| compare(a, b) == Ordering::Equal);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sorted(lines: &[&str], flags: SortFlags) -> Vec<String> {
+        let mut lines: Vec<String> = lines.iter().map(|l| l.to_string()).collect();
+        sort_lines(&mut lines, flags);
+        lines
+    }
+
+    #[test]
+    fn flags_from_bang_and_arguments() {
+        assert_eq!(SortFlags::parse(false, ""), Ok(SortFlags::default()));
+        let all = SortFlags { reverse: true, numeric: true, unique: true };
+        assert_eq!(SortFlags::parse(true, " n u"), Ok(all));
+        assert_eq!(SortFlags::parse(false, "x"), Err("Invalid argument: x".to_string()));
+    }
+
+    #[test]
+    fn first_number_includes_a_leading_minus() {
+        assert_eq!(first_number("x = -12;"), Some(-12));
+        assert_eq!(first_number("a-b 7"), Some(7));
+        assert_eq!(first_number("v2-3"), Some(2));
+        assert_eq!(first_number("none"), None);
+        assert_eq!(first_number("99999999999999999999"), Some(i64::MAX));
+        assert_eq!(first_number("-99999999999999999999"), Some(i64::MIN));
+    }
+
+    #[test]
+    fn numeric_sort_orders_negatives_first_and_keeps_numberless_lines_on_top() {
+        let flags = SortFlags { numeric: true, ..SortFlags::default() };
+        assert_eq!(
+            sorted(&["b 10", "x", "a -3", "c 2", "y"], flags),
+            ["x", "y", "a -3", "c 2", "b 10"]
+        );
+    }
+
+    #[test]
+    fn text_sort_reverse_and_unique() {
+        assert_eq!(sorted(&["b", "a", "c"], SortFlags::default()), ["a", "b", "c"]);
+        let flags = SortFlags { reverse: true, unique: true, ..SortFlags::default() };
+        assert_eq!(sorted(&["b", "a", "b", "c"], flags), ["c", "b", "a"]);
+    }
+
+    #[test]
+    fn unique_numeric_treats_equal_numbers_as_duplicates() {
+        let flags = SortFlags { numeric: true, unique: true, ..SortFlags::default() };
+        assert_eq!(sorted(&["x 1", "y 01", "z 2"], flags), ["x 1", "z 2"]);
+    }
+}
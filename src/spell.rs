@@ -0,0 +1,109 @@
+//! Spell checking for `:set spell`: a word list loaded from `spellfile` (one word per line,
+//! like `/usr/share/dict/words`) and detection of words missing from it.
+
+use std::collections::HashSet;
+use std::io;
+use std::ops::Range;
+use std::path::Path;
+
+/// Known words, lowercased so lookups are case-insensitive
+pub struct Dictionary {
+    words: HashSet<String>,
+}
+
+impl Dictionary {
+    /// Read a word list file
+    pub fn load(path: &Path) -> io::Result<Self> {
+        Ok(Self::from_words(std::fs::read_to_string(path)?.lines()))
+    }
+
+    /// Build a dictionary from words (surrounding whitespace and empty entries are ignored)
+    pub fn from_words<'a>(words: impl IntoIterator<Item = &'a str>) -> Self {
+        let words = words
+            .into_iter()
+            .map(str::trim)
+            .filter(|w| !w.is_empty())
+            .map(str::to_lowercase)
+            .collect();
+        Self { words }
+    }
+
+    pub fn contains(&self, word: &str) -> bool {
+        self.words.contains(&word.to_lowercase())
+    }
+}
+
+/// Column ranges of the words in `chars` that are not in `dict`. Words are runs of letters,
+/// possibly joined by apostrophes (don't); tokens mixing in digits or underscores (x86,
+/// snake_case) are not words and are skipped.
+pub fn misspelled(chars: &[char], dict: &Dictionary) -> Vec<Range<usize>> {
+    let is_token_char = |c: char| c.is_alphanumeric() || c == '_' || c == '\'';
+    let mut out = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        if !is_token_char(chars[i]) {
+            i += 1;
+            continue;
+        }
+        let mut start = i;
+        while i < chars.len() && is_token_char(chars[i]) {
+            i += 1;
+        }
+        // Quotes around a word ('like this') are not part of it
+        let mut end = i;
+        while start < end && chars[start] == '\'' {
+            start += 1;
+        }
+        while end > start && chars[end - 1] == '\'' {
+            end -= 1;
+        }
+        let token = &chars[start..end];
+        if token.is_empty() || !token.iter().all(|c| c.is_alphabetic() || *c == '\'') {
+            continue;
+        }
+        let word: String = token.iter().collect();
+        if !dict.contains(&word) {
+            out.push(start..end);
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check(line: &str, words: &[&str]) -> Vec<Range<usize>> {
+        let chars: Vec<char> = line.chars().collect();
+        misspelled(&chars, &Dictionary::from_words(words.iter().copied()))
+    }
+
+    #[test]
+    fn unknown_words_are_reported_by_column() {
+        assert_eq!(check("the quikc fox", &["the", "fox"]), vec![4..9]);
+        assert_eq!(check("teh fox", &["fox"]), vec![0..3]);
+    }
+
+    #[test]
+    fn lookups_ignore_case() {
+        assert!(check("The FOX", &["the", "Fox"]).is_empty());
+    }
+
+    #[test]
+    fn apostrophes_join_words_but_quotes_do_not() {
+        assert!(check("don't", &["don't"]).is_empty());
+        assert_eq!(check("'wrod'", &[]), vec![1..5]);
+    }
+
+    #[test]
+    fn identifiers_and_numbers_are_skipped() {
+        assert!(check("x86 snake_case 42", &[]).is_empty());
+    }
+
+    #[test]
+    fn blank_entries_in_the_word_list_are_ignored() {
+        let dict = Dictionary::from_words(["  word  ", "", "   "]);
+        assert!(dict.contains("word"));
+        assert!(!dict.contains(""));
+    }
+}
@@ -0,0 +1,167 @@
+//! Status bar format expansion (the `statusline` option).
+//!
+//! Placeholders:
+//! - `%f` file name (or path, see the `statusline_path` option), `%m` file flags (`[New]`,
+//!   `[RO]` with `readonly`, `[BOM]`, `[noeol]` when the file's last line had no line break,
+//!   `[+]`)
+//! - `%l` / `%c` cursor line / column (1-based), `%p` percentage through the file
+//! - `%y` filetype (`[rust]`, empty when unknown)
+//! - `%b` git branch, `%n` buffer position (` (2/3)`, empty with a single buffer)
+//! - `%=` separates the left-aligned and right-aligned halves, `%%` is a literal `%`
+//!
+//! Unknown placeholders are rendered literally.
+
+use std::path::Path;
+
+use crate::editor::Editor;
+use crate::options::PathDisplay;
+
+/// Expand a format string into (left, right) halves, split at the first `%=`.
+pub fn expand(format: &str, editor: &Editor) -> (String, String) {
+    let mut left = String::new();
+    let mut right = String::new();
+    let mut on_right = false;
+    let mut chars = format.chars();
+    while let Some(c) = chars.next() {
+        let out = if on_right { &mut right } else { &mut left };
+        if c != '%' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('=') if !on_right => on_right = true,
+            Some(spec) => match placeholder(spec, editor) {
+                Some(text) => out.push_str(&text),
+                None => {
+                    out.push('%');
+                    out.push(spec);
+                }
+            },
+            None => out.push('%'),
+        }
+    }
+    (left, right)
+}
+
+/// Value of a single placeholder, or None if `spec` is not a known placeholder
+fn placeholder(spec: char, editor: &Editor) -> Option<String> {
+    let buffer = editor.current_buffer();
+    let text = match spec {
+        '%' => "%".to_string(),
+        'f' => match &buffer.file_path {
+            Some(path) => {
+                let cwd = std::env::current_dir().unwrap_or_default();
+                display_path(path, editor.options.statusline_path, &cwd)
+            }
+            None => "[No Name]".to_string(),
+        },
+        'm' => {
+            let new = if buffer.is_new { "[New]" } else { "" };
+            let readonly = if editor.options.readonly { "[RO]" } else { "" };
+            let bom = if buffer.has_bom { "[BOM]" } else { "" };
+            let noeol = if buffer.eol { "" } else { "[noeol]" };
+            let modified = if buffer.modified { "[+]" } else { "" };
+            format!("{}{}{}{}{}", new, readonly, bom, noeol, modified)
+        }
+        'l' => (editor.cursor.line + 1).to_string(),
+        'c' => (editor.cursor.col + 1).to_string(),
+        'p' => {
+            let lines = buffer.line_count().max(1);
+            format!("{}%", (editor.cursor.line + 1) * 100 / lines)
+        }
+        'y' => buffer
+            .filetype
+            .as_ref()
+            .map(|ft| format!("[{}]", ft))
+            .unwrap_or_default(),
+        'b' => editor.git_branch.clone().unwrap_or_default(),
+        'n' => {
+            if editor.buffers.len() > 1 {
+                format!(" ({}/{})", editor.current_buf + 1, editor.buffers.len())
+            } else {
+                String::new()
+            }
+        }
+        _ => return None,
+    };
+    Some(text)
+}
+
+/// `path` as shown by `%f`. Relative paths are taken to be relative to `cwd`; a relative display
+/// of a file outside `cwd` falls back to the absolute path.
+fn display_path(path: &Path, mode: PathDisplay, cwd: &Path) -> String {
+    let absolute = cwd.join(path);
+    let shown = match mode {
+        PathDisplay::Name => path.file_name().map(Path::new).unwrap_or(path),
+        PathDisplay::Relative => absolute.strip_prefix(cwd).unwrap_or(&absolute),
+        PathDisplay::Absolute => &absolute,
+    };
+    shown.to_string_lossy().into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn editor(text: &str) -> Editor {
+        let mut editor = Editor::new();
+        editor.buffers[0].insert_str(0, 0, text);
+        editor.buffers[0].modified = false;
+        editor.git_branch = None;
+        editor
+    }
+
+    #[test]
+    fn expands_position_placeholders() {
+        let mut editor = editor("a\nb\nc\nd\n");
+        editor.cursor.line = 1;
+        editor.cursor.col = 0;
+        assert_eq!(
+            expand("%l:%c %p", &editor),
+            ("2:1 40%".to_string(), String::new())
+        );
+    }
+
+    #[test]
+    fn splits_at_the_alignment_marker() {
+        let editor = editor("");
+        assert_eq!(
+            expand(" %f %= %l ", &editor),
+            (" [No Name] ".to_string(), " 1 ".to_string())
+        );
+    }
+
+    #[test]
+    fn file_flags_and_filetype() {
+        let mut editor = editor("x\n");
+        editor.buffers[0].modified = true;
+        editor.buffers[0].filetype = Some("rust".to_string());
+        assert_eq!(expand("%m%y", &editor).0, "[+][rust]");
+        editor.buffers[0].modified = false;
+        editor.buffers[0].filetype = None;
+        assert_eq!(expand("%m%y", &editor).0, "");
+        editor.options.readonly = true;
+        assert_eq!(expand("%m", &editor).0, "[RO]");
+    }
+
+    #[test]
+    fn unknown_placeholders_render_literally() {
+        let editor = editor("");
+        assert_eq!(expand("%q 100%% %", &editor).0, "%q 100% %");
+    }
+
+    #[test]
+    fn path_display_modes() {
+        let cwd = PathBuf::from("/home/me/project");
+        let path = PathBuf::from("src/main.rs");
+        assert_eq!(display_path(&path, PathDisplay::Name, &cwd), "main.rs");
+        assert_eq!(display_path(&path, PathDisplay::Relative, &cwd), "src/main.rs");
+        assert_eq!(
+            display_path(&path, PathDisplay::Absolute, &cwd),
+            "/home/me/project/src/main.rs"
+        );
+        let outside = PathBuf::from("/etc/hosts");
+        assert_eq!(display_path(&outside, PathDisplay::Relative, &cwd), "/etc/hosts");
+    }
+}
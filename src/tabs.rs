@@ -0,0 +1,75 @@
+//! Tab stop arithmetic shared by `:retab`, rendering and cursor placement.
+
+/// Screen column reached after drawing `ch` at screen column `col`
+pub fn advance(col: usize, ch: char, tabstop: usize) -> usize {
+    if ch == '\t' {
+        (col / tabstop + 1) * tabstop
+    } else {
+        col + 1
+    }
+}
+
+/// Screen width of `chars` drawn from column 0
+pub fn display_width(chars: impl IntoIterator<Item = char>, tabstop: usize) -> usize {
+    chars.into_iter().fold(0, |col, ch| advance(col, ch, tabstop))
+}
+
+/// `chars` drawn from screen column `col`, with each tab replaced by the spaces reaching the
+/// next tab stop
+pub fn expand(chars: &[char], col: usize, tabstop: usize) -> String {
+    let mut out = String::new();
+    let mut col = col;
+    for &ch in chars {
+        let next = advance(col, ch, tabstop);
+        if ch == '\t' {
+            out.extend(std::iter::repeat_n(' ', next - col));
+        } else {
+            out.push(ch);
+        }
+        col = next;
+    }
+    out
+}
+
+/// Whitespace covering screen columns `start..end`: tabs wherever a whole tab fits
+/// (unless `expandtab`), then spaces for the remainder.
+pub fn fill_whitespace(start: usize, end: usize, tabstop: usize, expandtab: bool) -> String {
+    let mut out = String::new();
+    let mut col = start;
+    if !expandtab {
+        while (col / tabstop + 1) * tabstop <= end {
+            out.push('\t');
+            col = (col / tabstop + 1) * tabstop;
+        }
+    }
+    out.extend(std::iter::repeat_n(' ', end.saturating_sub(col)));
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tabs_advance_to_the_next_stop() {
+        assert_eq!(advance(0, '\t', 4), 4);
+        assert_eq!(advance(3, '\t', 4), 4);
+        assert_eq!(advance(4, '\t', 4), 8);
+        assert_eq!(advance(4, 'x', 4), 5);
+        assert_eq!(display_width("a\tb".chars(), 8), 9);
+    }
+
+    #[test]
+    fn expand_depends_on_the_start_column() {
+        assert_eq!(expand(&['\t', 'x'], 0, 4), "    x");
+        assert_eq!(expand(&['\t', 'x'], 2, 4), "  x");
+    }
+
+    #[test]
+    fn fill_whitespace_with_and_without_expandtab() {
+        assert_eq!(fill_whitespace(0, 10, 4, false), "\t\t  ");
+        assert_eq!(fill_whitespace(2, 10, 4, false), "\t\t  ");
+        assert_eq!(fill_whitespace(0, 10, 4, true), " ".repeat(10));
+        assert_eq!(fill_whitespace(0, 3, 4, false), "   ");
+    }
+}
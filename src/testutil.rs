@@ -0,0 +1,27 @@
+//! Fixtures shared by the unit tests.
+
+use std::path::PathBuf;
+
+use crate::buffer::Buffer;
+
+/// An empty scratch directory for one test
+pub fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("vibevim-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// A file (`f.txt`) in a fresh scratch directory holding `contents`
+pub fn temp_file(name: &str, contents: impl AsRef<[u8]>) -> PathBuf {
+    let path = temp_dir(name).join("f.txt");
+    std::fs::write(&path, contents).unwrap();
+    path
+}
+
+/// An unnamed buffer holding `text`
+pub fn buffer(text: &str) -> Buffer {
+    let mut buffer = Buffer::new();
+    buffer.insert_str(0, 0, text);
+    buffer
+}
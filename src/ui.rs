@@ -1,19 +1,65 @@
+use std::ops::Range;
+
 use ratatui::{
     layout::{Constraint, Direction, Layout, Rect},
     style::{Color, Modifier, Style},
     text::{Line, Span},
-    widgets::{Block, Borders, Paragraph},
+    widgets::{Block, Borders, Clear, Paragraph},
     Frame,
 };
 
 use crate::app::App;
-use crate::editor::Editor;
+use crate::editor::{self, Editor};
 use crate::mode::Mode;
+use crate::spell;
+use crate::statusline;
+use crate::tabs;
 
 /// The width reserved for line numbers
 const LINE_NUMBER_WIDTH: u16 = 6;
 /// Width of the file explorer sidebar when visible
 const SIDEBAR_WIDTH: u16 = 24;
+/// Text selected in visual mode
+const VISUAL_STYLE: Style = Style::new().bg(Color::Gray).fg(Color::Black);
+
+/// Keys listed in the F1 cheat sheet overlay. Written by hand to match the bindings in
+/// input.rs, since keys are not remappable yet (keybinds.rs is not part of the build)
+const CHEAT_SHEET: &[(&str, &str)] = &[
+    ("h j k l", "move left / down / up / right"),
+    ("w b e", "next word / previous word / end of word"),
+    ("]w [w", "next / previous subword"),
+    ("0 ^ $", "line start / first non-blank / line end"),
+    ("Home", "first non-blank, again for column 0"),
+    ("End", "line end"),
+    ("Ctrl+f Ctrl+b", "page down / up (also PageDown PageUp)"),
+    ("gg G", "first / last line"),
+    ("{ }", "previous / next paragraph"),
+    ("%", "matching bracket or keyword pair"),
+    ("[{ ]}", "enclosing block start / end"),
+    ("]s [s", "next / previous misspelled word"),
+    ("g; g,", "older / newer change"),
+    ("/ n N", "search / next match / previous match"),
+    ("i a I A", "insert before / after / at line start / at line end"),
+    ("o O", "open line below / above"),
+    ("x D dd J gS", "delete char / to line end / line; join / split line"),
+    ("r gcc", "replace char; toggle line comment"),
+    ("u Ctrl+r", "undo / redo (an insert session is one change)"),
+    ("v", "visual mode: move to select, then d/x delete, y yank, o other end"),
+    ("\"_d Space d", "visual: delete without overwriting the register"),
+    ("ga", "show the character code under the cursor"),
+    ("Ctrl+l", "repaint the screen"),
+    ("za zo zc", "toggle / open / close fold"),
+    ("zR zM zr zm", "open all / close all / open / close a level"),
+    ("Ctrl+o", "one normal-mode command from insert mode"),
+    ("Ctrl+v", "insert the next key or a u00e9 code literally"),
+    (":w :q :x", "write / quit / write if modified and quit"),
+    ("ZZ ZQ", "write if modified and quit / quit without saving"),
+    (":set", "change options (:set name=value, :set noname)"),
+    ("Space e", "toggle the file explorer"),
+    ("Space f", "show the current file in the explorer"),
+    ("Ctrl+w w", "switch focus between editor and explorer"),
+    ("m t", "explorer: mark entry / open marked (or selected) files"),
+];
 
 /// Render the editor UI (with optional file explorer sidebar)
 pub fn render(frame: &mut Frame, app: &mut App) {
@@ -36,13 +82,34 @@ pub fn render(frame: &mut Frame, app: &mut App) {
             } else {
                 Style::default()
             };
+            // Current directory as the title; borders and padding take 4 columns
+            let title = dir.cwd_title(SIDEBAR_WIDTH.saturating_sub(4) as usize);
             let block = Block::default()
-                .title(" Files ")
+                .title(format!(" {} ", title))
                 .borders(Borders::ALL)
                 .border_style(border_style);
             let inner = block.inner(sidebar_area);
             frame.render_widget(&block, sidebar_area);
             frame.render_widget(&widget, inner);
+            // Marked entries; the list scrolls just enough to show the selection
+            let explorer = dir.file_explorer();
+            let last_row = inner.height.saturating_sub(1) as usize;
+            let offset = explorer.selected_idx().saturating_sub(last_row);
+            let marked = Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD);
+            for (idx, file) in explorer.files().iter().enumerate().skip(offset) {
+                let row = (idx - offset) as u16;
+                if row >= inner.height {
+                    break;
+                }
+                if dir.is_marked(file.path()) {
+                    let row_area = Rect {
+                        y: inner.y + row,
+                        height: 1,
+                        ..inner
+                    };
+                    frame.buffer_mut().set_style(row_area, marked);
+                }
+            }
         }
         horizontal[1]
     } else {
@@ -72,6 +139,41 @@ pub fn render(frame: &mut Frame, app: &mut App) {
 
     // Position the cursor
     position_cursor(frame, editor, chunks[0], main_rect);
+
+    if app.help_visible {
+        render_cheat_sheet(frame, size);
+    }
+}
+
+/// Render the F1 key cheat sheet as a centered popup over everything else
+fn render_cheat_sheet(frame: &mut Frame, area: Rect) {
+    let key_width = CHEAT_SHEET.iter().map(|(k, _)| k.len()).max().unwrap_or(0);
+    let lines: Vec<Line> = CHEAT_SHEET
+        .iter()
+        .map(|(keys, action)| {
+            Line::from(vec![
+                Span::styled(
+                    format!(" {:width$}  ", keys, width = key_width),
+                    Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD),
+                ),
+                Span::raw(*action),
+            ])
+        })
+        .collect();
+    let content_width = lines.iter().map(|l| l.width()).max().unwrap_or(0) as u16 + 1;
+    let width = (content_width + 2).min(area.width);
+    let height = (lines.len() as u16 + 2).min(area.height);
+    let popup = Rect {
+        x: area.x + (area.width - width) / 2,
+        y: area.y + (area.height - height) / 2,
+        width,
+        height,
+    };
+    let block = Block::default()
+        .title(" Keys (any key to close) ")
+        .borders(Borders::ALL);
+    frame.render_widget(Clear, popup);
+    frame.render_widget(Paragraph::new(lines).block(block), popup);
 }
 
 /// Render the main text editing area with line numbers
@@ -92,12 +194,52 @@ fn render_text_area(frame: &mut Frame, editor: &mut Editor, area: Rect) {
     let visible_height = content_area.height as usize;
     editor.adjust_viewport_with_height(visible_height);
 
-    let start_line = editor.viewport_offset;
-    let end_line = (start_line + visible_height).min(editor.current_buffer().line_count());
+    let line_count = editor.current_buffer().line_count();
+
+    // Buffer lines shown on screen; a closed fold takes a single row
+    let mut visible = Vec::new();
+    let mut line_idx = editor.viewport_offset;
+    while line_idx < line_count && visible.len() < visible_height {
+        let fold = editor.folds.closed_at(line_idx);
+        visible.push((line_idx, fold));
+        line_idx = fold.map(|f| f.end + 1).unwrap_or(line_idx + 1);
+    }
+
+    // Cursor line background, set first so text styles layer on top of it
+    if editor.options.cursorline {
+        let cursor_line = editor.cursor.line;
+        let row = visible.iter().position(|&(line_idx, fold)| match fold {
+            Some(f) => f.contains(cursor_line),
+            None => line_idx == cursor_line,
+        });
+        if let Some(row) = row {
+            let style = Style::default().bg(editor.options.cursorlinecolor);
+            for area in [line_numbers_area, content_area] {
+                let row_area = Rect {
+                    y: area.y + row as u16,
+                    height: 1,
+                    ..area
+                };
+                frame.buffer_mut().set_style(row_area, style);
+            }
+        }
+    }
+
+    // Rulers at `colorcolumn`, on text rows only (not the `~` filler), over the cursor line
+    let ruler = Style::default().bg(Color::DarkGray);
+    for x in editor.options.colorcolumn.iter().filter_map(|&col| column_x(content_area, col)) {
+        let ruler_area = Rect {
+            x,
+            width: 1,
+            height: visible.len() as u16,
+            ..content_area
+        };
+        frame.buffer_mut().set_style(ruler_area, ruler);
+    }
 
     // Render line numbers
     let mut line_number_lines = Vec::new();
-    for line_idx in start_line..end_line {
+    for &(line_idx, _) in &visible {
         let num_str = format!("{:>4} ", line_idx + 1);
         let style = if line_idx == editor.cursor.line {
             Style::default().fg(Color::Yellow)
@@ -108,7 +250,7 @@ fn render_text_area(frame: &mut Frame, editor: &mut Editor, area: Rect) {
     }
 
     // Fill remaining lines with tildes (like vim)
-    for _ in end_line..start_line + visible_height {
+    for _ in visible.len()..visible_height {
         line_number_lines.push(Line::from(Span::styled(
             "    ~ ",
             Style::default().fg(Color::Blue),
@@ -118,17 +260,54 @@ fn render_text_area(frame: &mut Frame, editor: &mut Editor, area: Rect) {
     let line_numbers = Paragraph::new(line_number_lines);
     frame.render_widget(line_numbers, line_numbers_area);
 
-    // Render text content
+    // Render text content, marking misspelled words when spell checking is on and the word
+    // under the cursor with `cursorword`
+    let dict = editor.spell_dictionary();
+    let cursor_word = editor
+        .options
+        .cursorword
+        .then(|| editor.word_under_cursor())
+        .flatten();
     let mut content_lines = Vec::new();
-    for line_idx in start_line..end_line {
+    for &(line_idx, fold) in &visible {
         if let Some(line) = editor.current_buffer().line(line_idx) {
             let line_str: String = line.chars().filter(|c| *c != '\n').collect();
-            content_lines.push(Line::from(line_str));
+            match fold {
+                Some(f) => content_lines.push(Line::from(Span::styled(
+                    format!("+--{:>3} lines: {}", f.hidden_len() + 1, line_str.trim()),
+                    Style::default().fg(Color::Cyan).bg(Color::DarkGray),
+                ))),
+                None => {
+                    let chars: Vec<char> = line_str.chars().collect();
+                    let mut styles = vec![Style::default(); chars.len()];
+                    if let Some(word) = &cursor_word {
+                        let occurrence = Style::default().add_modifier(Modifier::UNDERLINED);
+                        let ranges = editor::whole_word_matches(&chars, word);
+                        highlight(&mut styles, ranges, occurrence);
+                    }
+                    if let Some(dict) = &dict {
+                        let misspelled = Style::default()
+                            .fg(Color::Red)
+                            .add_modifier(Modifier::UNDERLINED);
+                        highlight(&mut styles, spell::misspelled(&chars, dict), misspelled);
+                    }
+                    let selection = editor.visual_cols(line_idx);
+                    if let Some((cols, _)) = &selection {
+                        highlight(&mut styles, vec![cols.clone()], VISUAL_STYLE);
+                    }
+                    let mut line = styled_line(&chars, &styles, editor.options.tabstop);
+                    // A selected line break shows as one selected cell past the text
+                    if selection.is_some_and(|(_, line_break)| line_break) {
+                        line.spans.push(Span::styled(" ", VISUAL_STYLE));
+                    }
+                    content_lines.push(line);
+                }
+            }
         }
     }
 
     // Fill remaining lines
-    for _ in end_line..start_line + visible_height {
+    for _ in visible.len()..visible_height {
         content_lines.push(Line::from(""));
     }
 
@@ -136,6 +315,38 @@ fn render_text_area(frame: &mut Frame, editor: &mut Editor, area: Rect) {
     frame.render_widget(content, content_area);
 }
 
+/// Screen x of 1-based text column `column` in the text area `content_area` (which starts
+/// after the gutter), or None when the column is off screen
+fn column_x(content_area: Rect, column: usize) -> Option<u16> {
+    let offset = u16::try_from(column.checked_sub(1)?).ok()?;
+    (offset < content_area.width).then(|| content_area.x + offset)
+}
+
+/// Layer `style` over the chars in `ranges` (later layers win where they overlap)
+fn highlight(styles: &mut [Style], ranges: Vec<Range<usize>>, style: Style) {
+    for range in ranges {
+        for s in &mut styles[range] {
+            *s = s.patch(style);
+        }
+    }
+}
+
+/// A text line with tabs expanded, one span per run of equally styled chars
+fn styled_line(chars: &[char], styles: &[Style], tabstop: usize) -> Line<'static> {
+    let mut spans = Vec::new();
+    let mut col = 0;
+    let mut start = 0;
+    while start < chars.len() {
+        let style = styles[start];
+        let end = (start..chars.len()).find(|&i| styles[i] != style).unwrap_or(chars.len());
+        let text = tabs::expand(&chars[start..end], col, tabstop);
+        col += text.chars().count();
+        spans.push(Span::styled(text, style));
+        start = end;
+    }
+    Line::from(spans)
+}
+
 /// Render the status bar
 fn render_status_bar(frame: &mut Frame, editor: &Editor, area: Rect) {
     let mode_style = match editor.mode {
@@ -143,46 +354,26 @@ fn render_status_bar(frame: &mut Frame, editor: &Editor, area: Rect) {
         Mode::Insert => Style::default().bg(Color::Green).fg(Color::Black),
         Mode::Command => Style::default().bg(Color::Yellow).fg(Color::Black),
         Mode::Search => Style::default().bg(Color::Magenta).fg(Color::White),
+        Mode::Visual => Style::default().bg(Color::Cyan).fg(Color::Black),
     };
 
-    let filename = editor
-        .current_buffer()
-        .filename()
-        .unwrap_or_else(|| "[No Name]".to_string());
-
-    let modified = if editor.current_buffer().modified { "[+]" } else { "" };
-
-    let buf_info = if editor.buffers.len() > 1 {
-        format!(" ({}/{})", editor.current_buf + 1, editor.buffers.len())
+    let mode_text = if editor.insert_normal_pending {
+        " (INSERT) ".to_string()
     } else {
-        String::new()
+        format!(" {} ", editor.mode.as_str())
     };
-
-    let position = format!(
-        "{}:{}{} ",
-        editor.cursor.line + 1,
-        editor.cursor.col + 1,
-        buf_info
-    );
+    let (left, right) = statusline::expand(&editor.options.statusline, editor);
 
     // Calculate available space
-    let mode_text = format!(" {} ", editor.mode.as_str());
-    let file_text = format!(" {}{} ", filename, modified);
-    let left_len = mode_text.len() + file_text.len();
-    let right_len = position.len();
-    let padding = area.width as usize - left_len - right_len;
+    let used = mode_text.chars().count() + left.chars().count() + right.chars().count();
+    let padding = (area.width as usize).saturating_sub(used);
 
+    let info_style = Style::default().bg(Color::DarkGray).fg(Color::White);
     let status_line = Line::from(vec![
         Span::styled(mode_text, mode_style.add_modifier(Modifier::BOLD)),
-        Span::styled(file_text, Style::default().bg(Color::DarkGray).fg(Color::White)),
-        Span::styled(
-            " ".repeat(padding.max(0)),
-            Style::default().bg(Color::DarkGray),
-        ),
-        Span::styled(
-            position,
-            Style::default().bg(Color::DarkGray).fg(Color::White),
-        ),
+        Span::styled(left, info_style),
+        Span::styled(" ".repeat(padding), info_style),
+        Span::styled(right, info_style),
     ]);
 
     let status_bar = Paragraph::new(status_line);
@@ -217,9 +408,9 @@ fn position_cursor(frame: &mut Frame, editor: &Editor, text_area: Rect, main_rec
 
     // Calculate cursor position in text area
     let content_x = main_rect.x + LINE_NUMBER_WIDTH;
-    let visible_line = editor.cursor.line.saturating_sub(editor.viewport_offset);
+    let visible_line = editor.screen_rows_between(editor.viewport_offset, editor.cursor.line);
 
-    let x = content_x + editor.cursor.col as u16;
+    let x = content_x + editor.cursor_screen_col() as u16;
     let y = text_area.y + visible_line as u16;
 
     // Only show cursor if within visible area
@@ -227,3 +418,149 @@ fn position_cursor(frame: &mut Frame, editor: &Editor, text_area: Rect, main_rec
         frame.set_cursor_position((x, y));
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ratatui::{backend::TestBackend, Terminal};
+
+    /// Draw `app` on a `width` x `height` screen and return the cells
+    fn draw_cells(app: &mut App, width: u16, height: u16) -> ratatui::buffer::Buffer {
+        let mut terminal = Terminal::new(TestBackend::new(width, height)).unwrap();
+        terminal.draw(|frame| render(frame, app)).unwrap();
+        terminal.backend().buffer().clone()
+    }
+
+    /// Draw `app` on a `width` x `height` screen and return its rows as text
+    fn draw(app: &mut App, width: u16, height: u16) -> Vec<String> {
+        let buffer = draw_cells(app, width, height);
+        (0..height)
+            .map(|y| (0..width).map(|x| buffer[(x, y)].symbol()).collect())
+            .collect()
+    }
+
+    #[test]
+    fn cheat_sheet_overlay_lists_known_bindings() {
+        let mut app = App::new();
+        app.help_visible = true;
+        let screen = draw(&mut app, 100, 60).join("\n");
+        assert!(screen.contains("Keys (any key to close)"));
+        for (keys, action) in [
+            ("h j k l", "move left / down / up / right"),
+            ("u Ctrl+r", "undo / redo"),
+            ("ZZ ZQ", "quit without saving"),
+        ] {
+            let row = screen.lines().find(|row| row.contains(action)).unwrap();
+            assert!(row.contains(keys), "{:?} not on the row of {:?}", keys, action);
+        }
+    }
+
+    #[test]
+    fn cheat_sheet_is_hidden_by_default() {
+        let mut app = App::new();
+        let screen = draw(&mut app, 100, 60).join("\n");
+        assert!(!screen.contains("Keys (any key to close)"));
+    }
+
+    /// Screen row holding `text` and the column where it starts
+    fn find(screen: &[String], text: &str) -> (usize, usize) {
+        screen
+            .iter()
+            .enumerate()
+            .find_map(|(y, row)| Some((y, row.find(text)?)))
+            .unwrap()
+    }
+
+    #[test]
+    fn tabs_are_drawn_to_the_next_tab_stop() {
+        for tabstop in [4, 8] {
+            let mut app = App::new();
+            app.editor.current_buffer_mut().insert_str(0, 0, "a\tb\n\tcd");
+            app.editor.options.tabstop = tabstop;
+            let screen = draw(&mut app, 40, 10);
+            let (row, a) = find(&screen, "a ");
+            assert_eq!(screen[row][a..].find('b'), Some(tabstop));
+            assert_eq!(screen[row + 1][a..].find('c'), Some(tabstop));
+        }
+    }
+
+    #[test]
+    fn cursor_is_placed_after_expanded_tabs() {
+        let mut app = App::new();
+        app.editor.current_buffer_mut().insert_str(0, 0, "\tx");
+        app.editor.options.tabstop = 4;
+        let mut terminal = Terminal::new(TestBackend::new(40, 10)).unwrap();
+        app.editor.cursor.col = 1;
+        terminal.draw(|frame| render(frame, &mut app)).unwrap();
+        let on_x = terminal.get_cursor_position().unwrap();
+        // In normal mode the cursor sits on the last cell of the tab
+        app.editor.cursor.col = 0;
+        terminal.draw(|frame| render(frame, &mut app)).unwrap();
+        let on_tab = terminal.get_cursor_position().unwrap();
+        assert_eq!(on_x.x - on_tab.x, 1);
+        app.editor.mode = Mode::Insert;
+        terminal.draw(|frame| render(frame, &mut app)).unwrap();
+        assert_eq!(on_x.x - terminal.get_cursor_position().unwrap().x, 4);
+    }
+
+    #[test]
+    fn cursorword_underlines_other_occurrences() {
+        let mut app = App::new();
+        app.editor.current_buffer_mut().insert_str(0, 0, "foo food foo");
+        let (row, x) = find(&draw(&mut app, 40, 10), "foo food");
+        let underlined = |app: &mut App, col: usize| {
+            let cells = draw_cells(app, 40, 10);
+            cells[((x + col) as u16, row as u16)].modifier.contains(Modifier::UNDERLINED)
+        };
+        assert!(!underlined(&mut app, 9));
+        app.editor.options.apply("cursorword").unwrap();
+        assert!(underlined(&mut app, 9));
+        assert!(!underlined(&mut app, 4));
+    }
+
+    #[test]
+    fn cursorline_paints_the_whole_cursor_row() {
+        let mut app = App::new();
+        app.editor.current_buffer_mut().insert_str(0, 0, "first\nsecond");
+        app.editor.cursor.line = 1;
+        let (row, x) = find(&draw(&mut app, 40, 10), "second");
+        let bg = |app: &mut App, x: usize, y: usize| {
+            draw_cells(app, 40, 10)[(x as u16, y as u16)].bg
+        };
+        assert_ne!(bg(&mut app, x + 20, row), Color::Indexed(236));
+        app.editor.options.apply("cursorline").unwrap();
+        // Past the end of the text and in the line number column too
+        for col in [0, x, x + 20] {
+            assert_eq!(bg(&mut app, col, row), Color::Indexed(236));
+        }
+        assert_ne!(bg(&mut app, x, row - 1), Color::Indexed(236));
+        app.editor.options.apply("cursorlinecolor=#102030").unwrap();
+        assert_eq!(bg(&mut app, x, row), Color::Rgb(0x10, 0x20, 0x30));
+    }
+
+    #[test]
+    fn column_x_is_relative_to_the_text_area() {
+        let area = Rect::new(5, 0, 10, 4);
+        assert_eq!(column_x(area, 1), Some(5));
+        assert_eq!(column_x(area, 10), Some(14));
+        assert_eq!(column_x(area, 11), None);
+        assert_eq!(column_x(area, 0), None);
+        assert_eq!(column_x(area, usize::MAX), None);
+    }
+
+    #[test]
+    fn colorcolumn_marks_text_rows_only() {
+        let mut app = App::new();
+        app.editor.current_buffer_mut().insert_str(0, 0, "abc\ndef");
+        app.editor.options.apply("cc=2").unwrap();
+        let screen = draw(&mut app, 40, 10);
+        let (row, x) = find(&screen, "abc");
+        let cells = draw_cells(&mut app, 40, 10);
+        let bg = |x: usize, y: usize| cells[(x as u16, y as u16)].bg;
+        assert_eq!(bg(x + 1, row), Color::DarkGray);
+        assert_eq!(bg(x + 1, row + 1), Color::DarkGray);
+        assert_ne!(bg(x, row), Color::DarkGray);
+        // The `~` filler rows below the text
+        assert_ne!(bg(x + 1, row + 2), Color::DarkGray);
+    }
+}